@@ -0,0 +1,60 @@
+//! Scan throughput on a match-heavy tree: 50,000 `*.pyc` files in 200 directories.
+//! Every entry is a match, which is where a shared result vector would contend.
+//!
+//! Run with `cargo bench --bench traversal`.
+
+use clearcache::cache_types::CacheType;
+use clearcache::traversal::{CacheTraversal, TraversalConfig};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const DIRECTORIES: usize = 200;
+const FILES_PER_DIRECTORY: usize = 250;
+const RUNS: u32 = 5;
+
+fn main() {
+    let fixture = tempfile::TempDir::new().expect("create fixture directory");
+    build_fixture(fixture.path());
+    let patterns: Vec<_> = CacheType::Python
+        .get_patterns()
+        .into_iter()
+        .map(|pattern| (CacheType::Python, pattern))
+        .collect();
+
+    for (name, parallel, respect_clearcacheignore) in [
+        ("sequential", false, true),
+        ("parallel", true, true),
+        ("parallel, no ignore files", true, false),
+    ] {
+        let config = TraversalConfig {
+            parallel,
+            respect_clearcacheignore,
+            ..TraversalConfig::default()
+        };
+        let traversal = CacheTraversal::new(config, patterns.clone());
+
+        let mut best = Duration::MAX;
+        for _ in 0..RUNS {
+            let started = Instant::now();
+            let found = traversal.find_cache_items(fixture.path()).expect("scan fixture");
+            best = best.min(started.elapsed());
+            assert_eq!(found.len(), DIRECTORIES * FILES_PER_DIRECTORY);
+        }
+        println!(
+            "{:<28} {:>8.1} ms  {:>10.0} matches/s",
+            name,
+            best.as_secs_f64() * 1000.0,
+            (DIRECTORIES * FILES_PER_DIRECTORY) as f64 / best.as_secs_f64()
+        );
+    }
+}
+
+fn build_fixture(root: &Path) {
+    for dir in 0..DIRECTORIES {
+        let path = root.join(format!("pkg{}/src", dir));
+        std::fs::create_dir_all(&path).expect("create fixture directory");
+        for file in 0..FILES_PER_DIRECTORY {
+            std::fs::write(path.join(format!("module{}.pyc", file)), b"x").expect("write fixture file");
+        }
+    }
+}
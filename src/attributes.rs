@@ -0,0 +1,174 @@
+use crate::cache_types::{normalize_pattern, CachePattern, CacheType};
+use crate::traversal::matches_glob;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// Per-directory file marking extra paths as caches, e.g. `vendor/bundle cache type=node`
+pub const ATTRIBUTES_FILE: &str = ".clearcacheattributes";
+
+/// Name of the pattern reported for items matched by an attributes file
+pub const ATTRIBUTES_PATTERN: &str = "attributes";
+
+/// One line of an attributes file
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeRule {
+    pub glob: String,
+    pub cache: bool, // `cache`/`cache=true` marks matches; `-cache`/`cache=false` unmarks them
+    pub cache_type: CacheType,
+    pub library: bool, // Needs a reinstall to restore, so only cleaned with --include-libraries
+}
+
+/// Parse `<glob> <attr>...` lines in the style of `.gitattributes`.
+///
+/// Attributes are `cache`, `-cache`, `cache=true|false`, `type=<name>` (default `general`)
+/// and `library`/`library=true|false`. Blank lines and `#` comments are skipped, as are
+/// lines with an unknown type, so one bad rule doesn't disable the rest.
+pub fn parse_attributes(content: &str) -> Vec<AttributeRule> {
+    let mut rules = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let Some(glob) = fields.next() else {
+            continue;
+        };
+        let mut rule = AttributeRule {
+            glob: normalize_pattern(glob),
+            cache: false,
+            cache_type: CacheType::General,
+            library: false,
+        };
+        let mut valid = true;
+        for field in fields {
+            match field.split_once('=') {
+                None if field == "cache" => rule.cache = true,
+                None if field == "-cache" => rule.cache = false,
+                None if field == "library" => rule.library = true,
+                Some(("cache", value)) => rule.cache = value == "true",
+                Some(("library", value)) => rule.library = value == "true",
+                Some(("type", name)) => match CacheType::from_name(name) {
+                    Some(cache_type) => rule.cache_type = cache_type,
+                    None => valid = false,
+                },
+                _ => {}
+            }
+        }
+        if valid {
+            rules.push(rule);
+        }
+    }
+    rules
+}
+
+/// A rule together with the directory its file lives in, which its glob is relative to
+#[derive(Debug, Clone)]
+struct ScopedRule {
+    base: PathBuf,
+    file: PathBuf,
+    rule: AttributeRule,
+}
+
+/// Attribute rules in effect in each directory under a scan root: those of its own file
+/// followed by its ancestors', so deeper files take precedence. Directories are read once.
+#[derive(Debug, Default)]
+pub struct AttributeRules {
+    by_dir: RwLock<HashMap<PathBuf, Arc<Vec<ScopedRule>>>>,
+}
+
+impl AttributeRules {
+    /// The cache type and pattern an attributes file assigns to `path`, if any
+    pub fn match_path(&self, path: &Path, root: &Path, case_sensitive: bool) -> Option<(CacheType, CachePattern)> {
+        let rules = self.rules_for(path.parent()?, root);
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        // The last matching line wins, as in .gitattributes
+        let scoped = rules
+            .iter()
+            .rev()
+            .find(|scoped| matches_glob(path, &scoped.base, &file_name, &scoped.rule.glob, case_sensitive))?;
+        scoped.rule.cache.then(|| {
+            (
+                scoped.rule.cache_type.clone(),
+                CachePattern {
+                    name: ATTRIBUTES_PATTERN.to_string(),
+                    patterns: vec![scoped.rule.glob.clone()],
+                    description: format!("Marked as cache in {}", scoped.file.display()),
+                    is_directory: true,
+                    recursive_safe: true,
+                    is_library: scoped.rule.library,
+                    truncatable: false,
+                },
+            )
+        })
+    }
+
+    fn rules_for(&self, dir: &Path, root: &Path) -> Arc<Vec<ScopedRule>> {
+        if let Some(rules) = self.by_dir.read().unwrap().get(dir) {
+            return rules.clone();
+        }
+        if !dir.starts_with(root) {
+            return Arc::default();
+        }
+
+        let inherited = match dir.parent() {
+            Some(parent) if dir != root => self.rules_for(parent, root),
+            _ => Arc::default(),
+        };
+        let file = dir.join(ATTRIBUTES_FILE);
+        let rules = match std::fs::read_to_string(&file) {
+            Ok(content) => {
+                let mut rules = (*inherited).clone();
+                rules.extend(parse_attributes(&content).into_iter().map(|rule| ScopedRule {
+                    base: dir.to_path_buf(),
+                    file: file.clone(),
+                    rule,
+                }));
+                Arc::new(rules)
+            }
+            Err(_) => inherited,
+        };
+        self.by_dir.write().unwrap().insert(dir.to_path_buf(), rules.clone());
+        rules
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_attributes() {
+        let rules = parse_attributes(
+            "# generated bundles\n\
+             \n\
+             vendor/bundle cache type=node library\n\
+             js-out cache=true type=npm\n\
+             keep-me -cache\n\
+             odd cache type=cobol\n",
+        );
+        assert_eq!(
+            rules,
+            vec![
+                AttributeRule {
+                    glob: "vendor/bundle".to_string(),
+                    cache: true,
+                    cache_type: CacheType::Node,
+                    library: true,
+                },
+                AttributeRule {
+                    glob: "js-out".to_string(),
+                    cache: true,
+                    cache_type: CacheType::Node,
+                    library: false,
+                },
+                AttributeRule {
+                    glob: "keep-me".to_string(),
+                    cache: false,
+                    cache_type: CacheType::General,
+                    library: false,
+                },
+            ]
+        );
+    }
+}
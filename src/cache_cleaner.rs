@@ -1,33 +1,403 @@
-use crate::cache_types::{CachePattern, CacheType};
-use crate::traversal::{CacheTraversal, TraversalConfig};
-use crate::utils::calculate_directory_size;
+use crate::attributes::ATTRIBUTES_PATTERN;
+use crate::cache_types::{normalize_pattern, CachePattern, CacheType};
+use crate::checkpoint::Checkpoint;
+use crate::confirm::{sample_entries, Confirm, StdinConfirm};
+use crate::errors::{
+    CleanError, ErrorKind, ManagedSystemPathError, NestedRepositoryError, ProtectedPathError, VcsDirectoryError,
+    WorkingDirectoryError,
+};
+use crate::git::{changed_directories, is_affected};
+use crate::runner::{CommandRunner, SystemRunner};
+use crate::scan_cache::ScanCache;
+use crate::stores::{cargo_registry_entries, find_references, pnpm_store_entries, StoreReferences, StoreSelection};
+use crate::targets::{Baseline, Target};
+use crate::throttle::{IoLimiter, RateLimiter};
+use crate::trash::{space_for_move, StagingDir, TrashRun};
+use crate::traversal::{matches_pattern_static, CacheTraversal, PathExplanation, TraversalConfig};
+use crate::utils::{
+    available_space, calculate_directory_size, can_delete, filesystem_type, find_nested_repository, is_in_vcs_dir, is_network_filesystem,
+    active_build_marker, is_special_file, managed_by, open_files, records_access_time, DirectorySize, ProtectedPaths,
+};
+#[cfg(windows)]
+use crate::utils::clear_readonly;
 use anyhow::Result;
 use colored::*;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
 use rayon::prelude::*;
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
-use std::time::Instant;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
 
-#[derive(Debug)]
+#[derive(Debug, Default, Clone)]
 pub struct CleanResult {
     pub directories_cleaned: usize,
     pub files_deleted: u64,
     pub space_freed: u64,
-    pub errors: Vec<String>,
+    pub errors: Vec<CleanError>,
+    pub items: Vec<CleanedItem>,
+    pub omitted: Vec<OmittedItems>, // Items past --max-items-per-type, totalled per type
+    pub skipped: Vec<(PathBuf, SkipReason)>, // Matched items left in place, and why
+    pub status: CleanStatus,
+}
+
+/// Why a matched item was left in place
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SkipReason {
+    TooNew,            // Modified within --min-age
+    CompletedEarlier,  // Already cleaned by the interrupted run being resumed
+    NetworkFilesystem, // --skip-network
+    InUse,             // --skip-in-use
+    ActiveBuild,       // --skip-active-builds
+    TooManyFiles,      // --max-file-count
+    TrashFull,         // --exclude-larger-than-free-space
+    Declined,          // Library items the user chose to keep
+    NoAccessTimes,     // --store-max-age on a store mounted noatime
+    VcsMetadata,       // Inside `.git` or another VCS directory
+    NestedRepository,  // Holds a repository; deleted only with --force
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SkipReason::TooNew => "modified too recently",
+            SkipReason::CompletedEarlier => "completed by the interrupted run",
+            SkipReason::NetworkFilesystem => "on a network filesystem",
+            SkipReason::InUse => "in use",
+            SkipReason::ActiveBuild => "build in progress",
+            SkipReason::TooManyFiles => "too many files",
+            SkipReason::TrashFull => "would not fit in the trash",
+            SkipReason::Declined => "declined",
+            SkipReason::NoAccessTimes => "filesystem doesn't record access times (noatime)",
+            SkipReason::VcsMetadata => "version-control metadata",
+            SkipReason::NestedRepository => "contains a repository (use --force to delete)",
+        })
+    }
+}
+
+/// How a run ended. Ordered by precedence, so a multi-root run reports its most severe outcome.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CleanStatus {
+    /// No cache items matched
+    #[default]
+    NothingFound,
+    Success,
+    /// Some items (or Docker, or a store prune) failed; the rest were cleaned
+    CompletedWithErrors,
+    /// The user declined a confirmation prompt, or `--max-total-deletions` would have been
+    /// exceeded; nothing more was deleted
+    Aborted,
+}
+
+impl CleanResult {
+    /// A run stopped before deleting anything, because `reason`
+    fn refused(reason: String, skipped: Vec<(PathBuf, SkipReason)>) -> Self {
+        CleanResult {
+            errors: vec![CleanError::Other(reason)],
+            skipped,
+            status: CleanStatus::Aborted,
+            ..CleanResult::default()
+        }
+    }
+
+    /// Fold another root's result into this one
+    pub fn merge(&mut self, other: CleanResult) {
+        self.directories_cleaned += other.directories_cleaned;
+        self.files_deleted += other.files_deleted;
+        self.space_freed += other.space_freed;
+        self.errors.extend(other.errors);
+        self.items.extend(other.items);
+        self.skipped.extend(other.skipped);
+        for omitted in other.omitted {
+            match self.omitted.iter_mut().find(|o| o.cache_type == omitted.cache_type) {
+                Some(existing) => existing.add(omitted.items, omitted.files, omitted.size),
+                None => self.omitted.push(omitted),
+            }
+        }
+        self.omitted.sort_by(|a, b| a.cache_type.cmp(&b.cache_type));
+        self.status = self.status.max(other.status);
+    }
+}
+
+/// Outcome of a multi-root run: each root's own result, in the order the roots were given
+#[derive(Debug, Default)]
+pub struct RootResults {
+    pub roots: Vec<(PathBuf, CleanResult)>,
+    pub global: CleanResult, // Work that belongs to no single root, such as the cargo registry
+}
+
+impl RootResults {
+    /// One result covering every root, as a single-root run would report it
+    pub fn aggregate(&self) -> CleanResult {
+        let mut aggregate = CleanResult::default();
+        for (_, result) in &self.roots {
+            aggregate.merge(result.clone());
+        }
+        aggregate.merge(self.global.clone());
+        aggregate
+    }
+}
+
+/// Cleaned items of one type that were counted but not listed individually
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OmittedItems {
+    pub cache_type: CacheType,
+    pub items: usize,
+    pub files: u64,
+    pub size: u64,
+}
+
+impl OmittedItems {
+    fn add(&mut self, items: usize, files: u64, size: u64) {
+        self.items += items;
+        self.files += files;
+        self.size += size;
+    }
+}
+
+/// A cache item that was cleaned (or would be, in dry-run mode)
+#[derive(Debug, Clone, Serialize)]
+pub struct CleanedItem {
+    pub path: PathBuf,
+    pub cache_type: CacheType,
+    pub pattern_name: String,
+    pub is_library: bool,
+    pub files: u64,
+    pub size: u64,          // Bytes freed: disk usage, or logical length with --apparent-size
+    pub apparent_size: u64, // Sum of file lengths, as `du --apparent-size` reports
+    pub modified: Option<u64>, // Seconds since the Unix epoch
+    pub duration_secs: f64,    // Time spent measuring and deleting the item
+    pub size_known: bool,      // False when sizing was deferred in a dry-run preview
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<bool>, // With --baseline: missing from the earlier run's report
+}
+
+#[derive(Debug, Clone)]
+pub struct CleanerConfig {
+    pub parallel_threads: usize,
+    pub recursive: bool,
+    pub max_depth: usize, // How deep a recursive scan descends below the root
+    pub dry_run: bool,
+    pub verbose: bool,
+    pub include_libraries: bool,
+    pub no_ignore: bool,
+    pub respect_gitignore: bool,
+    pub follow_links: bool, // Follow symlinks while scanning; matched links are emptied in place
+    pub no_canonicalize: bool, // Dedup by logical path instead of resolving each match
+    pub case_insensitive: bool, // Match file names regardless of case, as case-insensitive filesystems do
+    pub pattern_names: Vec<String>, // Individual patterns enabled outside of cache_types
+    pub scan_cache: Option<PathBuf>, // Scan cache file; None disables subtree skipping
+    pub scan_cache_ttl: Duration,
+    pub xdg: bool, // Root is $XDG_CACHE_HOME; match known tool cache subdirectories
+    pub clear_readonly: bool, // Windows: clear read-only attributes and retry on PermissionDenied
+    pub truncate_logs: bool, // Empty files of truncatable patterns in place instead of deleting them
+    pub stage_deletes: bool, // Rename items into a staging directory under the root, then delete it at the end
+    pub reset_locks: bool, // Also remove lockfiles of selected types (requires include_libraries)
+    pub list_items: bool, // Print a line per deleted item (dry-run/verbose); off for tree/JSON output
+    pub io_concurrency: Option<usize>, // Max concurrent deletions; None means one per thread
+    pub protected_paths: ProtectedPaths,
+    pub confirm_large: Option<u64>, // Ask before deleting when the total exceeds this many bytes
+    pub targets: Option<Vec<Target>>, // Clean exactly these items instead of scanning
+    pub extra_patterns: Vec<(CacheType, CachePattern)>, // From a --pattern-provider
+    pub defer_sizing: bool, // Dry-run only: list matches first, size them only if confirmed
+    pub skip_network: bool, // Leave items on network filesystems alone instead of only warning
+    pub global_ignore: Option<PathBuf>, // Per-user ignore file applied in addition to .clearcacheignore
+    pub apparent_size: bool, // Report logical file lengths instead of disk usage
+    pub path_globs: Vec<String>, // Globs matched against the path relative to the root
+    pub trash_dir: Option<PathBuf>, // Move items here (undoable) instead of deleting them
+    pub since_commit: Option<String>, // Only clean caches near files changed since this git ref
+    pub skip_in_use: bool, // Linux: leave items with files held open by a process alone
+    pub skip_active_builds: bool, // Leave directories alone while their build tool's lockfile is held
+    pub docker: bool, // Run `docker system/volume prune`; only set when Docker is asked for by name
+    pub dedup_stores: bool, // Prune unreferenced pnpm store and cargo registry entries instead of whole stores
+    pub store_max_age: Option<Duration>, // Prune store entries unused for this long instead of whole stores
+    pub cargo_registry: Option<PathBuf>, // Also prune this shared registry, once per run (--prune-cargo-registry)
+    pub check_permissions: bool, // Dry-run only: flag items the real run would fail to delete
+    pub confirm_libraries: bool, // Ask once before deleting library items; safe caches don't wait
+    pub priority: Vec<CacheType>, // Clean these types first, in order, each finishing before the next starts
+    pub docker_wait: Duration, // How long to wait for a Docker daemon that isn't answering yet
+    pub max_items_per_type: Option<usize>, // List at most this many items per type; the rest are totalled
+    pub check_free_space: bool, // Skip items the trash can't hold when moving them there means copying
+    pub checkpoint_dir: Option<PathBuf>, // Record completed items here so an interrupted run can be resumed
+    pub resume: bool, // Skip items an interrupted run recorded as completed
+    pub max_file_count: Option<u64>, // Skip matched directories holding more files than this
+    pub count_only: bool, // Only total the matches: nothing is deleted or listed
+    pub target_free: Option<u64>, // Clean one item at a time until the filesystem has this much free space
+    pub order: CleanOrder, // Which items --target-free cleans first
+    pub min_age: Option<Duration>, // Skip items modified more recently than this
+    pub parallel_roots: usize, // How many roots `clean_roots` cleans at once
+    pub throttle: Option<u64>, // Delete at most this many bytes per second, across all threads
+    pub preserve_skeleton: bool, // Recreate matched directories empty after deleting them, for tools that need them to exist
+    pub allow_nested_repos: bool, // Delete matched directories even when a repository lives inside them
+    pub use_native_clean: bool, // Let `cargo clean` and `go clean -cache` remove their own output, falling back to deletion
+    pub sequential: bool, // --no-parallel: scan, size and delete on one thread, in path order
+    pub max_total_deletions: Option<usize>, // Fail instead of deleting more than this many items in the whole run
+    pub baseline: Option<Arc<Baseline>>, // Items of an earlier run's report; others are marked new
+    pub archive_dir: Option<PathBuf>, // Compress matched directories into `.tar.zst` archives here before deleting them
+    pub progress_target: ProgressOutput, // Where spinners are drawn; every root of a run shares one group there
+}
+
+/// A [`ProgressTarget`] held in [`CleanerConfig`], which prints as a placeholder so the config stays `Debug`
+#[derive(Clone)]
+pub struct ProgressOutput(pub ProgressTarget);
+
+impl Default for ProgressOutput {
+    fn default() -> Self {
+        Self(Arc::new(ProgressDrawTarget::stderr))
+    }
+}
+
+impl std::fmt::Debug for ProgressOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressOutput(..)")
+    }
+}
+
+/// Which items a `--target-free` run cleans first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CleanOrder {
+    #[default]
+    Largest,
+    Oldest, // By modification time; items without one go last
+}
+
+impl Default for CleanerConfig {
+    fn default() -> Self {
+        Self {
+            parallel_threads: num_cpus::get(),
+            recursive: false,
+            max_depth: 20,
+            dry_run: false,
+            verbose: false,
+            include_libraries: false,
+            no_ignore: false,
+            respect_gitignore: false,
+            follow_links: false,
+            no_canonicalize: false,
+            case_insensitive: false,
+            pattern_names: Vec::new(),
+            scan_cache: None,
+            scan_cache_ttl: Duration::from_secs(3600),
+            xdg: false,
+            clear_readonly: false,
+            truncate_logs: false,
+            stage_deletes: false,
+            reset_locks: false,
+            list_items: true,
+            io_concurrency: None,
+            protected_paths: ProtectedPaths::default(),
+            confirm_large: None,
+            targets: None,
+            extra_patterns: Vec::new(),
+            defer_sizing: false,
+            skip_network: false,
+            global_ignore: None,
+            apparent_size: false,
+            path_globs: Vec::new(),
+            trash_dir: None,
+            since_commit: None,
+            skip_in_use: false,
+            skip_active_builds: false,
+            docker: false,
+            dedup_stores: false,
+            store_max_age: None,
+            cargo_registry: None,
+            check_permissions: false,
+            confirm_libraries: false,
+            priority: Vec::new(),
+            docker_wait: Duration::from_secs(10),
+            max_items_per_type: None,
+            check_free_space: false,
+            checkpoint_dir: None,
+            resume: false,
+            max_file_count: None,
+            count_only: false,
+            target_free: None,
+            order: CleanOrder::Largest,
+            min_age: None,
+            parallel_roots: 1,
+            throttle: None,
+            preserve_skeleton: false,
+            allow_nested_repos: false,
+            use_native_clean: false,
+            sequential: false,
+            max_total_deletions: None,
+            baseline: None,
+            archive_dir: None,
+            progress_target: ProgressOutput::default(),
+        }
+    }
 }
 
 pub struct CacheCleaner {
     root_directory: PathBuf,
     cache_types: Vec<CacheType>,
-    parallel_threads: usize,
-    recursive: bool,
-    dry_run: bool,
-    verbose: bool,
-    include_libraries: bool,
-    no_ignore: bool,
-    respect_gitignore: bool,
+    config: CleanerConfig,
+    io_limiter: Option<IoLimiter>,
+    rate_limiter: Option<RateLimiter>,
+    confirmer: Arc<dyn Confirm>,
+    filesystem_probe: FilesystemProbe,
+    free_space_probe: FreeSpaceProbe,
+    disk_space_probe: DiskSpaceProbe,
+    trash: Option<Arc<TrashRun>>,
+    staging: Option<StagingDir>,
+    runner: Arc<dyn CommandRunner>,
+    working_dir: Option<PathBuf>, // Canonical; never deleted, nor anything containing it
+    output: Output,
+    warnings: Output,
+    progress_group: Option<MultiProgress>, // Shared by the roots of a run, so their spinners stack
+    visited: Option<VisitedSet>,
+    deletions: DeletionCount,
+    run_totals: Option<RunTotals>,
+    checkpoint: OnceLock<Checkpoint>,
+    listed: Mutex<HashMap<CacheType, usize>>, // Items collected so far per type, for --max-items-per-type
+    omitted: Mutex<Vec<OmittedItems>>,
+}
+
+/// Where item listings and notices are written; each line is flushed as it is written
+pub type Output = Arc<Mutex<dyn Write + Send>>;
+
+/// Where the spinner is drawn; stderr by default so stdout carries only listings and reports
+pub type ProgressTarget = Arc<dyn Fn() -> ProgressDrawTarget + Send + Sync>;
+
+/// Canonical item paths already claimed by an earlier root of a multi-root run
+pub type VisitedSet = Arc<Mutex<HashSet<PathBuf>>>;
+
+/// What cleaning one chunk of items came to
+struct ChunkOutcome {
+    cleaned: Vec<CleanedItem>,
+    errors: Vec<CleanError>,
+    skipped: Vec<(PathBuf, SkipReason)>, // Refused by a safety check that is working as intended
+}
+
+/// Items every root of a run has set out to delete, counted against `--max-total-deletions`
+pub type DeletionCount = Arc<AtomicUsize>;
+
+/// Bytes and files freed by a whole multi-root run, added to as each root deletes
+type RunTotals = (Arc<AtomicU64>, Arc<AtomicU64>);
+
+/// Reports the filesystem type a path lives on
+pub type FilesystemProbe = Arc<dyn Fn(&Path) -> Option<String> + Send + Sync>;
+
+/// Reports the free space moving an item into a directory would use up (see [`space_for_move`])
+pub type FreeSpaceProbe = Arc<dyn Fn(&Path, &Path) -> Option<u64> + Send + Sync>;
+
+/// Reports the free space on the filesystem holding a path (see [`available_space`])
+pub type DiskSpaceProbe = Arc<dyn Fn(&Path) -> Option<u64> + Send + Sync>;
+
+/// What a scan found under one root, with the items to leave in place already set aside
+struct CleanPlan {
+    started: Instant,
+    tasks: Vec<CleanTask>,
+    skipped: Vec<(PathBuf, SkipReason)>,
+    errors: Vec<CleanError>,
+    scan_cache: Option<(ScanCache, String)>, // With the pattern fingerprint to record it under
+    progress: ProgressBar,
 }
 
 #[derive(Debug, Clone)]
@@ -35,31 +405,130 @@ struct CleanTask {
     path: PathBuf,
     pattern: CachePattern,
     cache_type: CacheType,
+    modified: Option<SystemTime>,
+    sized: Option<DirectorySize>, // When measured ahead of deletion
 }
 
 impl CacheCleaner {
-    pub fn new(
-        root_directory: PathBuf,
-        cache_types: Vec<CacheType>,
-        parallel_threads: usize,
-        recursive: bool,
-        dry_run: bool,
-        verbose: bool,
-        include_libraries: bool,
-        no_ignore: bool,
-        respect_gitignore: bool,
-    ) -> Self {
+    pub fn new(root_directory: PathBuf, cache_types: Vec<CacheType>, config: CleanerConfig) -> Self {
+        let io_limiter = config.io_concurrency.map(IoLimiter::new);
+        let rate_limiter = config.throttle.map(RateLimiter::new);
+        let trash = config
+            .trash_dir
+            .as_deref()
+            .filter(|_| !config.dry_run)
+            .map(|dir| Arc::new(TrashRun::new(dir)));
+        let staging = (config.stage_deletes && !config.dry_run && trash.is_none()).then(|| StagingDir::new(&root_directory));
         Self {
             root_directory,
             cache_types,
-            parallel_threads,
-            recursive,
-            dry_run,
-            verbose,
-            include_libraries,
-            no_ignore,
-            respect_gitignore,
+            config,
+            io_limiter,
+            rate_limiter,
+            confirmer: Arc::new(StdinConfirm),
+            filesystem_probe: Arc::new(filesystem_type),
+            free_space_probe: Arc::new(space_for_move),
+            disk_space_probe: Arc::new(available_space),
+            trash,
+            staging,
+            runner: Arc::new(SystemRunner),
+            working_dir: std::env::current_dir().and_then(|dir| dir.canonicalize()).ok(),
+            output: Arc::new(Mutex::new(std::io::stdout())),
+            warnings: Arc::new(Mutex::new(std::io::stderr())),
+            progress_group: None,
+            visited: None,
+            deletions: DeletionCount::default(),
+            run_totals: None,
+            checkpoint: OnceLock::new(),
+            listed: Mutex::default(),
+            omitted: Mutex::default(),
+        }
+    }
+
+    /// Replace how filesystem types are detected (defaults to inspecting mounts)
+    pub fn with_filesystem_probe(mut self, probe: FilesystemProbe) -> Self {
+        self.filesystem_probe = probe;
+        self
+    }
+
+    /// Replace how the trash's free space is measured (defaults to [`space_for_move`])
+    pub fn with_free_space_probe(mut self, probe: FreeSpaceProbe) -> Self {
+        self.free_space_probe = probe;
+        self
+    }
+
+    /// Replace how the root's free space is measured for --target-free (defaults to [`available_space`])
+    pub fn with_disk_space_probe(mut self, probe: DiskSpaceProbe) -> Self {
+        self.disk_space_probe = probe;
+        self
+    }
+
+    /// Replace how external commands such as git are run
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// Replace where listings and notices are written (defaults to stdout)
+    pub fn with_output(mut self, output: Output) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Replace where warnings and notices about skipped items are written (defaults to stderr)
+    pub fn with_warnings(mut self, warnings: Output) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Replace where the spinner is drawn (defaults to stderr)
+    pub fn with_progress_target(mut self, target: ProgressTarget) -> Self {
+        self.config.progress_target = ProgressOutput(target);
+        self
+    }
+
+    /// Replace the directory treated as the process's working directory
+    pub fn with_working_dir(mut self, dir: PathBuf) -> Self {
+        self.working_dir = dir.canonicalize().ok().or(Some(dir));
+        self
+    }
+
+    /// Skip items claimed by another cleaner sharing this set, and claim the rest
+    pub fn with_visited(mut self, visited: VisitedSet) -> Self {
+        self.visited = Some(visited);
+        self
+    }
+
+    /// Move items into this trash run instead of a new one, so one `undo` restores a whole multi-root run
+    pub fn with_trash(mut self, trash: Option<Arc<TrashRun>>) -> Self {
+        if self.trash.is_some() {
+            self.trash = trash;
         }
+        self
+    }
+
+    /// Count deletions against `--max-total-deletions` together with other roots of the run
+    pub fn with_deletion_count(mut self, deletions: DeletionCount) -> Self {
+        self.deletions = deletions;
+        self
+    }
+
+    /// Draw the spinner as one line of `group` rather than on its own
+    fn with_progress_group(mut self, group: MultiProgress) -> Self {
+        self.progress_group = Some(group);
+        self
+    }
+
+    /// Also count what is freed into the totals of the whole run, as it is freed
+    fn with_run_totals(mut self, totals: RunTotals) -> Self {
+        self.run_totals = Some(totals);
+        self
+    }
+
+    /// Replace how confirmation prompts are answered (defaults to asking on stdin)
+    pub fn with_confirmer(mut self, confirmer: Arc<dyn Confirm>) -> Self {
+        self.confirmer = confirmer;
+        self
     }
 
     pub async fn clean(
@@ -67,280 +536,3962 @@ impl CacheCleaner {
         total_size: Arc<AtomicU64>,
         total_files: Arc<AtomicU64>,
     ) -> Result<CleanResult> {
-        let start_time = Instant::now();
+        let plan = self.plan().await?;
+        self.execute(plan, &total_size, &total_files).await
+    }
 
-        // Setup progress bar
-        let progress = ProgressBar::new_spinner();
-        progress.set_style(
-            ProgressStyle::default_spinner()
-                .template("{spinner:.green} {msg}")
-                .unwrap(),
-        );
+    /// Scan the root and set aside every item that is to be left in place. Nothing is deleted.
+    async fn plan(&self) -> Result<CleanPlan> {
+        let started = Instant::now();
+
+        // Everything in a Nix store or Homebrew prefix belongs to an installed package
+        if let Some(manager) = managed_by(&self.root_directory) {
+            return Err(ManagedSystemPathError(manager).into());
+        }
+
+        let progress = self.progress_bar();
         progress.set_message("Scanning directories...");
 
-        // Collect patterns based on include_libraries flag
-        let mut all_patterns = Vec::new();
-        for cache_type in &self.cache_types {
-            let patterns = if self.include_libraries {
-                // Include all patterns (both safe caches and libraries)
-                cache_type.get_patterns()
-            } else {
-                // Only include safe patterns (exclude libraries)
-                cache_type.get_safe_patterns()
-            };
-            
-            for pattern in patterns {
-                all_patterns.push((cache_type.clone(), pattern));
-            }
+        let all_patterns = self.collect_patterns();
+
+        // Skip subtrees that were clean on a recent run and haven't changed since
+        let fingerprint = self.pattern_fingerprint(&all_patterns);
+        // Explicit targets bypass the scan, so there is nothing to record
+        let scan_cache = match self.config.targets {
+            Some(_) => None,
+            None => self.config.scan_cache.as_deref().map(ScanCache::load),
+        };
+        let skip_paths = scan_cache
+            .as_ref()
+            .map(|cache| cache.fresh_subtrees(&self.root_directory, &fingerprint, self.config.scan_cache_ttl))
+            .unwrap_or_default();
+        if self.config.verbose && !skip_paths.is_empty() {
+            self.emit(format_args!("Skipping {} unchanged subtrees (scan cache)", skip_paths.len()));
         }
 
         // Find all cache directories/files
-        let tasks = self.find_cache_items(&all_patterns, &progress).await?;
-        
-        progress.set_message(format!("Found {} cache items to clean", tasks.len()));
+        let scan_span = tracing::info_span!("scan");
+        let mut tasks = match &self.config.targets {
+            Some(targets) => self.resolve_targets(targets, &all_patterns),
+            None => self.find_cache_items(&all_patterns, skip_paths, &progress).await?,
+        };
+        drop(scan_span);
+        // Directory listing order varies between filesystems; a reproducible run shouldn't
+        if self.config.sequential {
+            tasks.sort_by(|a, b| a.path.cmp(&b.path));
+        }
 
-        if tasks.is_empty() {
-            progress.finish_with_message("No cache items found to clean");
-            return Ok(CleanResult {
-                directories_cleaned: 0,
-                files_deleted: 0,
-                space_freed: 0,
-                errors: Vec::new(),
-            });
+        if let Some(reference) = &self.config.since_commit {
+            match changed_directories(self.runner.as_ref(), &self.root_directory, reference)? {
+                Some(changed) => tasks.retain(|task| {
+                    let path = task.path.canonicalize().unwrap_or_else(|_| task.path.clone());
+                    is_affected(&path, &changed)
+                }),
+                None => progress.suspend(|| {
+                    self.warn(
+                        "Warning:",
+                        format_args!(
+                            "{} is not in a git repository; ignoring --since-commit",
+                            self.root_directory.display(),
+                        ),
+                    )
+                }),
+            }
         }
 
-        // Handle Docker cleaning separately (requires Docker commands)
-        let docker_tasks: Vec<_> = tasks
-            .iter()
-            .filter(|t| t.cache_type == CacheType::Docker)
-            .collect();
+        let mut skipped = Vec::new();
 
-        let file_tasks: Vec<_> = tasks
-            .iter()
-            .filter(|t| t.cache_type != CacheType::Docker)
-            .collect();
+        // A cache written moments ago probably belongs to a build that is still running
+        if let Some(min_age) = self.config.min_age {
+            let now = SystemTime::now();
+            tasks.retain(|task| {
+                let Some(modified) = task.modified else {
+                    return true;
+                };
+                if now.duration_since(modified).unwrap_or_default() >= min_age {
+                    return true;
+                }
+                skipped.push((task.path.clone(), SkipReason::TooNew));
+                false
+            });
+        }
+
+        // Overlapping roots (or symlinks between them) reach the same item more than once
+        if let Some(visited) = &self.visited {
+            let mut visited = visited.lock().unwrap();
+            tasks.retain(|task| {
+                let path = if self.config.no_canonicalize {
+                    task.path.clone()
+                } else {
+                    task.path.canonicalize().unwrap_or_else(|_| task.path.clone())
+                };
+                if visited.iter().any(|claimed| path.starts_with(claimed)) {
+                    return false;
+                }
+                visited.insert(path);
+                true
+            });
+        }
+        
+        if let Some(dir) = self.config.checkpoint_dir.as_deref().filter(|_| !self.config.dry_run) {
+            match Checkpoint::open(dir, &self.root_directory, self.config.resume) {
+                Ok(checkpoint) => {
+                    if checkpoint.completed_count() > 0 {
+                        tasks.retain(|task| {
+                            if !checkpoint.is_completed(&task.path) {
+                                return true;
+                            }
+                            skipped.push((task.path.clone(), SkipReason::CompletedEarlier));
+                            false
+                        });
+                        progress.suspend(|| {
+                            self.emit(format_args!(
+                                "Resuming: {} items were completed by the interrupted run",
+                                checkpoint.completed_count()
+                            ))
+                        });
+                    }
+                    let _ = self.checkpoint.set(checkpoint);
+                }
+                Err(e) => progress.suspend(|| {
+                    self.warn("Warning:", format_args!("cannot write a checkpoint in {}: {}", dir.display(), e))
+                }),
+            }
+        }
 
+        // Content-addressed stores are pruned entry by entry instead of deleted whole
         let mut errors = Vec::new();
-        let mut directories_cleaned = 0;
+        if self.prunes_stores() {
+            tasks = self.expand_stores(tasks, &mut skipped, &mut errors);
+        }
 
-        // Clean Docker caches if present
-        if !docker_tasks.is_empty() {
-            progress.set_message("Cleaning Docker caches...");
-            match self.clean_docker_caches().await {
-                Ok(_) => directories_cleaned += 1,
-                Err(e) => errors.push(format!("Docker cleaning failed: {}", e)),
-            }
-        }
-
-        // Clean file system caches in parallel
-        if !file_tasks.is_empty() {
-            progress.set_message("Cleaning file system caches...");
-            
-            // Convert to owned tasks for parallel processing
-            let owned_tasks: Vec<CleanTask> = file_tasks.iter().map(|t| (*t).clone()).collect();
-            
-            // Process tasks in parallel
-            let chunk_size = (owned_tasks.len() / self.parallel_threads).max(1);
-            
-            let results: Vec<Result<(usize, u64, u64, Vec<String>), anyhow::Error>> = owned_tasks
-                .par_chunks(chunk_size)
-                .map(|chunk| {
-                    self.process_chunk(chunk, total_size.clone(), total_files.clone())
-                })
-                .collect();
+        // Caches on network mounts are slow to delete and may be shared with other machines
+        tasks.retain(|task| {
+            let Some(fstype) = (self.filesystem_probe)(&task.path).filter(|fs| is_network_filesystem(fs)) else {
+                return true;
+            };
+            let action = if self.config.skip_network { "Skipping" } else { "Warning:" };
+            progress.suspend(|| {
+                self.warn(action, format_args!("{} is on a network filesystem ({})", task.path.display(), fstype))
+            });
+            if self.config.skip_network {
+                skipped.push((task.path.clone(), SkipReason::NetworkFilesystem));
+            }
+            !self.config.skip_network
+        });
 
-            // Aggregate results
-            for result in results {
-                match result {
-                    Ok((dirs, _files, _size, errs)) => {
-                        directories_cleaned += dirs;
-                        errors.extend(errs);
-                    }
-                    Err(e) => errors.push(e.to_string()),
+        // A running build or open database would break if its files vanished
+        if self.config.skip_in_use {
+            let open = open_files();
+            tasks.retain(|task| {
+                let path = task.path.canonicalize().unwrap_or_else(|_| task.path.clone());
+                let Some(file) = open.iter().find(|file| file.starts_with(&path)) else {
+                    return true;
+                };
+                progress.suspend(|| {
+                    self.warn(
+                        "Skipping",
+                        format_args!("{} is in use ({} is open)", task.path.display(), file.display()),
+                    )
+                });
+                skipped.push((task.path.clone(), SkipReason::InUse));
+                false
+            });
+        }
+
+        // Deleting `target` under a running `cargo build` fails the build, or corrupts its output
+        if self.config.skip_active_builds {
+            tasks.retain(|task| {
+                let Some(marker) = active_build_marker(&task.path, task.cache_type.build_lockfiles()) else {
+                    return true;
+                };
+                progress.suspend(|| {
+                    self.warn(
+                        "Skipping",
+                        format_args!("{} while a build is running ({} is held)", task.path.display(), marker.display()),
+                    )
+                });
+                skipped.push((task.path.clone(), SkipReason::ActiveBuild));
+                false
+            });
+        }
+
+        // Millions of tiny files take ages to delete and usually mean a data directory matched by mistake
+        if let Some(max) = self.config.max_file_count {
+            progress.set_message("Counting files...");
+            let _span = tracing::info_span!("size");
+            self.for_each_task(&mut tasks, |task| {
+                if task.path.is_dir() {
+                    task.sized = measure_path(&task.path).ok();
                 }
-            }
+            });
+            tasks.retain(|task| {
+                let Some(size) = task.sized.filter(|size| size.files > max) else {
+                    return true;
+                };
+                progress.suspend(|| {
+                    self.warn(
+                        "Skipping",
+                        format_args!(
+                            "{} holds {} files (more than --max-file-count {})",
+                            task.path.display(),
+                            size.files,
+                            max,
+                        ),
+                    )
+                });
+                skipped.push((task.path.clone(), SkipReason::TooManyFiles));
+                false
+            });
         }
 
-        let duration = start_time.elapsed();
-        progress.finish_with_message(format!(
-            "Completed in {:.2}s",
-            duration.as_secs_f64()
-        ));
+        // A trash on another filesystem is filled by copying; skip what won't fit instead of failing midway.
+        // Those items could still be deleted directly.
+        if let Some(trash_dir) = self.config.trash_dir.as_deref().filter(|_| self.config.check_free_space && self.trash.is_some()) {
+            let mut used = 0;
+            tasks.retain_mut(|task| {
+                let Some(available) = (self.free_space_probe)(&task.path, trash_dir) else {
+                    return true;
+                };
+                let Some(size) = task.sized.or_else(|| measure_path(&task.path).ok()) else {
+                    return true;
+                };
+                task.sized = Some(size);
+                if used + size.apparent <= available {
+                    used += size.apparent;
+                    return true;
+                }
+                progress.suspend(|| {
+                    self.warn(
+                        "Skipping",
+                        format_args!(
+                            "{} ({}) would not fit in the trash ({} free)",
+                            task.path.display(),
+                            humansize::format_size(size.apparent, humansize::BINARY),
+                            humansize::format_size(available.saturating_sub(used), humansize::BINARY),
+                        ),
+                    )
+                });
+                skipped.push((task.path.clone(), SkipReason::TrashFull));
+                false
+            });
+        }
 
-        Ok(CleanResult {
-            directories_cleaned,
-            files_deleted: total_files.load(Ordering::Relaxed),
-            space_freed: total_size.load(Ordering::Relaxed),
+        Ok(CleanPlan {
+            started,
+            tasks,
+            skipped,
             errors,
+            scan_cache: scan_cache.map(|cache| (cache, fingerprint)),
+            progress,
         })
     }
 
-    async fn find_cache_items(
-        &self,
-        patterns: &[(CacheType, CachePattern)],
-        progress: &ProgressBar,
-    ) -> Result<Vec<CleanTask>> {
-        progress.set_message("Initializing efficient cache traversal...");
+    /// Clean the items `plan` found
+    async fn execute(&self, plan: CleanPlan, total_size: &Arc<AtomicU64>, total_files: &Arc<AtomicU64>) -> Result<CleanResult> {
+        let CleanPlan {
+            started: start_time,
+            mut tasks,
+            mut skipped,
+            mut errors,
+            mut scan_cache,
+            progress,
+        } = plan;
 
-        // Configure traversal based on user preferences
-        // By default, we don't respect .gitignore for cache cleaning since many
-        // cache directories (target/, node_modules/, etc.) are in .gitignore
-        // but we still want to clean them. We still respect .clearcacheignore
-        // for user-specific exclusions.
-        let config = TraversalConfig {
-            max_depth: if self.recursive { 20 } else { 1 },
-            follow_links: false, // Don't follow symlinks for safety
-            ignore_hidden: false, // We want to find cache dirs that start with .
-            respect_gitignore: self.respect_gitignore, // User can opt-in to respect .gitignore
-            respect_clearcacheignore: !self.no_ignore,
-            parallel: self.parallel_threads > 1,
-        };
+        progress.set_message(format!("Found {} cache items to clean", tasks.len()));
 
-        // Create traversal engine
-        let traversal = CacheTraversal::new(config, patterns.to_vec());
-        
-        progress.set_message("Scanning directories with optimized traversal...");
-        
-        // Use the new efficient traversal system
-        let found_items = traversal.find_cache_items(&self.root_directory)?;
-        
-        progress.set_message(format!("Found {} cache items", found_items.len()));
+        let run_docker = self.config.docker && self.cache_types.contains(&CacheType::Docker);
+        if tasks.is_empty() && !run_docker {
+            if let Some((cache, fingerprint)) = scan_cache.as_mut() {
+                self.update_scan_cache(cache, fingerprint, &[]);
+            }
+            progress.finish_with_message("No cache items found to clean");
+            let status = if errors.is_empty() { CleanStatus::NothingFound } else { CleanStatus::CompletedWithErrors };
+            return Ok(CleanResult {
+                directories_cleaned: 0,
+                files_deleted: 0,
+                space_freed: 0,
+                errors,
+                items: Vec::new(),
+                omitted: Vec::new(),
+                skipped,
+                status,
+            });
+        }
 
-        // Convert FoundCacheItem to CleanTask
-        let tasks: Vec<CleanTask> = found_items
-            .into_iter()
-            .map(|item| CleanTask {
-                path: item.path,
-                pattern: item.pattern,
-                cache_type: item.cache_type,
-            })
-            .collect();
+        // A count needs the totals only; with deferred sizing, not even those
+        if self.config.count_only {
+            if !self.sizing_deferred() {
+                progress.set_message("Calculating sizes...");
+                let _span = tracing::info_span!("size");
+                self.for_each_task(&mut tasks, |task| {
+                    if task.sized.is_none() {
+                        task.sized = measure_path(&task.path).ok();
+                    }
+                });
+            }
+            for size in tasks.iter().filter_map(|task| task.sized) {
+                self.count_freed(total_size, total_files, self.freed(&size), size.files);
+            }
+            progress.finish_with_message(format!("Counted {} cache items", tasks.len()));
+            return Ok(CleanResult {
+                directories_cleaned: tasks.len(),
+                files_deleted: total_files.load(Ordering::Relaxed),
+                space_freed: total_size.load(Ordering::Relaxed),
+                errors: Vec::new(),
+                items: Vec::new(),
+                omitted: Vec::new(),
+                skipped,
+                status: if tasks.is_empty() { CleanStatus::NothingFound } else { CleanStatus::Success },
+            });
+        }
 
-        Ok(tasks)
-    }
+        // A hard stop for unattended runs: a pattern gone wrong can match thousands of items.
+        // Checked before the prompts so nobody answers them for nothing; reserved after them,
+        // so items the user keeps don't use up the cap.
+        if let Some(message) = self.over_deletion_cap(tasks.len(), self.deletions.load(Ordering::SeqCst)) {
+            progress.finish_and_clear();
+            return Ok(CleanResult::refused(message, skipped));
+        }
 
-    fn process_chunk(
-        &self,
-        tasks: &[CleanTask],
-        total_size: Arc<AtomicU64>,
-        total_files: Arc<AtomicU64>,
-    ) -> Result<(usize, u64, u64, Vec<String>)> {
-        let mut directories_cleaned = 0;
-        let mut files_deleted = 0;
-        let mut space_freed = 0;
-        let mut errors = Vec::new();
+        // Large cleans deserve a second look; size everything up front so the prompt can show totals
+        if let Some(threshold) = self.config.confirm_large.filter(|_| !self.config.dry_run) {
+            progress.set_message("Calculating sizes...");
+            let size_span = tracing::info_span!("size");
+            self.for_each_task(&mut tasks, |task| {
+                task.sized = measure_path(&task.path).ok();
+            });
+            drop(size_span);
 
-        for task in tasks {
-            if self.verbose {
-                let library_indicator = if task.pattern.is_library { " [LIBRARY]" } else { "" };
-                println!(
-                    "Processing: {} ({}{})",
-                    task.path.display().to_string().bright_blue(),
-                    task.pattern.description.bright_yellow(),
-                    library_indicator.bright_red()
+            let total: u64 = tasks.iter().filter_map(|t| t.sized).map(|size| self.freed(&size)).sum();
+            if total > threshold {
+                let mut largest: Vec<&CleanTask> = tasks.iter().collect();
+                largest.sort_by_key(|t| std::cmp::Reverse(t.sized.map(|size| self.freed(&size)).unwrap_or(0)));
+                let mut message = format!(
+                    "About to free {} across {} items. Largest:",
+                    humansize::format_size(total, humansize::BINARY),
+                    tasks.len()
                 );
-            }
-
-            match self.clean_item(task) {
-                Ok((files, size)) => {
-                    directories_cleaned += 1;
-                    files_deleted += files;
-                    space_freed += size;
-                    total_files.fetch_add(files, Ordering::Relaxed);
-                    total_size.fetch_add(size, Ordering::Relaxed);
-
-                    if self.verbose || self.dry_run {
-                        let library_indicator = if task.pattern.is_library { " [LIBRARY]" } else { "" };
-                        println!(
-                            "  {} {} ({} files, {}{})",
-                            if self.dry_run { "Would delete:" } else { "Deleted:" },
-                            task.path.display().to_string().bright_green(),
-                            files.to_string().bright_cyan(),
-                            humansize::format_size(size, humansize::BINARY).bright_cyan(),
-                            library_indicator.bright_red()
-                        );
+                for task in largest.iter().take(5) {
+                    message.push_str(&format!(
+                        "\n  {} ({})",
+                        task.path.display(),
+                        humansize::format_size(task.sized.map(|size| self.freed(&size)).unwrap_or(0), humansize::BINARY)
+                    ));
+                    // A peek inside helps spot precious data that merely matches a pattern
+                    let sample = sample_entries(&task.path, 3);
+                    if !sample.is_empty() {
+                        message.push_str(&format!("\n      contains: {}", sample.join(", ")));
                     }
                 }
-                Err(e) => {
-                    errors.push(format!("Failed to clean {}: {}", task.path.display(), e));
+                message.push_str("\nContinue?");
+
+                if !self.confirmer.can_prompt() {
+                    progress.finish_and_clear();
+                    let reason = format!(
+                        "refusing to free {} without confirmation: more than --confirm-large {} and no terminal to ask on; \
+                         nothing was deleted (rerun interactively, raise --confirm-large, or pass --force)",
+                        humansize::format_size(total, humansize::BINARY),
+                        humansize::format_size(threshold, humansize::BINARY)
+                    );
+                    return Ok(CleanResult::refused(reason, skipped));
+                }
+                if !progress.suspend(|| self.confirmer.confirm(&message)) {
+                    progress.finish_with_message("Cancelled");
+                    return Ok(CleanResult {
+                        directories_cleaned: 0,
+                        files_deleted: 0,
+                        space_freed: 0,
+                        errors: Vec::new(),
+                        items: Vec::new(),
+                        omitted: Vec::new(),
+                        skipped,
+                        status: CleanStatus::Aborted,
+                    });
                 }
             }
         }
 
-        Ok((directories_cleaned, files_deleted, space_freed, errors))
-    }
+        // Libraries need a reinstall to restore, so they get their own prompt
+        if self.config.confirm_libraries && !self.config.dry_run && tasks.iter().any(|t| t.pattern.is_library) {
+            let libraries: Vec<&CleanTask> = tasks.iter().filter(|t| t.pattern.is_library).collect();
+            let mut message = format!("Delete {} library items? They must be reinstalled afterwards:", libraries.len());
+            for task in &libraries {
+                message.push_str(&format!("\n  {} ({})", task.path.display(), task.pattern.description));
+            }
+            if !progress.suspend(|| self.confirmer.confirm(&message)) {
+                progress.suspend(|| self.emit("Keeping library items; cleaning safe caches only".bright_yellow()));
+                tasks.retain(|task| {
+                    if !task.pattern.is_library {
+                        return true;
+                    }
+                    skipped.push((task.path.clone(), SkipReason::Declined));
+                    false
+                });
+            }
+        }
 
-    fn clean_item(&self, task: &CleanTask) -> Result<(u64, u64)> {
-        if !task.path.exists() {
-            return Ok((0, 0));
+        if self.config.max_total_deletions.is_some() && !self.config.dry_run {
+            let wanted = tasks.len();
+            let reserved = self
+                .deletions
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |done| {
+                    Some(done + wanted).filter(|_| self.over_deletion_cap(wanted, done).is_none())
+                });
+            // Another root of a parallel run got there first
+            if let Err(done) = reserved {
+                progress.finish_and_clear();
+                return Ok(CleanResult::refused(self.over_deletion_cap(wanted, done).unwrap_or_default(), skipped));
+            }
         }
 
-        let (files, size) = if task.path.is_dir() {
-            calculate_directory_size(&task.path)?
+        let mut items = Vec::new();
+        let mut directories_cleaned = 0;
+
+        // Docker prune is daemon-bound and file deletion disk-bound, so run them side by side
+        let docker = if run_docker && self.config.dry_run {
+            if self.config.list_items {
+                progress.suspend(|| {
+                    self.emit("Would run Docker cleanup commands:".bright_yellow());
+                    self.emit("  docker system prune -af");
+                    self.emit("  docker volume prune -f");
+                });
+            }
+            None
+        } else if run_docker {
+            let runner = self.runner.clone();
+            let working_dir = self.root_directory.clone();
+            let wait = self.config.docker_wait;
+            Some(tokio::task::spawn_blocking(move || clean_docker_caches(runner.as_ref(), &working_dir, wait)))
         } else {
-            let metadata = std::fs::metadata(&task.path)?;
-            (1, metadata.len())
+            None
         };
 
-        if !self.dry_run {
-            if task.path.is_dir() {
-                std::fs::remove_dir_all(&task.path)?;
-            } else {
-                std::fs::remove_file(&task.path)?;
+        progress.set_message(if docker.is_some() {
+            "Cleaning Docker and file system caches..."
+        } else {
+            "Cleaning file system caches..."
+        });
+        let delete_span = tracing::info_span!("delete");
+        let docker = async {
+            match docker {
+                Some(handle) => Some(handle.await.map_err(anyhow::Error::from).and_then(|result| result)),
+                None => None,
             }
-        }
-
-        Ok((files, size))
-    }
+        };
+        // Docker already runs on its own thread, so waiting for it afterwards still overlaps the two
+        let (docker_result, file_results) = if self.config.sequential {
+            let docker_result = docker.await;
+            (docker_result, block_on_executor(|| self.clean_files(&tasks, total_size, total_files)))
+        } else {
+            let file_results = block_on_executor(|| self.clean_files(&tasks, total_size, total_files));
+            (docker.await, file_results)
+        };
+        drop(delete_span);
 
-    async fn clean_docker_caches(&self) -> Result<()> {
-        if self.dry_run {
-            println!("{}", "Would run Docker cleanup commands:".bright_yellow());
-            println!("  docker system prune -af");
-            println!("  docker volume prune -f");
-            return Ok(());
+        match docker_result {
+            Some(Ok(())) => {
+                directories_cleaned += 1;
+                if self.config.verbose {
+                    progress.suspend(|| self.emit("Docker caches cleaned successfully".bright_green()));
+                }
+            }
+            Some(Err(e)) => errors.push(CleanError::Docker(e.to_string())),
+            None => {}
         }
 
-        // Check if Docker is available
-        let output = tokio::process::Command::new("docker")
-            .args(&["--version"])
-            .output()
-            .await;
+        for result in file_results {
+            match result {
+                Ok(outcome) => {
+                    directories_cleaned += outcome.cleaned.len();
+                    items.extend(outcome.cleaned);
+                    errors.extend(outcome.errors);
+                    skipped.extend(outcome.skipped);
+                }
+                Err(e) => errors.push(CleanError::Other(e.to_string())),
+            }
+        }
 
-        if output.is_err() {
-            return Err(anyhow::anyhow!("Docker is not available"));
+        // Every item is already out of the project; the staged copies can go at leisure
+        if let Some(staging) = &self.staging {
+            progress.set_message("Removing staged items...");
+            if let Err(e) = staging.purge() {
+                errors.push(CleanError::Other(format!(
+                    "Failed to remove staging directory {}: {}",
+                    staging.path().display(),
+                    e
+                )));
+            }
         }
 
-        // Clean Docker system
-        let output = tokio::process::Command::new("docker")
-            .args(&["system", "prune", "-af"])
-            .output()
-            .await?;
+        let mut omitted = std::mem::take(&mut *self.omitted.lock().unwrap());
+        omitted.sort_by(|a, b| a.cache_type.cmp(&b.cache_type));
+        for group in &omitted {
+            directories_cleaned += group.items;
+            progress.suspend(|| {
+                self.warn(
+                    "Warning:",
+                    format_args!(
+                        "more than {} {:?} items; listing only the first",
+                        self.config.max_items_per_type.unwrap_or_default(),
+                        group.cache_type,
+                    ),
+                )
+            });
+            if self.config.list_items && (self.config.verbose || self.config.dry_run) {
+                let size = if self.sizing_deferred() {
+                    "size not calculated".to_string()
+                } else {
+                    format!("{} total", humansize::format_size(group.size, humansize::BINARY))
+                };
+                progress.suspend(|| {
+                    self.emit(format_args!(
+                        "  ... {} more items of type {:?} ({})",
+                        group.items.to_string().bright_cyan(),
+                        group.cache_type,
+                        size.bright_cyan()
+                    ))
+                });
+            }
+        }
 
-        if !output.status.success() {
-            return Err(anyhow::anyhow!(
-                "Docker system prune failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        // Previews list matches straight away; the sizing walk only happens on request
+        if self.sizing_deferred() && !items.is_empty() {
+            let message = format!("Calculate sizes for {} items?", items.len());
+            if progress.suspend(|| self.confirmer.confirm(&message)) {
+                progress.set_message("Calculating sizes...");
+                let _span = tracing::info_span!("size");
+                self.for_each_task(&mut items, |item| {
+                    if let Ok(size) = measure_path(&item.path) {
+                        item.files = size.files;
+                        item.size = self.freed(&size);
+                        item.apparent_size = size.apparent;
+                        item.size_known = true;
+                        self.count_freed(total_size, total_files, item.size, size.files);
+                    }
+                });
+            }
         }
 
-        // Clean Docker volumes
-        let output = tokio::process::Command::new("docker")
-            .args(&["volume", "prune", "-f"])
-            .output()
-            .await?;
+        if let Some((cache, fingerprint)) = scan_cache.as_mut() {
+            // Anything not deleted is still present; after failures, treat everything as remaining
+            let remaining: Vec<PathBuf> = if self.config.dry_run || !errors.is_empty() {
+                tasks.iter().map(|t| t.path.clone()).collect()
+            } else {
+                Vec::new()
+            };
+            self.update_scan_cache(cache, fingerprint, &remaining);
+        }
 
-        if !output.status.success() {
-            return Err(anyhow::anyhow!(
-                "Docker volume prune failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        let duration = start_time.elapsed();
+        progress.finish_with_message(format!(
+            "Completed in {:.2}s",
+            duration.as_secs_f64()
+        ));
+
+        let status = if !errors.is_empty() {
+            CleanStatus::CompletedWithErrors
+        } else if directories_cleaned == 0 {
+            // Everything found was skipped or declined
+            CleanStatus::NothingFound
+        } else {
+            CleanStatus::Success
+        };
+        // After failures the checkpoint is kept, so --resume retries only the items that didn't complete
+        if status != CleanStatus::CompletedWithErrors {
+            if let Some(checkpoint) = self.checkpoint.get() {
+                let _ = checkpoint.clear();
+            }
+        }
+        Ok(CleanResult {
+            directories_cleaned,
+            files_deleted: total_files.load(Ordering::Relaxed),
+            space_freed: total_size.load(Ordering::Relaxed),
+            errors,
+            items,
+            omitted,
+            skipped,
+            status,
+        })
+    }
+
+    /// A spinner for this root; never on stdout, which may be piped to a file or parser
+    fn progress_bar(&self) -> ProgressBar {
+        let progress = match &self.progress_group {
+            Some(group) => group.add(ProgressBar::new_spinner()),
+            None => ProgressBar::with_draw_target(None, (self.config.progress_target.0)()),
+        };
+        progress.set_style(
+            ProgressStyle::default_spinner()
+                .template("{spinner:.green} {msg}")
+                .unwrap(),
+        );
+        progress
+    }
+
+    /// Collect patterns for the selected types and individually enabled pattern names
+    fn collect_patterns(&self) -> Vec<(CacheType, CachePattern)> {
+        let mut all_patterns = Vec::new();
+        for cache_type in CacheType::all() {
+            let type_selected = self.cache_types.contains(&cache_type);
+            let patterns = if self.config.xdg {
+                cache_type.get_xdg_patterns()
+            } else if self.config.include_libraries {
+                // Include all patterns (both safe caches and libraries)
+                cache_type.get_patterns()
+            } else {
+                // Only include safe patterns (exclude libraries)
+                cache_type.get_safe_patterns()
+            };
+
+            for pattern in patterns {
+                if type_selected || self.config.pattern_names.contains(&pattern.name) {
+                    all_patterns.push((cache_type.clone(), pattern));
+                }
+            }
+
+            if type_selected && self.config.reset_locks && self.config.include_libraries && !self.config.xdg {
+                for pattern in cache_type.get_lockfile_patterns() {
+                    all_patterns.push((cache_type.clone(), pattern));
+                }
+            }
+        }
+
+        // Path globs are explicit user choices, so they apply regardless of type selection
+        if !self.config.path_globs.is_empty() && !self.config.xdg {
+            all_patterns.push((CacheType::General, CachePattern {
+                name: "path_glob".to_string(),
+                patterns: self.config.path_globs.iter().map(|glob| normalize_pattern(glob)).collect(),
+                description: "Path glob from --path-glob".to_string(),
+                is_directory: true,
+                recursive_safe: true,
+                is_library: false,
+                truncatable: false,
+            }));
+        }
+
+        // Provided patterns follow the same type selection and library rules as built-in ones
+        if !self.config.xdg {
+            for (cache_type, pattern) in &self.config.extra_patterns {
+                let selected = self.cache_types.contains(cache_type) || self.config.pattern_names.contains(&pattern.name);
+                if selected && (self.config.include_libraries || !pattern.is_library) {
+                    all_patterns.push((cache_type.clone(), pattern.clone()));
+                }
+            }
+        }
+
+        // The first matching pattern claims a path, so a language's own pattern must come before
+        // General's catch-alls (e.g. `build`) for the per-type breakdown to attribute it there
+        all_patterns.sort_by_key(|(cache_type, _)| *cache_type == CacheType::General);
+        all_patterns
+    }
+
+    /// Re-validate explicit targets, keeping those that still exist and match a selected pattern
+    fn resolve_targets(&self, targets: &[Target], patterns: &[(CacheType, CachePattern)]) -> Vec<CleanTask> {
+        let mut tasks = Vec::new();
+        // Targets may be recorded relative to the given root or canonicalized
+        let canonical_root = self.root_directory.canonicalize().unwrap_or_else(|_| self.root_directory.clone());
+        for target in targets {
+            let pattern = patterns
+                .iter()
+                .find(|(cache_type, pattern)| {
+                    *cache_type == target.cache_type
+                        && (matches_pattern_static(&target.path, &self.root_directory, pattern, !self.config.case_insensitive)
+                            || matches_pattern_static(&target.path, &canonical_root, pattern, !self.config.case_insensitive))
+                })
+                .map(|(_, pattern)| pattern.clone());
+
+            let metadata = std::fs::symlink_metadata(&target.path).ok();
+            match (pattern, metadata) {
+                (Some(pattern), Some(metadata)) => {
+                    tasks.push(CleanTask {
+                        path: target.path.clone(),
+                        pattern,
+                        cache_type: target.cache_type.clone(),
+                        modified: metadata.modified().ok(),
+                        sized: None,
+                    });
+                }
+                _ => {
+                    if self.config.verbose {
+                        self.emit(format_args!("Skipping target {} (missing or no longer a cache item)", target.path.display()));
+                    }
+                }
+            }
+        }
+        tasks
+    }
+
+    /// Identify the pattern set and traversal options a scan cache record is valid for
+    fn pattern_fingerprint(&self, patterns: &[(CacheType, CachePattern)]) -> String {
+        let mut names: Vec<&str> = patterns.iter().map(|(_, p)| p.name.as_str()).collect();
+        names.sort_unstable();
+        format!(
+            "{}|recursive={}|ignore={}|gitignore={}",
+            names.join(","),
+            self.config.recursive,
+            !self.config.no_ignore,
+            self.config.respect_gitignore
+        )
+    }
+
+    fn update_scan_cache(&self, cache: &mut ScanCache, fingerprint: &str, remaining: &[PathBuf]) {
+        let Some(path) = self.config.scan_cache.as_deref() else {
+            return;
+        };
+        cache.record(&self.root_directory, fingerprint, remaining);
+        if let Err(e) = cache.save(path) {
+            if self.config.verbose {
+                self.emit(format!("Failed to save scan cache: {}", e).bright_yellow());
+            }
+        }
+    }
+
+    /// Trace why `path` would or wouldn't be matched with the current configuration
+    pub fn explain(&self, path: &Path) -> PathExplanation {
+        let resolve = |p: &Path| {
+            p.canonicalize()
+                .or_else(|_| std::path::absolute(p))
+                .unwrap_or_else(|_| p.to_path_buf())
+        };
+        let traversal = CacheTraversal::new(self.traversal_config(HashSet::new()), self.collect_patterns());
+        traversal.explain_path(&resolve(&self.root_directory), &resolve(path))
+    }
+
+    fn traversal_config(&self, skip_paths: HashSet<PathBuf>) -> TraversalConfig {
+        // Configure traversal based on user preferences
+        // By default, we don't respect .gitignore for cache cleaning since many
+        // cache directories (target/, node_modules/, etc.) are in .gitignore
+        // but we still want to clean them. We still respect .clearcacheignore
+        // for user-specific exclusions.
+        TraversalConfig {
+            max_depth: if self.config.recursive { self.config.max_depth } else { 1 },
+            follow_links: self.config.follow_links, // Off by default for safety
+            ignore_hidden: false, // We want to find cache dirs that start with .
+            respect_gitignore: self.config.respect_gitignore, // User can opt-in to respect .gitignore
+            respect_clearcacheignore: !self.config.no_ignore,
+            parallel: self.config.parallel_threads > 1 && !self.config.sequential,
+            skip_paths,
+            global_ignore: self.config.global_ignore.clone(),
+            attributes: !self.config.xdg,
+            case_sensitive: !self.config.case_insensitive,
+            canonicalize: !self.config.no_canonicalize,
+        }
+    }
+
+    async fn find_cache_items(
+        &self,
+        patterns: &[(CacheType, CachePattern)],
+        skip_paths: HashSet<PathBuf>,
+        progress: &ProgressBar,
+    ) -> Result<Vec<CleanTask>> {
+        progress.set_message("Initializing efficient cache traversal...");
+
+        let config = self.traversal_config(skip_paths);
+
+        // Create traversal engine
+        let traversal = CacheTraversal::new(config, patterns.to_vec());
+        
+        progress.set_message("Scanning directories with optimized traversal...");
+        
+        // Use the new efficient traversal system
+        let found_items = traversal.find_cache_items(&self.root_directory)?;
+        // A non-recursive scan stops at depth 1 by design
+        if let Some(warning) = traversal.depth_limit_warning().filter(|_| self.config.recursive) {
+            progress.suspend(|| self.warn("Warning:", warning));
+        }
+        
+        progress.set_message(format!("Found {} cache items", found_items.len()));
+
+        // Convert FoundCacheItem to CleanTask. Attribute rules follow the same type
+        // selection and library rules as built-in patterns.
+        let tasks: Vec<CleanTask> = found_items
+            .into_iter()
+            // Deleting a matched symlink would free nothing; only --follow-links cleans its target
+            .filter(|item| self.config.follow_links || !item.path.is_symlink())
+            .filter(|item| {
+                item.pattern.name != ATTRIBUTES_PATTERN
+                    || (self.cache_types.contains(&item.cache_type)
+                        && (self.config.include_libraries || !item.pattern.is_library))
+            })
+            .map(|item| CleanTask {
+                path: item.path,
+                pattern: item.pattern,
+                cache_type: item.cache_type,
+                modified: item.modified,
+                sized: None,
+            })
+            .collect();
+
+        Ok(tasks)
+    }
+
+    /// Add freed bytes and files to this cleaner's counters and to the run's totals
+    fn count_freed(&self, total_size: &AtomicU64, total_files: &AtomicU64, size: u64, files: u64) {
+        total_size.fetch_add(size, Ordering::Relaxed);
+        total_files.fetch_add(files, Ordering::Relaxed);
+        if let Some((run_size, run_files)) = &self.run_totals {
+            run_size.fetch_add(size, Ordering::Relaxed);
+            run_files.fetch_add(files, Ordering::Relaxed);
+        }
+    }
+
+    fn process_chunk(
+        &self,
+        tasks: &[CleanTask],
+        total_size: Arc<AtomicU64>,
+        total_files: Arc<AtomicU64>,
+    ) -> Result<ChunkOutcome> {
+        let mut cleaned = Vec::new();
+        let mut errors = Vec::new();
+        let mut skipped = Vec::new();
+
+        for task in tasks {
+            if self.config.verbose {
+                let library_indicator = if task.pattern.is_library { " [LIBRARY]" } else { "" };
+                self.emit(format_args!(
+                    "Processing: {} ({}{})",
+                    task.path.display().to_string().bright_blue(),
+                    task.pattern.description.bright_yellow(),
+                    library_indicator.bright_red()
+                ));
+            }
+
+            let started = Instant::now();
+            match self.clean_item(task) {
+                Ok(measured) => {
+                    // Best effort: a lost line only means the item is checked again on resume
+                    if let Some(checkpoint) = self.checkpoint.get() {
+                        let _ = checkpoint.record(&task.path);
+                    }
+                    let (files, size) = (measured.files, self.freed(&measured));
+                    let duration = started.elapsed();
+                    self.count_freed(&total_size, &total_files, size, files);
+                    let listed = self.admit(&task.cache_type, files, size);
+                    if listed {
+                        cleaned.push(CleanedItem {
+                            path: task.path.clone(),
+                            cache_type: task.cache_type.clone(),
+                            pattern_name: task.pattern.name.clone(),
+                            is_library: task.pattern.is_library,
+                            files,
+                            size,
+                            apparent_size: measured.apparent,
+                            modified: task
+                                .modified
+                                .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+                                .map(|d| d.as_secs()),
+                            duration_secs: duration.as_secs_f64(),
+                            size_known: !self.sizing_deferred(),
+                            new: self.is_new(&task.path),
+                        });
+                    }
+
+                    // A preview should warn about deletions the real run would fail on
+                    let would_fail = if self.config.dry_run && self.config.check_permissions {
+                        can_delete(&task.path).err()
+                    } else {
+                        None
+                    };
+
+                    if listed && self.config.list_items && (self.config.verbose || self.config.dry_run) {
+                        let library_indicator = if task.pattern.is_library { " [LIBRARY]" } else { "" };
+                        // Slow items (e.g. on network mounts) stand out by their throughput
+                        let timing = if self.config.verbose {
+                            format!(
+                                " in {:.2?}, {}/s",
+                                duration,
+                                humansize::format_size(throughput(size, duration), humansize::BINARY)
+                            )
+                        } else {
+                            String::new()
+                        };
+                        let (files, size) = if self.sizing_deferred() {
+                            ("?".to_string(), "?".to_string())
+                        } else {
+                            (files.to_string(), humansize::format_size(size, humansize::BINARY))
+                        };
+                        let failure_indicator = if would_fail.is_some() { " [WOULD FAIL]" } else { "" };
+                        let new_indicator = if self.is_new(&task.path) == Some(true) { " (new)" } else { "" };
+                        self.emit(format_args!(
+                            "  {} {}{} ({} files, {}{}){}{}",
+                            match (self.config.dry_run, self.truncates(task)) {
+                                (true, false) => "Would delete:",
+                                (true, true) => "Would truncate:",
+                                (false, false) => "Deleted:",
+                                (false, true) => "Truncated:",
+                            },
+                            task.path.display().to_string().bright_green(),
+                            new_indicator.bright_magenta(),
+                            files.bright_cyan(),
+                            size.bright_cyan(),
+                            library_indicator.bright_red(),
+                            timing.dimmed(),
+                            failure_indicator.bright_red()
+                        ));
+                    }
+
+                    if let Some(e) = would_fail {
+                        errors.push(CleanError::Item {
+                            path: task.path.clone(),
+                            kind: ErrorKind::PermissionDenied,
+                            message: format!("would fail: {}", e),
+                        });
+                    }
+                }
+                Err(e) => match refusal_skip(&e) {
+                    Some(reason) => {
+                        if self.config.verbose {
+                            self.emit(format!("  Skipped: {} ({})", task.path.display(), reason).bright_yellow());
+                        }
+                        skipped.push((task.path.clone(), reason));
+                    }
+                    None => errors.push(CleanError::item(task.path.clone(), &e)),
+                },
+            }
+        }
+
+        Ok(ChunkOutcome { cleaned, errors, skipped })
+    }
+
+    fn clean_item(&self, task: &CleanTask) -> Result<DirectorySize> {
+        if !task.path.exists() {
+            return Ok(DirectorySize::default());
+        }
+
+        self.check_deletable(&task.path, &self.root_directory)?;
+
+        // A followed link is emptied in place, so what it points at must pass the same checks
+        let emptied = if task.path.is_symlink() {
+            let target = task.path.canonicalize()?;
+            let root = self.root_directory.canonicalize().unwrap_or_else(|_| self.root_directory.clone());
+            self.check_deletable(&target, &root)?;
+            Some(target).filter(|_| self.config.follow_links)
+        } else {
+            Some(task.path.clone())
+        };
+
+        // A vendored repository or submodule inside a matched `build` may hold uncommitted work
+        if let Some(dir) = emptied.filter(|dir| !self.config.allow_nested_repos && dir.is_dir()) {
+            if let Some(repository) = find_nested_repository(&dir) {
+                return Err(NestedRepositoryError(repository).into());
+            }
+        }
+
+        if self.sizing_deferred() {
+            return Ok(DirectorySize::default());
+        }
+
+        let mut size = match task.sized {
+            Some(sized) => sized,
+            None => {
+                let _span = tracing::info_span!("size", path = %task.path.display());
+                measure_path(&task.path)?
+            }
+        };
+
+        if !self.config.dry_run {
+            let _span = tracing::info_span!("delete item", cache_type = ?task.cache_type, path = %task.path.display());
+            if let Some(limiter) = &self.rate_limiter {
+                limiter.consume(self.freed(&size));
+            }
+            if self.truncates(task) {
+                let _permit = self.io_limiter.as_ref().map(IoLimiter::acquire);
+                std::fs::OpenOptions::new().write(true).open(&task.path)?.set_len(0)?;
+            } else {
+                // Only real directories; a followed link is already emptied in place
+                let skeleton = std::fs::symlink_metadata(&task.path)
+                    .ok()
+                    .filter(|metadata| self.config.preserve_skeleton && task.pattern.is_directory && metadata.is_dir());
+                let archive = match &self.config.archive_dir {
+                    Some(archive_dir) if task.path.is_dir() && !task.path.is_symlink() => Some(self.archive(task, archive_dir)?),
+                    _ => None,
+                };
+                // Whatever the tool leaves behind (or the whole path, if it isn't installed) is deleted directly
+                if archive.is_none() {
+                    self.native_clean(task)?;
+                }
+                if std::fs::symlink_metadata(&task.path).is_ok() {
+                    self.remove_path(&task.path)?;
+                }
+                // Only the difference is reclaimed; the archive still takes up space
+                if let Some(archive) = archive {
+                    size.apparent = size.apparent.saturating_sub(archive.apparent);
+                    size.actual = size.actual.saturating_sub(archive.actual);
+                }
+                if let Some(metadata) = skeleton {
+                    std::fs::create_dir(&task.path)?;
+                    std::fs::set_permissions(&task.path, metadata.permissions())?;
+                }
+            }
+        }
+
+        Ok(size)
+    }
+
+    /// Refuse paths that must never be deleted, whatever pattern matched them
+    fn check_deletable(&self, path: &Path, root: &Path) -> Result<()> {
+        if self.config.protected_paths.is_protected(path) {
+            return Err(ProtectedPathError.into());
+        }
+
+        // Reached through a followed link or an explicit target despite the root check
+        if let Some(manager) = managed_by(path) {
+            return Err(ManagedSystemPathError(manager).into());
+        }
+
+        // Patterns such as `logs` also match inside `.git`; losing history is never cache cleaning
+        if is_in_vcs_dir(path, root) {
+            return Err(VcsDirectoryError.into());
         }
 
-        if self.verbose {
-            println!("{}", "Docker caches cleaned successfully".bright_green());
+        // Removing the working directory strands this process and whatever launched it
+        if let Some(working_dir) = &self.working_dir {
+            let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+            if working_dir.starts_with(&path) {
+                return Err(WorkingDirectoryError.into());
+            }
         }
+        Ok(())
+    }
 
+    /// Under `--use-native-clean`, have the owning tool clean `task` in place. Skipped when
+    /// deleting into the trash, since the tool's deletion can't be restored.
+    fn native_clean(&self, task: &CleanTask) -> Result<()> {
+        if !self.config.use_native_clean || self.trash.is_some() {
+            return Ok(());
+        }
+        let Some(command) = native_clean_command(self.runner.as_ref(), task) else {
+            return Ok(());
+        };
+        let output = self.runner.run(command.program, command.args, &command.working_dir)?;
+        if !output.success {
+            anyhow::bail!(
+                "`{} {}` failed: {}",
+                command.program,
+                command.args.join(" "),
+                output.stderr.trim()
+            );
+        }
         Ok(())
     }
-} 
\ No newline at end of file
+
+    /// Under `--archive-instead`, compress `task` into a `.tar.zst` under `archive_dir`, at the
+    /// path it had under the root, so `tar --zstd -xf` in the original parent restores it.
+    /// Returns the archive's size; the original is only deleted once this succeeds.
+    fn archive(&self, task: &CleanTask, archive_dir: &Path) -> Result<DirectorySize> {
+        let Some(name) = task.path.file_name() else {
+            anyhow::bail!("Cannot archive {}", task.path.display());
+        };
+        let relative = task.path.strip_prefix(&self.root_directory).unwrap_or(Path::new(name));
+        let mut archive = archive_dir.join(relative).into_os_string();
+        archive.push(".tar.zst");
+        let archive = PathBuf::from(archive);
+        if archive.exists() {
+            anyhow::bail!("Archive {} already exists", archive.display());
+        }
+        if let Some(archive_parent) = archive.parent() {
+            std::fs::create_dir_all(archive_parent)?;
+        }
+
+        if let Err(e) = write_archive(&task.path, Path::new(name), &archive) {
+            let _ = std::fs::remove_file(&archive);
+            return Err(e.context(format!("Failed to archive {}", task.path.display())));
+        }
+        Ok(DirectorySize::of_file(&std::fs::metadata(&archive)?))
+    }
+
+    /// Whether another item of `cache_type` may be listed under `--max-items-per-type`.
+    /// Items past the cap are added to that type's omitted totals instead.
+    fn admit(&self, cache_type: &CacheType, files: u64, size: u64) -> bool {
+        let Some(max) = self.config.max_items_per_type else {
+            return true;
+        };
+        let mut listed = self.listed.lock().unwrap();
+        let count = listed.entry(cache_type.clone()).or_default();
+        if *count < max {
+            *count += 1;
+            return true;
+        }
+
+        let mut omitted = self.omitted.lock().unwrap();
+        match omitted.iter_mut().find(|o| &o.cache_type == cache_type) {
+            Some(group) => group.add(1, files, size),
+            None => omitted.push(OmittedItems {
+                cache_type: cache_type.clone(),
+                items: 1,
+                files,
+                size,
+            }),
+        }
+        false
+    }
+
+    /// Whether `task` is a plain file to empty in place, keeping it open for whatever writes to it
+    fn truncates(&self, task: &CleanTask) -> bool {
+        self.config.truncate_logs
+            && task.pattern.truncatable
+            && std::fs::symlink_metadata(&task.path).is_ok_and(|metadata| metadata.is_file())
+    }
+
+    /// Whether `path` is missing from the `--baseline` report, when one was given
+    fn is_new(&self, path: &Path) -> Option<bool> {
+        self.config.baseline.as_ref().map(|baseline| !baseline.contains(path))
+    }
+
+    /// Write one line and flush it, so piped output shows progress during long scans
+    fn emit(&self, line: impl std::fmt::Display) {
+        let mut output = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(output, "{}", line).and_then(|_| output.flush());
+    }
+
+    /// Write one warning line, led by `label` (e.g. "Warning:" or "Skipping")
+    fn warn(&self, label: &str, message: impl std::fmt::Display) {
+        let mut warnings = self.warnings.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(warnings, "{} {}", label.bright_yellow(), message).and_then(|_| warnings.flush());
+    }
+
+    fn freed(&self, size: &DirectorySize) -> u64 {
+        size.freed(self.config.apparent_size)
+    }
+
+    fn sizing_deferred(&self) -> bool {
+        self.config.dry_run && self.config.defer_sizing
+    }
+
+    fn remove_path(&self, path: &Path) -> std::io::Result<()> {
+        // A followed link is emptied rather than removed, so it still points at its (now empty) target.
+        // Only the matched link is dereferenced: links inside its target are removed, never followed.
+        if self.config.follow_links && path.is_symlink() && path.is_dir() {
+            for entry in std::fs::read_dir(path)? {
+                self.remove_entry(&entry?.path())?;
+            }
+            return Ok(());
+        }
+        self.remove_entry(path)
+    }
+
+    /// Delete (or trash, or stage) one path; a symlink is removed itself, not what it points at
+    fn remove_entry(&self, path: &Path) -> std::io::Result<()> {
+        let _permit = self.io_limiter.as_ref().map(IoLimiter::acquire);
+        if let Some(trash) = &self.trash {
+            return trash.store(path);
+        }
+        if self.staging.as_ref().is_some_and(|staging| staging.stage(path)) {
+            return Ok(());
+        }
+        let result = remove_path_once(path);
+
+        // Windows refuses to delete read-only files; clear the attribute and retry once
+        #[cfg(windows)]
+        if let Err(e) = &result {
+            if e.kind() == std::io::ErrorKind::PermissionDenied && self.config.clear_readonly {
+                clear_readonly(path)?;
+                return remove_path_once(path);
+            }
+        }
+
+        result
+    }
+
+    /// Why deleting `wanted` more items, after `done` by the run so far, would break
+    /// `--max-total-deletions`; `None` when within the cap or deleting nothing
+    fn over_deletion_cap(&self, wanted: usize, done: usize) -> Option<String> {
+        let max = self.config.max_total_deletions.filter(|_| !self.config.dry_run)?;
+        if done + wanted <= max {
+            return None;
+        }
+        Some(if done == 0 {
+            format!("refusing to delete {} items, more than --max-total-deletions {}; nothing was deleted", wanted, max)
+        } else {
+            format!(
+                "refusing to delete {} more items from {}: {} were already deleted and --max-total-deletions is {}",
+                wanted,
+                self.root_directory.display(),
+                done,
+                max
+            )
+        })
+    }
+
+    /// Whether content-addressed stores are pruned entry by entry rather than deleted whole
+    fn prunes_stores(&self) -> bool {
+        self.config.dedup_stores || self.config.store_max_age.is_some()
+    }
+
+    /// Replace each pnpm store among `tasks` with the entries to prune from it, keeping every entry
+    /// a project under the root still references (`--dedup-stores`) or that was used within
+    /// `--store-max-age`. The entries are then deleted (or trashed) like any other item.
+    fn expand_stores(
+        &self,
+        tasks: Vec<CleanTask>,
+        skipped: &mut Vec<(PathBuf, SkipReason)>,
+        errors: &mut Vec<CleanError>,
+    ) -> Vec<CleanTask> {
+        let (stores, mut tasks): (Vec<CleanTask>, Vec<CleanTask>) =
+            tasks.into_iter().partition(|task| task.pattern.name == "pnpm_cache");
+        if stores.is_empty() {
+            return tasks;
+        }
+        let references = self.store_references(std::slice::from_ref(&self.root_directory));
+        for store in stores {
+            if self.store_ages_unknown(&store.path) {
+                skipped.push((store.path, SkipReason::NoAccessTimes));
+                continue;
+            }
+            match pnpm_store_entries(&store.path, self.store_selection(&references.pnpm)) {
+                Ok(entries) => tasks.extend(entries.into_iter().map(|entry| self.store_task(entry, CacheType::Node, "pnpm_store"))),
+                Err(e) => errors.push(CleanError::Store {
+                    path: store.path,
+                    message: e.to_string(),
+                }),
+            }
+        }
+        tasks
+    }
+
+    /// Plan the run's global work. Docker needs no plan; the cargo registry, when given, is the root
+    /// of this cleaner. The registry is shared by every project, so only `Cargo.lock` files under
+    /// `roots` count as references: projects elsewhere lose crates they use, and download them again
+    /// on their next build.
+    fn plan_global(&self, roots: &[PathBuf]) -> CleanPlan {
+        let mut skipped = Vec::new();
+        let mut entries = Vec::new();
+        if self.config.cargo_registry.is_some() {
+            if self.store_ages_unknown(&self.root_directory) {
+                skipped.push((self.root_directory.clone(), SkipReason::NoAccessTimes));
+            } else {
+                let references = self.store_references(roots);
+                entries = cargo_registry_entries(&self.root_directory, self.store_selection(&references.cargo));
+            }
+        }
+        CleanPlan {
+            started: Instant::now(),
+            tasks: entries
+                .into_iter()
+                .map(|entry| self.store_task(entry, CacheType::Rust, "cargo_registry"))
+                .collect(),
+            skipped,
+            errors: Vec::new(),
+            scan_cache: None,
+            progress: self.progress_bar(),
+        }
+    }
+
+    /// Whether `--store-max-age` can't judge the store at `path`: on a `noatime` mount, reading an
+    /// entry leaves no trace, so one used every day would look as old as its download
+    fn store_ages_unknown(&self, path: &Path) -> bool {
+        self.config.store_max_age.is_some() && records_access_time(path) == Some(false)
+    }
+
+    /// Packages referenced by projects under `roots`; only needed for `--dedup-stores`
+    fn store_references(&self, roots: &[PathBuf]) -> StoreReferences {
+        let mut references = StoreReferences::default();
+        for root in roots.iter().filter(|_| self.config.dedup_stores) {
+            let found = find_references(root);
+            references.pnpm.extend(found.pnpm);
+            references.cargo.extend(found.cargo);
+        }
+        references
+    }
+
+    fn store_selection<'a>(&self, referenced: &'a HashSet<String>) -> StoreSelection<'a> {
+        StoreSelection {
+            referenced: self.config.dedup_stores.then_some(referenced),
+            used_since: self.config.store_max_age.and_then(|age| SystemTime::now().checked_sub(age)),
+        }
+    }
+
+    /// A store entry to delete, reported under `<store>_dedup` or `<store>_stale`
+    fn store_task(&self, path: PathBuf, cache_type: CacheType, store: &str) -> CleanTask {
+        let (suffix, reason) = if self.config.dedup_stores {
+            ("dedup", "no project references it")
+        } else {
+            ("stale", "unused for longer than --store-max-age")
+        };
+        CleanTask {
+            modified: std::fs::symlink_metadata(&path).and_then(|metadata| metadata.modified()).ok(),
+            path,
+            pattern: CachePattern {
+                name: format!("{}_{}", store, suffix),
+                patterns: Vec::new(),
+                description: format!("Store entry {}", reason),
+                is_directory: false,
+                recursive_safe: true,
+                is_library: false,
+                truncatable: false,
+            },
+            cache_type,
+            sized: None,
+        }
+    }
+
+    /// Delete filesystem items in parallel chunks
+    fn clean_files(
+        &self,
+        tasks: &[CleanTask],
+        total_size: &Arc<AtomicU64>,
+        total_files: &Arc<AtomicU64>,
+    ) -> Vec<Result<ChunkOutcome>> {
+        let mut file_tasks: Vec<CleanTask> = tasks
+            .iter()
+            .filter(|t| t.cache_type != CacheType::Docker)
+            .cloned()
+            .collect();
+        if file_tasks.is_empty() {
+            return Vec::new();
+        }
+
+        if let Some(target) = self.config.target_free {
+            return self.clean_until_free(file_tasks, target, total_size, total_files);
+        }
+
+        // Prioritized types run as separate tiers so quick cleans aren't stuck behind heavy ones
+        file_tasks.sort_by_key(|t| self.priority_rank(&t.cache_type));
+        let mut results = Vec::new();
+        for tier in file_tasks.chunk_by(|a, b| self.priority_rank(&a.cache_type) == self.priority_rank(&b.cache_type)) {
+            if self.config.sequential {
+                results.push(self.process_chunk(tier, total_size.clone(), total_files.clone()));
+                continue;
+            }
+            let chunk_size = (tier.len() / self.config.parallel_threads.max(1)).max(1);
+            results.par_extend(
+                tier.par_chunks(chunk_size)
+                    .map(|chunk| self.process_chunk(chunk, total_size.clone(), total_files.clone())),
+            );
+        }
+        results
+    }
+
+    /// Delete items one at a time, largest or oldest first (within `--priority` tiers), until the
+    /// root's filesystem has `target` bytes free. A dry run projects the free space instead.
+    fn clean_until_free(
+        &self,
+        mut tasks: Vec<CleanTask>,
+        target: u64,
+        total_size: &Arc<AtomicU64>,
+        total_files: &Arc<AtomicU64>,
+    ) -> Vec<Result<ChunkOutcome>> {
+        match self.config.order {
+            // Without sizes there is nothing to order by; keep the scan order
+            CleanOrder::Largest if self.sizing_deferred() => {}
+            CleanOrder::Largest => {
+                self.for_each_task(&mut tasks, |task| {
+                    if task.sized.is_none() {
+                        task.sized = measure_path(&task.path).ok();
+                    }
+                });
+                tasks.sort_by_key(|task| std::cmp::Reverse(task.sized.map(|size| self.freed(&size)).unwrap_or(0)));
+            }
+            CleanOrder::Oldest => tasks.sort_by_key(|task| (task.modified.is_none(), task.modified)),
+        }
+        tasks.sort_by_key(|task| self.priority_rank(&task.cache_type));
+
+        let Some(initial) = (self.disk_space_probe)(&self.root_directory) else {
+            self.warn(
+                "Warning:",
+                format_args!("cannot determine the free space of {}; nothing cleaned", self.root_directory.display()),
+            );
+            return Vec::new();
+        };
+        let freed_before = total_size.load(Ordering::Relaxed);
+        let mut results = Vec::new();
+        for (cleaned, task) in tasks.iter().enumerate() {
+            let available = if self.config.dry_run {
+                Some(initial + total_size.load(Ordering::Relaxed) - freed_before)
+            } else {
+                (self.disk_space_probe)(&self.root_directory)
+            };
+            if let Some(available) = available.filter(|available| *available >= target) {
+                if self.config.list_items {
+                    self.emit(format_args!(
+                        "Reached {} free; leaving {} items",
+                        humansize::format_size(available, humansize::BINARY),
+                        tasks.len() - cleaned
+                    ));
+                }
+                break;
+            }
+            results.push(self.process_chunk(std::slice::from_ref(task), total_size.clone(), total_files.clone()));
+        }
+        results
+    }
+
+    /// Run `f` on every element with rayon, or in order on this thread under `--no-parallel`
+    fn for_each_task<T: Send>(&self, tasks: &mut [T], f: impl Fn(&mut T) + Send + Sync) {
+        if self.config.sequential {
+            tasks.iter_mut().for_each(f);
+        } else {
+            tasks.par_iter_mut().for_each(f);
+        }
+    }
+
+    /// Position of a type in `--priority`; unlisted types share the last tier
+    fn priority_rank(&self, cache_type: &CacheType) -> usize {
+        self.config
+            .priority
+            .iter()
+            .position(|t| t == cache_type)
+            .unwrap_or(self.config.priority.len())
+    }
+}
+
+/// State shared by the roots of one run
+#[derive(Clone)]
+struct RunState {
+    visited: Option<VisitedSet>, // With dedupe: items claimed by an earlier root
+    deletions: DeletionCount,
+    trash: Option<Arc<TrashRun>>, // One trash run, so a single `undo` restores every root
+    runner: Arc<dyn CommandRunner>,
+    confirmer: Arc<dyn Confirm>,
+    progress: MultiProgress,
+    totals: Option<RunTotals>,
+}
+
+impl RunState {
+    fn new(config: &CleanerConfig, dedupe: bool) -> Self {
+        Self {
+            visited: dedupe.then(VisitedSet::default),
+            deletions: DeletionCount::default(),
+            trash: config
+                .trash_dir
+                .as_deref()
+                .filter(|_| !config.dry_run)
+                .map(|dir| Arc::new(TrashRun::new(dir))),
+            runner: Arc::new(SystemRunner),
+            confirmer: Arc::new(StdinConfirm),
+            progress: MultiProgress::with_draw_target((config.progress_target.0)()),
+            totals: None,
+        }
+    }
+
+    fn cleaner(&self, root: &Path, cache_types: &[CacheType], config: &CleanerConfig) -> CacheCleaner {
+        let mut cleaner = CacheCleaner::new(root.to_path_buf(), cache_types.to_vec(), config.clone())
+            .with_runner(self.runner.clone())
+            .with_confirmer(self.confirmer.clone())
+            .with_deletion_count(self.deletions.clone())
+            .with_trash(self.trash.clone())
+            .with_progress_group(self.progress.clone());
+        if let Some(visited) = &self.visited {
+            cleaner = cleaner.with_visited(visited.clone());
+        }
+        if let Some(totals) = &self.totals {
+            cleaner = cleaner.with_run_totals(totals.clone());
+        }
+        cleaner
+    }
+}
+
+/// Write `dir` to `archive` as a zstd-compressed tarball whose entries sit under `name`. Links
+/// are stored as links; named pipes, sockets and devices are left out.
+fn write_archive(dir: &Path, name: &Path, archive: &Path) -> Result<()> {
+    let mut encoder = zstd::Encoder::new(std::fs::File::create(archive)?, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+    let mut builder = tar::Builder::new(&mut encoder);
+    builder.follow_symlinks(false);
+    for entry in WalkDir::new(dir).follow_links(false) {
+        let entry = entry?;
+        if is_special_file(&entry.file_type()) {
+            continue;
+        }
+        builder.append_path_with_name(entry.path(), name.join(entry.path().strip_prefix(dir)?))?;
+    }
+    builder.into_inner()?;
+    encoder.finish()?.sync_all()?;
+    Ok(())
+}
+
+/// Refusals that protect data the user keeps on purpose are skips, not failures: they don't
+/// make the run exit non-zero
+fn refusal_skip(error: &anyhow::Error) -> Option<SkipReason> {
+    if error.is::<VcsDirectoryError>() {
+        return Some(SkipReason::VcsMetadata);
+    }
+    if error.is::<NestedRepositoryError>() {
+        return Some(SkipReason::NestedRepository);
+    }
+    None
+}
+
+/// Clean several roots in turn, stopping if the user declines a prompt for one of them.
+/// With `config.parallel_roots` above one, that many roots are cleaned at once; roots that
+/// overlap are still cleaned one after another, in the order given.
+///
+/// With `dedupe`, an item reachable from more than one root is processed once and
+/// reported under the first root that contains it.
+pub async fn clean_roots(
+    roots: &[PathBuf],
+    cache_types: &[CacheType],
+    config: &CleanerConfig,
+    dedupe: bool,
+    total_size: Arc<AtomicU64>,
+    total_files: Arc<AtomicU64>,
+) -> Result<RootResults> {
+    clean_roots_with(RunState::new(config, dedupe), roots, cache_types, config, total_size, total_files).await
+}
+
+async fn clean_roots_with(
+    run: RunState,
+    roots: &[PathBuf],
+    cache_types: &[CacheType],
+    config: &CleanerConfig,
+    total_size: Arc<AtomicU64>,
+    total_files: Arc<AtomicU64>,
+) -> Result<RootResults> {
+    let run = RunState {
+        totals: Some((total_size, total_files)),
+        ..run
+    };
+    // Global work runs once, after every root, rather than with each of them
+    let root_config = CleanerConfig {
+        docker: false,
+        cargo_registry: None,
+        ..config.clone()
+    };
+    if config.max_total_deletions.is_some() && !config.dry_run {
+        return clean_roots_capped(run, roots, cache_types, config, &root_config).await;
+    }
+    let mut results = if config.parallel_roots > 1 && roots.len() > 1 {
+        clean_roots_parallel(roots, cache_types, &root_config, &run).await?
+    } else {
+        let mut results = Vec::new();
+        for root in roots {
+            let cleaner = run.cleaner(root, cache_types, &root_config);
+            let result = clean_root(&cleaner, None).await?;
+            let aborted = result.status == CleanStatus::Aborted;
+            results.push((root.clone(), result));
+            if aborted {
+                break;
+            }
+        }
+        RootResults {
+            roots: results,
+            ..RootResults::default()
+        }
+    };
+
+    if results.roots.iter().all(|(_, result)| result.status != CleanStatus::Aborted) {
+        if let Some(cleaner) = global_cleaner(roots, cache_types, config, &run) {
+            results.global = clean_root(&cleaner, Some(cleaner.plan_global(roots))).await?;
+        }
+    }
+    Ok(results)
+}
+
+/// With `--max-total-deletions`, every root is scanned before anything is deleted, so a run over
+/// the cap stops without touching any root. Roots are then cleaned one after another; an item
+/// reachable from several roots is planned once, under the first.
+async fn clean_roots_capped(
+    run: RunState,
+    roots: &[PathBuf],
+    cache_types: &[CacheType],
+    config: &CleanerConfig,
+    root_config: &CleanerConfig,
+) -> Result<RootResults> {
+    let run = RunState {
+        visited: Some(run.visited.clone().unwrap_or_default()),
+        ..run
+    };
+    let mut planned = Vec::new();
+    for root in roots {
+        let cleaner = run.cleaner(root, cache_types, root_config);
+        let plan = cleaner.plan().await?;
+        planned.push((root.clone(), cleaner, plan));
+    }
+    let global = global_cleaner(roots, cache_types, config, &run).map(|cleaner| {
+        let plan = cleaner.plan_global(roots);
+        (cleaner, plan)
+    });
+
+    let wanted = planned.iter().map(|(_, _, plan)| plan.tasks.len()).sum::<usize>()
+        + global.as_ref().map_or(0, |(_, plan)| plan.tasks.len());
+    if let Some(max) = config.max_total_deletions.filter(|max| wanted > *max) {
+        if let Some((_, plan)) = global {
+            plan.progress.finish_and_clear();
+        }
+        let roots = planned
+            .into_iter()
+            .map(|(root, _, plan)| {
+                plan.progress.finish_and_clear();
+                let result = CleanResult {
+                    skipped: plan.skipped,
+                    status: CleanStatus::Aborted,
+                    ..CleanResult::default()
+                };
+                (root, result)
+            })
+            .collect();
+        let reason = format!("refusing to delete {} items, more than --max-total-deletions {}; nothing was deleted", wanted, max);
+        return Ok(RootResults {
+            roots,
+            global: CleanResult::refused(reason, Vec::new()),
+        });
+    }
+
+    let mut results = RootResults::default();
+    for (root, cleaner, plan) in planned {
+        let result = clean_root(&cleaner, Some(plan)).await?;
+        let aborted = result.status == CleanStatus::Aborted;
+        results.roots.push((root, result));
+        if aborted {
+            return Ok(results);
+        }
+    }
+    if let Some((cleaner, plan)) = global {
+        results.global = clean_root(&cleaner, Some(plan)).await?;
+    }
+    Ok(results)
+}
+
+/// The cleaner for work shared by every root, done once per run after the roots are cleaned:
+/// `docker system prune`, and pruning the cargo registry with `--prune-cargo-registry`
+fn global_cleaner(roots: &[PathBuf], cache_types: &[CacheType], config: &CleanerConfig, run: &RunState) -> Option<CacheCleaner> {
+    let docker = config.docker && cache_types.contains(&CacheType::Docker);
+    if !docker && config.cargo_registry.is_none() {
+        return None;
+    }
+    let mut types = vec![CacheType::Rust];
+    if docker {
+        types.push(CacheType::Docker);
+    }
+    // Registry entries are checked against the registry, as the root they were found under
+    let root = config.cargo_registry.as_ref().unwrap_or(&roots[0]);
+    Some(run.cleaner(root, &types, config))
+}
+
+async fn clean_roots_parallel(
+    roots: &[PathBuf],
+    cache_types: &[CacheType],
+    config: &CleanerConfig,
+    run: &RunState,
+) -> Result<RootResults> {
+    let permits = Arc::new(tokio::sync::Semaphore::new(config.parallel_roots));
+    let aborted = Arc::new(AtomicBool::new(false));
+    let mut running = tokio::task::JoinSet::new();
+    for group in overlapping_groups(roots) {
+        let group: Vec<(usize, PathBuf)> = group.into_iter().map(|index| (index, roots[index].clone())).collect();
+        let (permits, aborted, run) = (permits.clone(), aborted.clone(), run.clone());
+        let (cache_types, config) = (cache_types.to_vec(), config.clone());
+        running.spawn(async move {
+            let _permit = permits.acquire_owned().await?;
+            let mut results = Vec::new();
+            for (index, root) in group {
+                // A declined prompt stops roots that haven't started, as in a sequential run
+                if aborted.load(Ordering::Relaxed) {
+                    break;
+                }
+                let result = clean_root(&run.cleaner(&root, &cache_types, &config), None).await?;
+                if result.status == CleanStatus::Aborted {
+                    aborted.store(true, Ordering::Relaxed);
+                }
+                results.push((index, root, result));
+            }
+            anyhow::Ok(results)
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(group) = running.join_next().await {
+        results.extend(group??);
+    }
+    results.sort_by_key(|(index, _, _)| *index);
+    Ok(RootResults {
+        roots: results.into_iter().map(|(_, root, result)| (root, result)).collect(),
+        ..RootResults::default()
+    })
+}
+
+/// Clean one root of a multi-root run. It counts into its own totals, so its result reports
+/// what it alone freed; the cleaner adds to the run's totals as it goes.
+async fn clean_root(cleaner: &CacheCleaner, plan: Option<CleanPlan>) -> Result<CleanResult> {
+    let (root_size, root_files) = (Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)));
+    let plan = match plan {
+        Some(plan) => plan,
+        None => cleaner.plan().await?,
+    };
+    cleaner.execute(plan, &root_size, &root_files).await
+}
+
+/// Indices of `roots` grouped so that roots where one contains the other share a group,
+/// each group in the order given
+fn overlapping_groups(roots: &[PathBuf]) -> Vec<Vec<usize>> {
+    let canonical: Vec<PathBuf> = roots
+        .iter()
+        .map(|root| root.canonicalize().unwrap_or_else(|_| root.clone()))
+        .collect();
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for (index, root) in canonical.iter().enumerate() {
+        let overlaps = |other: &usize| root.starts_with(&canonical[*other]) || canonical[*other].starts_with(root);
+        let (mut joined, rest): (Vec<Vec<usize>>, Vec<Vec<usize>>) =
+            groups.into_iter().partition(|group| group.iter().any(overlaps));
+        let mut group: Vec<usize> = joined.drain(..).flatten().collect();
+        group.push(index);
+        group.sort_unstable();
+        groups = rest;
+        groups.push(group);
+    }
+    groups.sort_by_key(|group| group[0]);
+    groups
+}
+
+/// How often `docker info` is retried while the daemon starts
+const DOCKER_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Prune unused Docker data through the daemon, waiting up to `wait` for it to come up
+fn clean_docker_caches(runner: &dyn CommandRunner, working_dir: &Path, wait: Duration) -> Result<()> {
+    // A missing binary won't appear by waiting
+    if runner.run("docker", &["--version"], working_dir).is_err() {
+        return Err(anyhow::anyhow!("Docker is not available"));
+    }
+
+    let deadline = Instant::now() + wait;
+    loop {
+        let info = runner.run("docker", &["info"], working_dir)?;
+        if info.success {
+            break;
+        }
+        if Instant::now() >= deadline {
+            return Err(anyhow::anyhow!(
+                "Docker daemon did not respond within {}s: {}",
+                wait.as_secs(),
+                info.stderr.trim()
+            ));
+        }
+        std::thread::sleep(DOCKER_POLL_INTERVAL);
+    }
+
+    let output = runner.run("docker", &["system", "prune", "-af"], working_dir)?;
+    if !output.success {
+        return Err(anyhow::anyhow!("Docker system prune failed: {}", output.stderr));
+    }
+
+    let output = runner.run("docker", &["volume", "prune", "-f"], working_dir)?;
+    if !output.success {
+        return Err(anyhow::anyhow!("Docker volume prune failed: {}", output.stderr));
+    }
+
+    Ok(())
+}
+
+/// Bytes per second, treating an unmeasurably short duration as one microsecond
+fn throughput(bytes: u64, duration: Duration) -> u64 {
+    (bytes as f64 / duration.as_secs_f64().max(1e-6)) as u64
+}
+
+/// Count files and bytes under a cache item
+fn measure_path(path: &Path) -> Result<DirectorySize> {
+    if path.is_dir() {
+        calculate_directory_size(path)
+    } else {
+        let metadata = std::fs::metadata(path)?;
+        if is_special_file(&metadata.file_type()) {
+            return Ok(DirectorySize::default());
+        }
+        Ok(DirectorySize::of_file(&metadata))
+    }
+}
+
+/// A tool's own command for cleaning one matched item
+#[derive(Debug, PartialEq, Eq)]
+struct NativeCleanCommand {
+    program: &'static str,
+    args: &'static [&'static str],
+    working_dir: PathBuf,
+}
+
+/// Run blocking work from async code. On a multi-threaded runtime the worker hands its other
+/// tasks to another thread first; a current-thread runtime has no other thread to hand them to.
+fn block_on_executor<T>(work: impl FnOnce() -> T) -> T {
+    match tokio::runtime::Handle::try_current().map(|handle| handle.runtime_flavor()) {
+        Ok(tokio::runtime::RuntimeFlavor::MultiThread) => tokio::task::block_in_place(work),
+        _ => work(),
+    }
+}
+
+/// The command that cleans `task` the way its tool would: `cargo clean` for a `target` that
+/// `cargo metadata` reports as the project's target directory, and `go clean -cache` for the
+/// directory `go env GOCACHE` reports. Both tools clean wherever they are configured to
+/// (`CARGO_TARGET_DIR`, `build.target-dir`, a workspace root, `GOCACHE`), so they are only used
+/// when that is exactly the matched path.
+fn native_clean_command(runner: &dyn CommandRunner, task: &CleanTask) -> Option<NativeCleanCommand> {
+    let parent = task.path.parent()?;
+    let same = |a: &Path, b: &Path| a.canonicalize().ok().is_some_and(|a| b.canonicalize().is_ok_and(|b| a == b));
+    match (&task.cache_type, task.pattern.name.as_str()) {
+        (CacheType::Rust, "cargo_target") if parent.join("Cargo.toml").is_file() => {
+            let output = runner
+                .run("cargo", &["metadata", "--format-version", "1", "--no-deps", "--offline"], parent)
+                .ok()
+                .filter(|output| output.success)?;
+            let metadata: serde_json::Value = serde_json::from_str(&output.stdout).ok()?;
+            let target_dir = PathBuf::from(metadata.get("target_directory")?.as_str()?);
+            same(&target_dir, &task.path).then(|| NativeCleanCommand {
+                program: "cargo",
+                args: &["clean"],
+                working_dir: parent.to_path_buf(),
+            })
+        }
+        (CacheType::Go, "go_build_cache" | "xdg_go") if task.path.file_name()? == "go-build" => {
+            let output = runner.run("go", &["env", "GOCACHE"], parent).ok().filter(|output| output.success)?;
+            let gocache = PathBuf::from(output.stdout.trim());
+            same(&gocache, &task.path).then(|| NativeCleanCommand {
+                program: "go",
+                args: &["clean", "-cache"],
+                working_dir: parent.to_path_buf(),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn remove_path_once(path: &Path) -> std::io::Result<()> {
+    if std::fs::symlink_metadata(path)?.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::CommandOutput;
+    use crate::trash::undo_last;
+    use tempfile::TempDir;
+
+    async fn run_clean(root: &Path, cache_types: Vec<CacheType>, config: CleanerConfig) -> CleanResult {
+        let cleaner = CacheCleaner::new(root.to_path_buf(), cache_types, config);
+        cleaner
+            .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_reset_locks_only_with_flag_and_selected_types() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for lockfile in ["package-lock.json", "yarn.lock", "poetry.lock", "Cargo.lock"] {
+            std::fs::write(root.join(lockfile), "lock").unwrap();
+        }
+
+        let libraries = CleanerConfig {
+            include_libraries: true,
+            ..CleanerConfig::default()
+        };
+        run_clean(root, vec![CacheType::Node, CacheType::Python], libraries.clone()).await;
+        assert!(root.join("package-lock.json").exists());
+        assert!(root.join("poetry.lock").exists());
+
+        let reset_locks = CleanerConfig {
+            reset_locks: true,
+            ..libraries
+        };
+        run_clean(root, vec![CacheType::Node], reset_locks).await;
+        assert!(!root.join("package-lock.json").exists());
+        assert!(!root.join("yarn.lock").exists());
+        assert!(root.join("poetry.lock").exists());
+        assert!(root.join("Cargo.lock").exists());
+    }
+
+    #[tokio::test]
+    async fn test_targets_round_trip_from_json_scan() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in ["a/__pycache__", "b/__pycache__", "c/__pycache__"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+
+        let scan_config = CleanerConfig {
+            dry_run: true,
+            recursive: true,
+            ..CleanerConfig::default()
+        };
+        let scan = run_clean(root, vec![CacheType::Python], scan_config).await;
+        assert_eq!(scan.items.len(), 3);
+        let report = crate::report::JsonReport {
+            schema_version: crate::report::SCHEMA_VERSION,
+            root,
+            roots: &[root.to_path_buf()],
+            dry_run: true,
+            status: scan.status,
+            items_cleaned: scan.directories_cleaned,
+            files: scan.files_deleted,
+            bytes_freed: scan.space_freed,
+            apparent_bytes_freed: scan.space_freed,
+            items: &scan.items,
+            omitted: &scan.omitted,
+            by_type: &[],
+            errors: &scan.errors,
+            age_histogram: None,
+            budget: None,
+            duplicates: None,
+        };
+        let mut targets = crate::targets::parse_targets(&serde_json::to_string(&report).unwrap()).unwrap();
+        assert_eq!(targets.len(), 3);
+
+        // Things change between review and execution: one item vanishes and a non-cache path is added
+        std::fs::remove_dir_all(root.join("c/__pycache__")).unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        targets.push(crate::targets::Target {
+            path: root.join("src"),
+            cache_type: CacheType::Python,
+        });
+        std::fs::create_dir_all(root.join("d/__pycache__")).unwrap();
+
+        let config = CleanerConfig {
+            targets: Some(targets),
+            ..CleanerConfig::default()
+        };
+        let result = run_clean(root, vec![CacheType::Python], config).await;
+        assert_eq!(result.directories_cleaned, 2);
+        assert!(result.errors.is_empty());
+        assert!(!root.join("a/__pycache__").exists());
+        assert!(!root.join("b/__pycache__").exists());
+        assert!(root.join("src").exists());
+        // Items that were not in the reviewed scan are left alone
+        assert!(root.join("d/__pycache__").exists());
+    }
+
+    #[tokio::test]
+    async fn test_items_record_deletion_time() {
+        let temp_dir = TempDir::new().unwrap();
+        let pycache = temp_dir.path().join("__pycache__");
+        std::fs::create_dir_all(&pycache).unwrap();
+        std::fs::write(pycache.join("module.pyc"), vec![0u8; 2048]).unwrap();
+
+        let result = run_clean(temp_dir.path(), vec![CacheType::Python], CleanerConfig::default()).await;
+        assert_eq!(result.items.len(), 1);
+        assert!(result.items[0].duration_secs > 0.0);
+        assert_eq!(throughput(2048, Duration::from_millis(500)), 4096);
+        assert_eq!(throughput(1, Duration::ZERO), 1_000_000);
+    }
+
+    /// Answers every prompt with a fixed reply and counts how often it was asked
+    struct MockConfirm {
+        answer: bool,
+        interactive: bool,
+        asked: AtomicU64,
+        last_message: std::sync::Mutex<String>,
+    }
+
+    impl MockConfirm {
+        fn new(answer: bool) -> Self {
+            Self {
+                answer,
+                interactive: true,
+                asked: AtomicU64::new(0),
+                last_message: std::sync::Mutex::new(String::new()),
+            }
+        }
+    }
+
+    impl Confirm for MockConfirm {
+        fn confirm(&self, message: &str) -> bool {
+            self.asked.fetch_add(1, Ordering::Relaxed);
+            *self.last_message.lock().unwrap() = message.to_string();
+            self.answer
+        }
+
+        fn can_prompt(&self) -> bool {
+            self.interactive
+        }
+    }
+
+    /// Docker stand-in whose prune only succeeds if file cleaning finishes while it runs
+    struct MockDocker {
+        watched: PathBuf,
+        saw_files_cleaned: std::sync::atomic::AtomicBool,
+    }
+
+    impl CommandRunner for MockDocker {
+        fn run(&self, program: &str, args: &[&str], _working_dir: &Path) -> Result<CommandOutput> {
+            assert_eq!(program, "docker");
+            let success = match args {
+                ["system", "prune", ..] => {
+                    let deadline = Instant::now() + Duration::from_secs(5);
+                    while self.watched.exists() && Instant::now() < deadline {
+                        std::thread::sleep(Duration::from_millis(10));
+                    }
+                    self.saw_files_cleaned.store(!self.watched.exists(), Ordering::Relaxed);
+                    true
+                }
+                ["volume", "prune", ..] => false,
+                _ => true,
+            };
+            Ok(CommandOutput {
+                success,
+                stderr: if success { String::new() } else { "volume in use".to_string() },
+                ..CommandOutput::default()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_docker_and_file_cleaning_run_concurrently() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let pycache = root.join("__pycache__");
+        std::fs::create_dir_all(&pycache).unwrap();
+
+        let docker = Arc::new(MockDocker {
+            watched: pycache.clone(),
+            saw_files_cleaned: std::sync::atomic::AtomicBool::new(false),
+        });
+        let config = CleanerConfig {
+            docker: true,
+            ..CleanerConfig::default()
+        };
+        let result = CacheCleaner::new(root.to_path_buf(), vec![CacheType::Python, CacheType::Docker], config)
+            .with_runner(docker.clone())
+            .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+            .await
+            .unwrap();
+
+        assert!(docker.saw_files_cleaned.load(Ordering::Relaxed));
+        assert_eq!(result.directories_cleaned, 1);
+        assert_eq!(result.items.len(), 1);
+        assert_eq!(result.errors, vec![CleanError::Docker("Docker volume prune failed: volume in use".to_string())]);
+    }
+
+    /// Docker stand-in that counts prunes
+    #[derive(Default)]
+    struct CountingDocker {
+        prunes: AtomicU64,
+    }
+
+    impl CommandRunner for CountingDocker {
+        fn run(&self, _program: &str, args: &[&str], _working_dir: &Path) -> Result<CommandOutput> {
+            if args.starts_with(&["system", "prune"]) {
+                self.prunes.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(CommandOutput {
+                success: true,
+                ..CommandOutput::default()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_docker_is_pruned_once_per_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let roots: Vec<PathBuf> = ["web", "api"].iter().map(|name| temp_dir.path().join(name)).collect();
+        for root in &roots {
+            std::fs::create_dir_all(root.join("__pycache__")).unwrap();
+        }
+
+        for parallel_roots in [1, 2] {
+            let config = CleanerConfig {
+                docker: true,
+                parallel_roots,
+                ..CleanerConfig::default()
+            };
+            let docker = Arc::new(CountingDocker::default());
+            let run = RunState {
+                runner: docker.clone(),
+                ..RunState::new(&config, false)
+            };
+            let types = [CacheType::Python, CacheType::Docker];
+            let results = clean_roots_with(run, &roots, &types, &config, Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+                .await
+                .unwrap();
+
+            assert_eq!(docker.prunes.load(Ordering::Relaxed), 1);
+            assert_eq!(results.aggregate().directories_cleaned, 3);
+            for root in &roots {
+                std::fs::create_dir_all(root.join("__pycache__")).unwrap();
+            }
+        }
+    }
+
+    /// A Docker whose daemon answers `docker info` only after some failed attempts
+    struct StartingDocker {
+        binary: bool,
+        failures_left: AtomicU64,
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl CommandRunner for StartingDocker {
+        fn run(&self, _program: &str, args: &[&str], _working_dir: &Path) -> Result<CommandOutput> {
+            if !self.binary {
+                return Err(anyhow::anyhow!("Failed to run 'docker'"));
+            }
+            self.calls.lock().unwrap().push(args.join(" "));
+            let ready = args[0] != "info"
+                || self
+                    .failures_left
+                    .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                    .is_err();
+            Ok(CommandOutput {
+                success: ready,
+                stderr: if ready { String::new() } else { "Cannot connect to the Docker daemon".to_string() },
+                ..CommandOutput::default()
+            })
+        }
+    }
+
+    #[test]
+    fn test_docker_waits_for_daemon() {
+        let starting = StartingDocker {
+            binary: true,
+            failures_left: AtomicU64::new(2),
+            calls: Mutex::new(Vec::new()),
+        };
+        clean_docker_caches(&starting, Path::new("."), Duration::from_secs(5)).unwrap();
+        assert_eq!(
+            *starting.calls.lock().unwrap(),
+            vec!["--version", "info", "info", "info", "system prune -af", "volume prune -f"]
+        );
+
+        // A daemon that stays down gives up at the deadline without pruning
+        let down = StartingDocker {
+            binary: true,
+            failures_left: AtomicU64::new(u64::MAX),
+            calls: Mutex::new(Vec::new()),
+        };
+        let error = clean_docker_caches(&down, Path::new("."), Duration::ZERO).unwrap_err();
+        assert!(error.to_string().contains("did not respond"));
+        assert_eq!(*down.calls.lock().unwrap(), vec!["--version", "info"]);
+
+        // A missing binary fails at once
+        let missing = StartingDocker {
+            binary: false,
+            failures_left: AtomicU64::new(0),
+            calls: Mutex::new(Vec::new()),
+        };
+        let started = Instant::now();
+        let error = clean_docker_caches(&missing, Path::new("."), Duration::from_secs(5)).unwrap_err();
+        assert_eq!(error.to_string(), "Docker is not available");
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_deferred_sizing_skips_walk_until_confirmed() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let pycache = root.join("__pycache__");
+        std::fs::create_dir_all(&pycache).unwrap();
+        std::fs::write(pycache.join("module.pyc"), vec![0u8; 1024]).unwrap();
+
+        let preview = |answer: bool| {
+            let confirmer = Arc::new(MockConfirm::new(answer));
+            let config = CleanerConfig {
+                dry_run: true,
+                defer_sizing: true,
+                apparent_size: true,
+                ..CleanerConfig::default()
+            };
+            let cleaner = CacheCleaner::new(root.to_path_buf(), vec![CacheType::Python], config)
+                .with_confirmer(confirmer.clone());
+            async move {
+                let result = cleaner
+                    .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+                    .await
+                    .unwrap();
+                (result, confirmer.asked.load(Ordering::Relaxed))
+            }
+        };
+
+        // Declining leaves the items unsized: no walk took place
+        let (result, asked) = preview(false).await;
+        assert_eq!(asked, 1);
+        assert_eq!(result.items.len(), 1);
+        assert!(!result.items[0].size_known);
+        assert_eq!((result.items[0].files, result.items[0].size), (0, 0));
+        assert_eq!(result.space_freed, 0);
+
+        let (result, _) = preview(true).await;
+        assert!(result.items[0].size_known);
+        assert_eq!((result.items[0].files, result.items[0].size), (1, 1024));
+        assert_eq!(result.space_freed, 1024);
+        assert!(pycache.exists());
+    }
+
+    #[tokio::test]
+    async fn test_skip_network_filesystems() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("local/__pycache__")).unwrap();
+        std::fs::create_dir_all(root.join("mounted/__pycache__")).unwrap();
+
+        let clean_with = |skip_network: bool| {
+            let config = CleanerConfig {
+                recursive: true,
+                skip_network,
+                ..CleanerConfig::default()
+            };
+            let probe: FilesystemProbe = Arc::new(|path: &Path| {
+                let fstype = if path.to_string_lossy().contains("mounted") { "nfs4" } else { "ext4" };
+                Some(fstype.to_string())
+            });
+            let cleaner = CacheCleaner::new(root.to_path_buf(), vec![CacheType::Python], config)
+                .with_filesystem_probe(probe);
+            async move {
+                cleaner
+                    .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let result = clean_with(true).await;
+        assert_eq!(result.directories_cleaned, 1);
+        assert!(!root.join("local/__pycache__").exists());
+        assert!(root.join("mounted/__pycache__").exists());
+
+        // Without the flag network items are only warned about
+        clean_with(false).await;
+        assert!(!root.join("mounted/__pycache__").exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_skip_in_use_keeps_open_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("busy/__pycache__")).unwrap();
+        std::fs::create_dir_all(root.join("idle/__pycache__")).unwrap();
+        let held = std::fs::File::create(root.join("busy/__pycache__/module.pyc")).unwrap();
+
+        let config = CleanerConfig {
+            recursive: true,
+            skip_in_use: true,
+            ..CleanerConfig::default()
+        };
+        let result = CacheCleaner::new(root.to_path_buf(), vec![CacheType::Python], config)
+            .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+            .await
+            .unwrap();
+
+        assert_eq!(result.directories_cleaned, 1);
+        assert!(root.join("busy/__pycache__").exists());
+        assert!(!root.join("idle/__pycache__").exists());
+        drop(held);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_skip_active_builds_keeps_locked_target() {
+        use std::os::unix::io::AsRawFd;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for project in ["building", "idle"] {
+            std::fs::create_dir_all(root.join(project).join("target/debug")).unwrap();
+            std::fs::write(root.join(project).join("Cargo.toml"), "[package]").unwrap();
+            std::fs::write(root.join(project).join("target/debug/.cargo-lock"), "").unwrap();
+        }
+        // As `cargo build` holds it; the idle project's lock is left over from an earlier build
+        let lock = std::fs::File::open(root.join("building/target/debug/.cargo-lock")).unwrap();
+        assert_eq!(unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX) }, 0);
+
+        let config = CleanerConfig {
+            recursive: true,
+            include_libraries: true,
+            skip_active_builds: true,
+            ..CleanerConfig::default()
+        };
+        let result = run_clean(root, vec![CacheType::Rust], config).await;
+
+        assert_eq!(result.skipped, vec![(root.join("building/target"), SkipReason::ActiveBuild)]);
+        assert!(root.join("building/target/debug").exists());
+        assert!(!root.join("idle/target").exists());
+        drop(lock);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_check_permissions_flags_unwritable_items() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Root may delete regardless of permission bits, so nothing would be flagged
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("ok/__pycache__")).unwrap();
+        let locked = root.join("locked/__pycache__");
+        std::fs::create_dir_all(locked.join("nested")).unwrap();
+        std::fs::write(locked.join("nested/data.bin"), "data").unwrap();
+        std::fs::set_permissions(locked.join("nested"), std::fs::Permissions::from_mode(0o555)).unwrap();
+
+        let config = CleanerConfig {
+            recursive: true,
+            dry_run: true,
+            check_permissions: true,
+            ..CleanerConfig::default()
+        };
+        let result = run_clean(root, vec![CacheType::Python], config).await;
+        std::fs::set_permissions(locked.join("nested"), std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert_eq!(result.items.len(), 2);
+        assert_eq!(result.errors.len(), 1);
+        assert!(matches!(
+            &result.errors[0],
+            CleanError::Item { path, kind: ErrorKind::PermissionDenied, .. } if path.ends_with("locked/__pycache__")
+        ));
+        assert!(locked.join("nested/data.bin").exists());
+    }
+
+    #[tokio::test]
+    async fn test_artifact_archive_is_a_single_file_item() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("ci")).unwrap();
+        std::fs::write(root.join("ci/artifacts.zip"), vec![0u8; 3000]).unwrap();
+        std::fs::write(root.join("ci/deps.cache.tar"), vec![0u8; 500]).unwrap();
+        std::fs::write(root.join("ci/release.zip"), vec![0u8; 100]).unwrap();
+
+        let config = CleanerConfig {
+            recursive: true,
+            dry_run: true,
+            apparent_size: true,
+            ..CleanerConfig::default()
+        };
+        let result = run_clean(root, vec![CacheType::General], config).await;
+
+        assert_eq!(result.items.len(), 2);
+        let archive = result.items.iter().find(|item| item.path.ends_with("ci/artifacts.zip")).unwrap();
+        assert_eq!(archive.pattern_name, "artifact_archives");
+        assert_eq!((archive.files, archive.size), (1, 3000));
+        assert_eq!(result.space_freed, 3500);
+    }
+
+    #[tokio::test]
+    async fn test_library_deletion_waits_for_confirmation() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        let clean_with = |answer: bool| {
+            std::fs::create_dir_all(root.join("web/node_modules/left-pad")).unwrap();
+            std::fs::create_dir_all(root.join("api/__pycache__")).unwrap();
+            let confirmer = Arc::new(MockConfirm::new(answer));
+            let config = CleanerConfig {
+                recursive: true,
+                include_libraries: true,
+                confirm_libraries: true,
+                ..CleanerConfig::default()
+            };
+            let cleaner = CacheCleaner::new(root.to_path_buf(), vec![CacheType::Node, CacheType::Python], config)
+                .with_confirmer(confirmer.clone());
+            async move {
+                let result = cleaner
+                    .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+                    .await
+                    .unwrap();
+                (result, confirmer)
+            }
+        };
+
+        // Declining keeps libraries but still cleans safe caches
+        let (result, confirmer) = clean_with(false).await;
+        assert_eq!(confirmer.asked.load(Ordering::Relaxed), 1);
+        assert!(confirmer.last_message.lock().unwrap().contains("node_modules"));
+        assert_eq!(result.directories_cleaned, 1);
+        assert!(root.join("web/node_modules").exists());
+        assert!(!root.join("api/__pycache__").exists());
+
+        let (result, _) = clean_with(true).await;
+        assert_eq!(result.directories_cleaned, 2);
+        assert!(!root.join("web/node_modules").exists());
+
+        // Nothing to ask about without library items
+        std::fs::remove_dir_all(root.join("web")).unwrap();
+        let config = CleanerConfig {
+            recursive: true,
+            include_libraries: true,
+            confirm_libraries: true,
+            ..CleanerConfig::default()
+        };
+        let confirmer = Arc::new(MockConfirm::new(false));
+        std::fs::create_dir_all(root.join("api/__pycache__")).unwrap();
+        CacheCleaner::new(root.to_path_buf(), vec![CacheType::Python], config)
+            .with_confirmer(confirmer.clone())
+            .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+            .await
+            .unwrap();
+        assert_eq!(confirmer.asked.load(Ordering::Relaxed), 0);
+        assert!(!root.join("api/__pycache__").exists());
+    }
+
+    #[tokio::test]
+    async fn test_refuses_to_delete_working_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("app/__pycache__/nested")).unwrap();
+        std::fs::create_dir_all(root.join("lib/__pycache__")).unwrap();
+
+        let config = CleanerConfig {
+            recursive: true,
+            ..CleanerConfig::default()
+        };
+        for working_dir in [root.join("app/__pycache__"), root.join("app/__pycache__/nested")] {
+            let result = CacheCleaner::new(root.to_path_buf(), vec![CacheType::Python], config.clone())
+                .with_working_dir(working_dir)
+                .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+                .await
+                .unwrap();
+
+            assert!(root.join("app/__pycache__").exists());
+            assert_eq!(result.errors.len(), 1);
+            assert!(matches!(&result.errors[0], CleanError::Item { kind: ErrorKind::Protected, .. }));
+            assert!(result.errors[0].to_string().contains("current working directory"));
+        }
+        assert!(!root.join("lib/__pycache__").exists());
+    }
+
+    /// Records how many complete lines had been written at each flush
+    #[derive(Default)]
+    struct FlushRecorder {
+        written: Vec<u8>,
+        lines_at_flush: Vec<usize>,
+    }
+
+    impl Write for FlushRecorder {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.lines_at_flush.push(self.written.iter().filter(|b| **b == b'\n').count());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_listing_is_flushed_line_by_line() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for project in ["a", "b", "c"] {
+            std::fs::create_dir_all(root.join(project).join("__pycache__")).unwrap();
+        }
+
+        let recorder = Arc::new(Mutex::new(FlushRecorder::default()));
+        let config = CleanerConfig {
+            recursive: true,
+            dry_run: true,
+            parallel_threads: 1,
+            ..CleanerConfig::default()
+        };
+        CacheCleaner::new(root.to_path_buf(), vec![CacheType::Python], config)
+            .with_output(recorder.clone())
+            .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+            .await
+            .unwrap();
+
+        // Every line is flushed on its own rather than when the run ends
+        let recorder = recorder.lock().unwrap();
+        assert_eq!(recorder.lines_at_flush, vec![1, 2, 3]);
+        assert_eq!(String::from_utf8_lossy(&recorder.written).matches("Would delete:").count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_skip_notices_go_to_the_warning_sink() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("crowded/__pycache__")).unwrap();
+        for i in 0..3 {
+            std::fs::write(root.join(format!("crowded/__pycache__/{}.bin", i)), "x").unwrap();
+        }
+
+        let warnings = Arc::new(Mutex::new(FlushRecorder::default()));
+        let config = CleanerConfig {
+            recursive: true,
+            max_file_count: Some(1),
+            ..CleanerConfig::default()
+        };
+        CacheCleaner::new(root.to_path_buf(), vec![CacheType::Python], config)
+            .with_warnings(warnings.clone())
+            .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+            .await
+            .unwrap();
+
+        let warnings = warnings.lock().unwrap();
+        let text = String::from_utf8_lossy(&warnings.written);
+        assert!(text.contains("max-file-count"), "{}", text);
+        assert_eq!(warnings.lines_at_flush, vec![1]);
+    }
+
+    /// A terminal that keeps everything drawn to it
+    #[derive(Debug, Default, Clone)]
+    struct RecordingTerm(Arc<Mutex<String>>);
+
+    impl indicatif::TermLike for RecordingTerm {
+        fn width(&self) -> u16 {
+            80
+        }
+        fn move_cursor_up(&self, _n: usize) -> std::io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_down(&self, _n: usize) -> std::io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_right(&self, _n: usize) -> std::io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_left(&self, _n: usize) -> std::io::Result<()> {
+            Ok(())
+        }
+        fn write_line(&self, s: &str) -> std::io::Result<()> {
+            self.0.lock().unwrap().push_str(&format!("{}\n", s));
+            Ok(())
+        }
+        fn write_str(&self, s: &str) -> std::io::Result<()> {
+            self.0.lock().unwrap().push_str(s);
+            Ok(())
+        }
+        fn clear_line(&self) -> std::io::Result<()> {
+            self.0.lock().unwrap().push('\r');
+            Ok(())
+        }
+        fn flush(&self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_progress_stays_off_output() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("api/__pycache__")).unwrap();
+
+        let output = Arc::new(Mutex::new(FlushRecorder::default()));
+        let term = RecordingTerm::default();
+        let progress_term = term.clone();
+        let config = CleanerConfig {
+            recursive: true,
+            dry_run: true,
+            verbose: true,
+            ..CleanerConfig::default()
+        };
+        CacheCleaner::new(root.to_path_buf(), vec![CacheType::Python], config)
+            .with_output(output.clone())
+            .with_progress_target(Arc::new(move || ProgressDrawTarget::term_like(Box::new(progress_term.clone()))))
+            .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+            .await
+            .unwrap();
+
+        let progress = term.0.lock().unwrap();
+        assert!(progress.contains("Found 1 cache items to clean"));
+        assert!(progress.contains('\r'));
+
+        let output = output.lock().unwrap();
+        let listing = String::from_utf8_lossy(&output.written);
+        assert!(listing.contains("Would delete:"));
+        assert!(!listing.contains("cache items to clean"));
+        assert!(!listing.contains('\r'));
+    }
+
+    #[tokio::test]
+    async fn test_confirm_large_prompts_above_threshold() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let pycache = root.join("__pycache__");
+        std::fs::create_dir_all(&pycache).unwrap();
+        std::fs::write(pycache.join("module.pyc"), vec![0u8; 4096]).unwrap();
+
+        let clean_with = |threshold: u64, answer: bool| {
+            let confirmer = Arc::new(MockConfirm::new(answer));
+            let config = CleanerConfig {
+                confirm_large: Some(threshold),
+                apparent_size: true,
+                ..CleanerConfig::default()
+            };
+            let cleaner = CacheCleaner::new(root.to_path_buf(), vec![CacheType::Python], config)
+                .with_confirmer(confirmer.clone());
+            async move {
+                let result = cleaner
+                    .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+                    .await
+                    .unwrap();
+                let message = confirmer.last_message.lock().unwrap().clone();
+                (result, confirmer.asked.load(Ordering::Relaxed), message)
+            }
+        };
+
+        // Declining a large clean deletes nothing; the prompt shows what's inside
+        let (result, asked, message) = clean_with(1024, false).await;
+        assert_eq!(asked, 1);
+        assert!(message.contains("contains: module.pyc"));
+        assert_eq!(result.status, CleanStatus::Aborted);
+        assert!(result.errors.is_empty());
+        assert!(pycache.exists());
+
+        // With nobody to ask, the run stops with an error instead of taking EOF as "no"
+        let closed = Arc::new(MockConfirm {
+            interactive: false,
+            ..MockConfirm::new(true)
+        });
+        let config = CleanerConfig {
+            confirm_large: Some(1024),
+            ..CleanerConfig::default()
+        };
+        let result = CacheCleaner::new(root.to_path_buf(), vec![CacheType::Python], config)
+            .with_confirmer(closed.clone())
+            .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+            .await
+            .unwrap();
+        assert_eq!(closed.asked.load(Ordering::Relaxed), 0);
+        assert_eq!(result.status, CleanStatus::Aborted);
+        assert!(matches!(&result.errors[..], [CleanError::Other(reason)] if reason.contains("no terminal")));
+        assert!(pycache.exists());
+
+        // Below the threshold there is no prompt
+        let (result, asked, _) = clean_with(1 << 20, false).await;
+        assert_eq!(asked, 0);
+        assert_eq!(result.status, CleanStatus::Success);
+        assert_eq!(result.space_freed, 4096);
+        assert!(!pycache.exists());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_clear_readonly_removes_readonly_files() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let cache_dir = temp_dir.path().join("cache");
+        std::fs::create_dir_all(&cache_dir).unwrap();
+        let file = cache_dir.join("packed.bin");
+        std::fs::write(&file, "data").unwrap();
+        let mut permissions = std::fs::metadata(&file).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&file, permissions).unwrap();
+
+        let task = CleanTask {
+            path: cache_dir.clone(),
+            pattern: CacheType::General.get_patterns().remove(0),
+            cache_type: CacheType::General,
+            modified: None,
+            sized: None,
+        };
+
+        let cleaner = CacheCleaner::new(temp_dir.path().to_path_buf(), vec![CacheType::General], CleanerConfig::default());
+        assert!(cleaner.clean_item(&task).is_err());
+        assert!(cache_dir.exists());
+
+        let config = CleanerConfig {
+            clear_readonly: true,
+            ..CleanerConfig::default()
+        };
+        let cleaner = CacheCleaner::new(temp_dir.path().to_path_buf(), vec![CacheType::General], config);
+        cleaner.clean_item(&task).unwrap();
+        assert!(!cache_dir.exists());
+    }
+
+    #[tokio::test]
+    async fn test_priority_cleans_types_in_order() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for project in ["a", "b", "c", "d"] {
+            std::fs::create_dir_all(root.join(project).join("node_modules/left-pad")).unwrap();
+            std::fs::create_dir_all(root.join(project).join("__pycache__")).unwrap();
+            std::fs::create_dir_all(root.join(project).join("build")).unwrap();
+        }
+
+        let config = CleanerConfig {
+            recursive: true,
+            include_libraries: true,
+            parallel_threads: 4,
+            priority: vec![CacheType::Python, CacheType::General],
+            ..CleanerConfig::default()
+        };
+        let result = run_clean(root, vec![CacheType::Node, CacheType::Python, CacheType::General], config).await;
+
+        let order: Vec<&CacheType> = result.items.iter().map(|item| &item.cache_type).collect();
+        assert_eq!(order.len(), 12);
+        assert!(order[..4].iter().all(|t| **t == CacheType::Python));
+        assert!(order[4..8].iter().all(|t| **t == CacheType::General));
+        assert!(order[8..].iter().all(|t| **t == CacheType::Node));
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_roots_processed_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("code");
+        std::fs::create_dir_all(root.join("web/node_modules/left-pad")).unwrap();
+        std::fs::write(root.join("web/node_modules/left-pad/index.js"), "module.exports = 1").unwrap();
+        std::fs::create_dir_all(root.join("api/__pycache__")).unwrap();
+
+        let config = CleanerConfig {
+            recursive: true,
+            dry_run: true,
+            include_libraries: true,
+            ..CleanerConfig::default()
+        };
+        let types = vec![CacheType::Node, CacheType::Python];
+        // The second root is inside the first and the third reaches it through `..`
+        let roots = vec![root.clone(), root.join("web"), root.join("api/../web")];
+        let total_files = Arc::new(AtomicU64::new(0));
+
+        let deduped = clean_roots(&roots, &types, &config, true, Arc::new(AtomicU64::new(0)), total_files.clone())
+            .await
+            .unwrap();
+        let counts: Vec<usize> = deduped.roots.iter().map(|(_, result)| result.items.len()).collect();
+        assert_eq!(counts, vec![2, 0, 0]);
+        assert_eq!(deduped.roots[0].0, root);
+        assert_eq!(total_files.load(Ordering::Relaxed), 1);
+
+        let repeated = clean_roots(&roots, &types, &config, false, Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+            .await
+            .unwrap();
+        let counts: Vec<usize> = repeated.roots.iter().map(|(_, result)| result.items.len()).collect();
+        assert_eq!(counts, vec![2, 1, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_attributes_file_marks_extra_caches() {
+        for parallel_threads in [1, 4] {
+            let temp_dir = TempDir::new().unwrap();
+            let root = temp_dir.path();
+            std::fs::create_dir_all(root.join("web/js-bundle")).unwrap();
+            std::fs::write(root.join("web/js-bundle/app.js"), "bundled").unwrap();
+            std::fs::create_dir_all(root.join("web/vendored/js-bundle")).unwrap();
+            std::fs::create_dir_all(root.join("docs/js-bundle")).unwrap();
+            std::fs::write(root.join(".clearcacheattributes"), "js-bundle cache type=node\n").unwrap();
+            // A deeper file overrides the root's rule for its own subtree
+            std::fs::write(root.join("web/vendored/.clearcacheattributes"), "js-bundle -cache\n").unwrap();
+
+            let config = CleanerConfig {
+                recursive: true,
+                parallel_threads,
+                ..CleanerConfig::default()
+            };
+            // Attribute rules follow type selection like built-in patterns
+            let unselected = run_clean(root, vec![CacheType::Python], config.clone()).await;
+            assert!(unselected.items.is_empty());
+
+            let result = run_clean(root, vec![CacheType::Node], config).await;
+            let mut cleaned: Vec<(PathBuf, CacheType)> = result
+                .items
+                .iter()
+                .map(|item| (item.path.strip_prefix(root).unwrap_or(&item.path).to_path_buf(), item.cache_type.clone()))
+                .collect();
+            cleaned.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(
+                cleaned,
+                vec![
+                    (PathBuf::from("docs/js-bundle"), CacheType::Node),
+                    (PathBuf::from("web/js-bundle"), CacheType::Node),
+                ]
+            );
+            assert!(!root.join("web/js-bundle").exists());
+            assert!(root.join("web/vendored/js-bundle").exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_status_for_each_outcome() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let config = CleanerConfig {
+            recursive: true,
+            ..CleanerConfig::default()
+        };
+        let cleaner = |config: CleanerConfig| CacheCleaner::new(root.to_path_buf(), vec![CacheType::Python], config);
+        let run = |cleaner: CacheCleaner| async move {
+            cleaner
+                .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+                .await
+                .unwrap()
+                .status
+        };
+
+        assert_eq!(run(cleaner(config.clone())).await, CleanStatus::NothingFound);
+
+        std::fs::create_dir_all(root.join("app/__pycache__")).unwrap();
+        std::fs::write(root.join("app/__pycache__/module.pyc"), vec![0u8; 4096]).unwrap();
+        let declined = CleanerConfig {
+            confirm_large: Some(0),
+            ..config.clone()
+        };
+        let status = run(cleaner(declined).with_confirmer(Arc::new(MockConfirm::new(false)))).await;
+        assert_eq!(status, CleanStatus::Aborted);
+
+        // Deleting the working directory is refused, which is an error
+        let status = run(cleaner(config.clone()).with_working_dir(root.join("app/__pycache__"))).await;
+        assert_eq!(status, CleanStatus::CompletedWithErrors);
+
+        assert_eq!(run(cleaner(config)).await, CleanStatus::Success);
+        assert!(!root.join("app/__pycache__").exists());
+
+        // Merged results keep the most severe outcome
+        let mut merged = CleanResult::default();
+        for status in [CleanStatus::Success, CleanStatus::CompletedWithErrors, CleanStatus::NothingFound] {
+            merged.merge(CleanResult {
+                status,
+                ..CleanResult::default()
+            });
+        }
+        assert_eq!(merged.status, CleanStatus::CompletedWithErrors);
+    }
+
+    #[tokio::test]
+    async fn test_path_glob_trailing_slash_matches_same_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in ["node_modules", "web/node_modules", "apps/api/node_modules"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+
+        let mut found = Vec::new();
+        for glob in ["node_modules", "node_modules/", "node_modules//"] {
+            let config = CleanerConfig {
+                recursive: true,
+                dry_run: true,
+                path_globs: vec![glob.to_string()],
+                ..CleanerConfig::default()
+            };
+            let result = run_clean(root, vec![CacheType::Python], config).await;
+            let mut paths: Vec<PathBuf> = result.items.into_iter().map(|item| item.path).collect();
+            paths.sort();
+            found.push(paths);
+        }
+        assert_eq!(found[0].len(), 3);
+        assert_eq!(found[0], found[1]);
+        assert_eq!(found[0], found[2]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_follow_links_cleans_symlinked_cache() {
+        for parallel_threads in [1, 4] {
+            let temp_dir = TempDir::new().unwrap();
+            let root = temp_dir.path().join("work");
+            let scratch = temp_dir.path().join("scratch/pycache");
+            std::fs::create_dir_all(root.join("app")).unwrap();
+            std::fs::create_dir_all(&scratch).unwrap();
+            std::fs::write(scratch.join("index.bin"), vec![0u8; 4096]).unwrap();
+            let link = root.join("app/__pycache__");
+            std::os::unix::fs::symlink(&scratch, &link).unwrap();
+            // A loop back to the root, and a second link to the same cache
+            std::os::unix::fs::symlink(&root, root.join("app/loop")).unwrap();
+            std::os::unix::fs::symlink(&scratch, root.join("__pycache__")).unwrap();
+
+            let config = |follow_links| CleanerConfig {
+                parallel_threads,
+                recursive: true,
+                follow_links,
+                ..CleanerConfig::default()
+            };
+
+            let result = run_clean(&root, vec![CacheType::Python], config(false)).await;
+            assert!(result.items.is_empty());
+            assert!(scratch.join("index.bin").exists());
+
+            let result = run_clean(&root, vec![CacheType::Python], config(true)).await;
+            assert_eq!(result.items.len(), 1);
+            assert_eq!(result.files_deleted, 1);
+            assert!(!scratch.join("index.bin").exists());
+            assert!(link.is_symlink() && link.is_dir());
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_follow_links_never_follows_nested_links() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("work");
+        let scratch = temp_dir.path().join("scratch/pycache");
+        let precious = temp_dir.path().join("precious");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::create_dir_all(&scratch).unwrap();
+        std::fs::create_dir_all(&precious).unwrap();
+        std::fs::write(scratch.join("index.bin"), vec![0u8; 4096]).unwrap();
+        std::fs::write(precious.join("notes.txt"), "keep me").unwrap();
+        // One link inside the cache leads out of it, another back into it
+        std::os::unix::fs::symlink(&precious, scratch.join("outside")).unwrap();
+        std::os::unix::fs::symlink(&scratch, scratch.join("cycle")).unwrap();
+        std::os::unix::fs::symlink(&scratch, root.join("__pycache__")).unwrap();
+
+        let config = CleanerConfig {
+            follow_links: true,
+            ..CleanerConfig::default()
+        };
+        let result = run_clean(&root, vec![CacheType::Python], config.clone()).await;
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.items.len(), 1);
+        assert_eq!(std::fs::read_dir(&scratch).unwrap().count(), 0);
+        assert_eq!(std::fs::read_to_string(precious.join("notes.txt")).unwrap(), "keep me");
+
+        // The link's target is checked too: emptying the working directory is refused
+        std::fs::write(scratch.join("index.bin"), "cache").unwrap();
+        let result = CacheCleaner::new(root.clone(), vec![CacheType::Python], config)
+            .with_working_dir(scratch.clone())
+            .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+            .await
+            .unwrap();
+        assert_eq!(result.status, CleanStatus::CompletedWithErrors);
+        assert!(scratch.join("index.bin").exists());
+    }
+
+    #[tokio::test]
+    async fn test_truncate_logs_empties_files_in_place() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::write(root.join("server.log"), "request served\n".repeat(100)).unwrap();
+        std::fs::create_dir(root.join("logs")).unwrap();
+        std::fs::write(root.join("logs/old.txt"), "rotated").unwrap();
+
+        let config = CleanerConfig {
+            truncate_logs: true,
+            ..CleanerConfig::default()
+        };
+        let result = run_clean(root, vec![CacheType::General], config).await;
+        assert_eq!(result.directories_cleaned, 2);
+        assert_eq!(std::fs::metadata(root.join("server.log")).unwrap().len(), 0);
+        // Directories matched by a truncatable pattern are still removed
+        assert!(!root.join("logs").exists());
+    }
+
+    #[tokio::test]
+    async fn test_max_items_per_type_totals_the_rest() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for i in 0..5 {
+            std::fs::write(root.join(format!("mod{}.pyc", i)), vec![0u8; 100 * (i + 1)]).unwrap();
+        }
+        std::fs::write(root.join("server.log"), "served").unwrap();
+
+        let config = CleanerConfig {
+            apparent_size: true,
+            max_items_per_type: Some(2),
+            ..CleanerConfig::default()
+        };
+        let result = run_clean(root, vec![CacheType::Python, CacheType::General], config).await;
+
+        let python: Vec<&CleanedItem> = result.items.iter().filter(|i| i.cache_type == CacheType::Python).collect();
+        assert_eq!(python.len(), 2);
+        assert_eq!(result.items.len(), 3);
+        assert_eq!(result.omitted.len(), 1);
+        let omitted = &result.omitted[0];
+        assert_eq!((omitted.cache_type.clone(), omitted.items, omitted.files), (CacheType::Python, 3, 3));
+
+        // Listed and omitted items together account for every match
+        assert_eq!(result.directories_cleaned, 6);
+        let listed: u64 = python.iter().map(|i| i.size).sum();
+        assert_eq!(listed + omitted.size, 1500);
+        assert_eq!(result.space_freed, 1500 + "served".len() as u64);
+    }
+
+    #[tokio::test]
+    async fn test_vcs_directories_never_deleted() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join(".git/logs")).unwrap();
+        std::fs::write(root.join(".git/logs/HEAD"), "history").unwrap();
+        std::fs::create_dir_all(root.join("vendor/.hg")).unwrap();
+        std::fs::create_dir_all(root.join("logs")).unwrap();
+
+        // `logs` matches .git/logs, and the globs name the VCS directories outright
+        let config = CleanerConfig {
+            recursive: true,
+            no_ignore: true,
+            path_globs: vec![".git".to_string(), "vendor/.hg".to_string()],
+            ..CleanerConfig::default()
+        };
+        let result = run_clean(root, vec![CacheType::General], config).await;
+
+        assert!(root.join(".git/logs/HEAD").exists());
+        assert!(root.join("vendor/.hg").exists());
+        assert!(!root.join("logs").exists());
+        // Refusing is the safety check working, not a failure that should fail the run
+        assert!(result.errors.is_empty());
+        assert_eq!(result.status, CleanStatus::Success);
+        let refused: Vec<&Path> = result
+            .skipped
+            .iter()
+            .filter(|(_, reason)| *reason == SkipReason::VcsMetadata)
+            .map(|(path, _)| path.as_path())
+            .collect();
+        assert!(refused.contains(&root.join(".git").as_path()));
+        assert!(refused.contains(&root.join(".git/logs").as_path()));
+        assert!(refused.contains(&root.join("vendor/.hg").as_path()));
+    }
+
+    #[tokio::test]
+    async fn test_rename_to_delete_leaves_no_staging_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in ["a/__pycache__", "b/__pycache__"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join("mod.cpython-312.pyc"), vec![0u8; 4096]).unwrap();
+        }
+
+        let config = CleanerConfig {
+            recursive: true,
+            stage_deletes: true,
+            apparent_size: true,
+            ..CleanerConfig::default()
+        };
+        let result = run_clean(root, vec![CacheType::Python], config).await;
+        assert!(result.errors.is_empty());
+        assert_eq!(result.space_freed, 8192);
+        assert!(!root.join("a/__pycache__").exists());
+        assert!(!root.join("b/__pycache__").exists());
+        assert!(!root.join(format!(".clearcache-staging-{}", std::process::id())).exists());
+    }
+
+    #[tokio::test]
+    async fn test_trash_skips_items_larger_than_free_space() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("work");
+        for (dir, size) in [("small/__pycache__", 1000), ("large/__pycache__", 5000)] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join("data.bin"), vec![0u8; size]).unwrap();
+        }
+
+        let config = |trash: bool| CleanerConfig {
+            recursive: true,
+            trash_dir: trash.then(|| temp_dir.path().join("trash")),
+            check_free_space: true,
+            ..CleanerConfig::default()
+        };
+        // As if the trash were on another filesystem with 4000 bytes free
+        let probe: FreeSpaceProbe = Arc::new(|_: &Path, _: &Path| Some(4000));
+        let clean = |trash: bool| {
+            let cleaner =
+                CacheCleaner::new(root.clone(), vec![CacheType::Python], config(trash)).with_free_space_probe(probe.clone());
+            async move { cleaner.clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0))).await.unwrap() }
+        };
+
+        let result = clean(true).await;
+        assert_eq!(result.directories_cleaned, 1);
+        assert!(!root.join("small/__pycache__").exists());
+        assert!(root.join("large/__pycache__").exists());
+
+        // Deleting directly needs no room, so the same item is cleaned
+        let result = clean(false).await;
+        assert_eq!(result.directories_cleaned, 1);
+        assert!(!root.join("large/__pycache__").exists());
+    }
+
+    #[tokio::test]
+    async fn test_resume_skips_items_completed_before_interruption() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("work");
+        let checkpoint_dir = temp_dir.path().join("checkpoints");
+        for dir in ["a/__pycache__", "b/__pycache__", "c/__pycache__"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+
+        // An interrupted run deleted a and b, recording each as it went; a was regenerated since
+        let interrupted = Checkpoint::open(&checkpoint_dir, &root, false).unwrap();
+        interrupted.record(&root.join("a/__pycache__")).unwrap();
+        interrupted.record(&root.join("b/__pycache__")).unwrap();
+        std::fs::remove_dir(root.join("b/__pycache__")).unwrap();
+        drop(interrupted);
+
+        let config = CleanerConfig {
+            recursive: true,
+            checkpoint_dir: Some(checkpoint_dir.clone()),
+            resume: true,
+            ..CleanerConfig::default()
+        };
+        let result = run_clean(&root, vec![CacheType::Python], config).await;
+        let cleaned: Vec<PathBuf> = result.items.iter().map(|item| item.path.clone()).collect();
+        assert_eq!(cleaned, vec![root.join("c/__pycache__")]);
+        assert!(root.join("a/__pycache__").exists());
+        assert!(!root.join("c/__pycache__").exists());
+
+        // The finished run clears its checkpoint
+        assert_eq!(std::fs::read_dir(&checkpoint_dir).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_max_file_count_skips_crowded_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("small/__pycache__")).unwrap();
+        std::fs::write(root.join("small/__pycache__/data.bin"), "x").unwrap();
+        std::fs::create_dir_all(root.join("crowded/__pycache__")).unwrap();
+        for i in 0..50 {
+            std::fs::write(root.join(format!("crowded/__pycache__/{}.bin", i)), "x").unwrap();
+        }
+
+        let config = CleanerConfig {
+            recursive: true,
+            max_file_count: Some(10),
+            ..Default::default()
+        };
+        let result = run_clean(root, vec![CacheType::Python], config).await;
+
+        assert_eq!(result.directories_cleaned, 1);
+        assert!(!root.join("small/__pycache__").exists());
+        assert_eq!(std::fs::read_dir(root.join("crowded/__pycache__")).unwrap().count(), 50);
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_patterns_prefer_language_type() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("web/build")).unwrap();
+        std::fs::write(root.join("web/build/index.bin"), "x").unwrap();
+
+        let config = CleanerConfig {
+            recursive: true,
+            extra_patterns: vec![(
+                CacheType::Node,
+                CachePattern {
+                    name: "node_build".to_string(),
+                    patterns: vec!["build".to_string()],
+                    description: "Bundler output".to_string(),
+                    is_directory: true,
+                    recursive_safe: true,
+                    is_library: false,
+                    truncatable: false,
+                },
+            )],
+            ..Default::default()
+        };
+        let result = run_clean(root, vec![CacheType::General, CacheType::Node], config).await;
+
+        assert_eq!(result.items.len(), 1);
+        assert_eq!(result.items[0].cache_type, CacheType::Node);
+        assert_eq!(result.items[0].pattern_name, "node_build");
+    }
+
+    #[tokio::test]
+    async fn test_count_only_totals_without_deleting() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for project in ["a", "b"] {
+            std::fs::create_dir_all(root.join(project).join("__pycache__")).unwrap();
+            std::fs::write(root.join(project).join("__pycache__/data.bin"), vec![0u8; 1000]).unwrap();
+        }
+
+        let config = CleanerConfig {
+            recursive: true,
+            dry_run: true,
+            count_only: true,
+            apparent_size: true,
+            ..Default::default()
+        };
+        let result = run_clean(root, vec![CacheType::Python], config).await;
+
+        assert_eq!(result.status, CleanStatus::Success);
+        assert_eq!(result.directories_cleaned, 2);
+        assert_eq!(result.files_deleted, 2);
+        assert_eq!(result.space_freed, 2000);
+        assert!(result.items.is_empty());
+        assert!(root.join("a/__pycache__/data.bin").exists());
+        assert!(root.join("b/__pycache__/data.bin").exists());
+    }
+
+    #[tokio::test]
+    async fn test_target_free_stops_once_reached() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().to_path_buf();
+        for (project, size) in [("small", 100), ("medium", 2000), ("large", 5000)] {
+            std::fs::create_dir_all(root.join(project).join("__pycache__")).unwrap();
+            std::fs::write(root.join(project).join("__pycache__/data.bin"), vec![0u8; size]).unwrap();
+        }
+
+        // Each deletion frees 1000 bytes, starting from 1000
+        let probe_root = root.clone();
+        let probe: DiskSpaceProbe = Arc::new(move |_: &Path| {
+            let deleted = ["small", "medium", "large"]
+                .iter()
+                .filter(|project| !probe_root.join(project).join("__pycache__").exists())
+                .count() as u64;
+            Some(1000 + deleted * 1000)
+        });
+        let config = CleanerConfig {
+            recursive: true,
+            apparent_size: true,
+            target_free: Some(3000),
+            ..Default::default()
+        };
+        let result = CacheCleaner::new(root.clone(), vec![CacheType::Python], config)
+            .with_disk_space_probe(probe)
+            .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+            .await
+            .unwrap();
+
+        assert_eq!(result.directories_cleaned, 2);
+        assert_eq!(result.space_freed, 7000);
+        assert!(!root.join("large/__pycache__").exists());
+        assert!(!root.join("medium/__pycache__").exists());
+        assert!(root.join("small/__pycache__").exists());
+    }
+
+    #[tokio::test]
+    async fn test_min_age_records_skipped_items() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for project in ["fresh", "stale"] {
+            std::fs::create_dir_all(root.join(project).join("__pycache__")).unwrap();
+            std::fs::write(root.join(project).join("__pycache__/data.bin"), "x").unwrap();
+        }
+        let week_ago = SystemTime::now() - Duration::from_secs(7 * 86400);
+        std::fs::File::open(root.join("stale/__pycache__")).unwrap().set_modified(week_ago).unwrap();
+
+        let config = CleanerConfig {
+            recursive: true,
+            min_age: Some(Duration::from_secs(86400)),
+            ..Default::default()
+        };
+        let result = run_clean(root, vec![CacheType::Python], config).await;
+
+        assert_eq!(result.directories_cleaned, 1);
+        assert!(!root.join("stale/__pycache__").exists());
+        assert_eq!(result.skipped, vec![(root.join("fresh/__pycache__"), SkipReason::TooNew)]);
+        assert!(root.join("fresh/__pycache__").exists());
+    }
+
+    #[tokio::test]
+    async fn test_multi_root_spinners_use_the_configured_target() {
+        let temp_dir = TempDir::new().unwrap();
+        let roots: Vec<PathBuf> = ["one", "two"].iter().map(|name| temp_dir.path().join(name)).collect();
+        for root in &roots {
+            std::fs::create_dir_all(root.join("__pycache__")).unwrap();
+        }
+
+        let term = RecordingTerm::default();
+        let progress_term = term.clone();
+        let config = CleanerConfig {
+            recursive: true,
+            dry_run: true,
+            parallel_roots: 2,
+            progress_target: ProgressOutput(Arc::new(move || ProgressDrawTarget::term_like(Box::new(progress_term.clone())))),
+            ..CleanerConfig::default()
+        };
+        let total = Arc::new(AtomicU64::new(0));
+        clean_roots(&roots, &[CacheType::Python], &config, false, total.clone(), total).await.unwrap();
+
+        assert!(term.0.lock().unwrap().contains("cache items to clean"), "{:?}", term.0.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_parallel_roots_merge_in_root_order() {
+        for parallel_roots in [1, 3] {
+            let temp_dir = TempDir::new().unwrap();
+            let roots: Vec<PathBuf> = ["one", "two", "three"].iter().map(|name| temp_dir.path().join(name)).collect();
+            for (root, size) in roots.iter().zip([100, 200, 300]) {
+                std::fs::create_dir_all(root.join("app/__pycache__")).unwrap();
+                std::fs::write(root.join("app/__pycache__/data.bin"), vec![0u8; size]).unwrap();
+            }
+            // Nested in the first root, so it waits for it and finds nothing left
+            let all_roots = vec![roots[0].clone(), roots[1].clone(), roots[0].join("app"), roots[2].clone()];
+            assert_eq!(overlapping_groups(&all_roots), vec![vec![0, 2], vec![1], vec![3]]);
+
+            let config = CleanerConfig {
+                recursive: true,
+                apparent_size: true,
+                parallel_roots,
+                ..Default::default()
+            };
+            let total_size = Arc::new(AtomicU64::new(0));
+            let results = clean_roots(&all_roots, &[CacheType::Python], &config, true, total_size.clone(), Arc::new(AtomicU64::new(0)))
+                .await
+                .unwrap();
+
+            // Each root reports what it alone freed, in the order given
+            let order: Vec<&PathBuf> = results.roots.iter().map(|(root, _)| root).collect();
+            assert_eq!(order, all_roots.iter().collect::<Vec<_>>());
+            let freed: Vec<u64> = results.roots.iter().map(|(_, result)| result.space_freed).collect();
+            assert_eq!(freed, vec![100, 200, 0, 300]);
+            assert_eq!(total_size.load(Ordering::Relaxed), 600);
+            assert!(roots.iter().all(|root| !root.join("app/__pycache__").exists()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_config_pattern_expands_env_var() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("go/pkg/mod/cache")).unwrap();
+        std::fs::create_dir_all(root.join("other/pkg/mod")).unwrap();
+        let gopath = root.join("go").to_string_lossy().to_string();
+
+        let user_config =
+            crate::config::Config::parse_with_env("patterns = [\"${GOPATH}/pkg/mod\"]\n", |_| Some(gopath.clone())).unwrap();
+        let config = CleanerConfig {
+            recursive: true,
+            dry_run: true,
+            path_globs: user_config.patterns,
+            ..CleanerConfig::default()
+        };
+        let result = run_clean(root, vec![CacheType::Python], config).await;
+        let paths: Vec<PathBuf> = result.items.into_iter().map(|item| item.path).collect();
+        assert_eq!(paths, vec![root.join("go/pkg/mod")]);
+    }
+
+    #[tokio::test]
+    async fn test_throttle_limits_deletion_rate() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for project in ["a", "b", "c"] {
+            let cache = root.join(project).join("__pycache__");
+            std::fs::create_dir_all(&cache).unwrap();
+            std::fs::write(cache.join("data.bin"), vec![0u8; 50_000]).unwrap();
+        }
+
+        // The first second's worth is a free burst; the other 50,000 bytes wait at 100,000 B/s
+        let config = CleanerConfig {
+            recursive: true,
+            apparent_size: true,
+            throttle: Some(100_000),
+            ..CleanerConfig::default()
+        };
+        let started = Instant::now();
+        let result = run_clean(root, vec![CacheType::Python], config).await;
+        assert_eq!(result.directories_cleaned, 3);
+        assert_eq!(result.space_freed, 150_000);
+        assert!(started.elapsed() >= Duration::from_millis(500), "took {:?}", started.elapsed());
+    }
+
+    #[tokio::test]
+    async fn test_preserve_skeleton_leaves_empty_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let cache = root.join("app/__pycache__");
+        std::fs::create_dir_all(cache.join("nested")).unwrap();
+        std::fs::write(cache.join("data.bin"), vec![0u8; 4096]).unwrap();
+        std::fs::write(cache.join("nested/more.bin"), vec![0u8; 4096]).unwrap();
+
+        let config = CleanerConfig {
+            recursive: true,
+            preserve_skeleton: true,
+            ..CleanerConfig::default()
+        };
+        let result = run_clean(root, vec![CacheType::Python], config).await;
+        assert_eq!(result.directories_cleaned, 1);
+        assert_eq!(result.files_deleted, 2);
+        assert!(cache.is_dir());
+        assert_eq!(std::fs::read_dir(&cache).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_profile_output_records_phase_spans() {
+        use crate::profile::Profiler;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("work");
+        let cache = root.join("app/__pycache__");
+        std::fs::create_dir_all(&cache).unwrap();
+        std::fs::write(cache.join("data.bin"), vec![0u8; 4096]).unwrap();
+
+        // The subscriber is installed for this thread only, so keep every span on it
+        let profiler = Profiler::new();
+        let _subscriber = tracing::subscriber::set_default(tracing_subscriber::registry().with(profiler.clone()));
+        let config = CleanerConfig {
+            recursive: true,
+            sequential: true,
+            ..CleanerConfig::default()
+        };
+        run_clean(&root, vec![CacheType::Python], config).await;
+
+        let trace = temp_dir.path().join("trace.json");
+        profiler.write(&trace).unwrap();
+        let trace: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&trace).unwrap()).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+        let names: Vec<&str> = events.iter().map(|event| event["name"].as_str().unwrap()).collect();
+        for expected in ["scan", "size", "delete", "delete item"] {
+            assert!(names.contains(&expected), "{} missing from {:?}", expected, names);
+        }
+        let item = events.iter().find(|event| event["name"] == "delete item").unwrap();
+        assert_eq!(item["args"]["cache_type"], "Python");
+        assert_eq!(item["args"]["path"], cache.display().to_string());
+        assert!(events[0]["ph"] == "X");
+    }
+
+    #[tokio::test]
+    async fn test_editor_caches_are_safe() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in ["cpp/.ccls-cache", "scala/.metals", "cpp/sub/.cache/clangd", "src"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join("data.bin"), "x").unwrap();
+        }
+
+        let config = CleanerConfig {
+            recursive: true,
+            dry_run: true,
+            ..CleanerConfig::default()
+        };
+        let result = run_clean(root, vec![CacheType::from_name("lsp").unwrap()], config).await;
+        let mut found: Vec<(PathBuf, String, bool)> = result
+            .items
+            .into_iter()
+            .map(|item| (item.path.strip_prefix(root).unwrap().to_path_buf(), item.pattern_name, item.is_library))
+            .collect();
+        found.sort();
+        assert_eq!(found, vec![
+            (PathBuf::from("cpp/.ccls-cache"), "ccls_cache".to_string(), false),
+            (PathBuf::from("cpp/sub/.cache/clangd"), "clangd_cache".to_string(), false),
+            (PathBuf::from("scala/.metals"), "metals_cache".to_string(), false),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_directory_containing_repository_is_refused() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let build = root.join("app/build");
+        std::fs::create_dir_all(build.join("vendored/.git")).unwrap();
+        std::fs::write(build.join("vendored/wip.c"), "uncommitted").unwrap();
+
+        let config = CleanerConfig {
+            recursive: true,
+            ..CleanerConfig::default()
+        };
+        let result = run_clean(root, vec![CacheType::General], config).await;
+        assert!(build.join("vendored/wip.c").exists());
+        assert!(result.errors.is_empty());
+        assert_ne!(result.status, CleanStatus::CompletedWithErrors);
+        assert_eq!(result.skipped, vec![(build.clone(), SkipReason::NestedRepository)]);
+
+        let config = CleanerConfig {
+            recursive: true,
+            allow_nested_repos: true,
+            ..CleanerConfig::default()
+        };
+        let result = run_clean(root, vec![CacheType::General], config).await;
+        assert!(result.errors.is_empty());
+        assert!(!build.exists());
+    }
+
+    /// Cargo stand-in that cleans `target` itself, or isn't installed at all
+    struct MockCargo {
+        installed: bool,
+        target_dir: &'static str, // Relative to the project, as `cargo metadata` would resolve it
+        clean_fails: bool,
+        calls: Mutex<Vec<(String, PathBuf)>>,
+    }
+
+    impl MockCargo {
+        fn new(installed: bool, target_dir: &'static str) -> Self {
+            Self {
+                installed,
+                target_dir,
+                clean_fails: false,
+                calls: Mutex::default(),
+            }
+        }
+    }
+
+    impl CommandRunner for MockCargo {
+        fn run(&self, program: &str, args: &[&str], working_dir: &Path) -> Result<CommandOutput> {
+            if !self.installed {
+                return Err(anyhow::anyhow!("Failed to run '{}'", program));
+            }
+            let target_dir = working_dir.join(self.target_dir);
+            if args[0] == "metadata" {
+                return Ok(CommandOutput {
+                    success: true,
+                    stdout: serde_json::json!({ "packages": [], "target_directory": target_dir }).to_string(),
+                    ..CommandOutput::default()
+                });
+            }
+            self.calls
+                .lock()
+                .unwrap()
+                .push((format!("{} {}", program, args.join(" ")), working_dir.to_path_buf()));
+            if self.clean_fails {
+                return Ok(CommandOutput {
+                    success: false,
+                    stderr: "error: failed to remove file".to_string(),
+                    ..CommandOutput::default()
+                });
+            }
+            std::fs::remove_dir_all(target_dir)?;
+            Ok(CommandOutput {
+                success: true,
+                ..CommandOutput::default()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_native_clean_runs_cargo_clean_in_rust_projects() {
+        let config = CleanerConfig {
+            recursive: true,
+            include_libraries: true,
+            use_native_clean: true,
+            ..CleanerConfig::default()
+        };
+        let project_in = |temp_dir: &TempDir| {
+            let project = temp_dir.path().join("app");
+            std::fs::create_dir_all(project.join("target/debug")).unwrap();
+            std::fs::write(project.join("Cargo.toml"), "[package]").unwrap();
+            std::fs::write(project.join("target/debug/app"), "binary").unwrap();
+            project
+        };
+
+        for installed in [true, false] {
+            let temp_dir = TempDir::new().unwrap();
+            let root = temp_dir.path();
+            let project = project_in(&temp_dir);
+            // Not a Cargo project, so deleted directly
+            std::fs::create_dir_all(root.join("site/target")).unwrap();
+
+            let cargo = Arc::new(MockCargo::new(installed, "target"));
+            let cleaner = CacheCleaner::new(root.to_path_buf(), vec![CacheType::Rust], config.clone()).with_runner(cargo.clone());
+            let result = cleaner
+                .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+                .await
+                .unwrap();
+
+            let expected = if installed { vec![("cargo clean".to_string(), project.clone())] } else { Vec::new() };
+            assert_eq!(*cargo.calls.lock().unwrap(), expected);
+            assert!(result.errors.is_empty(), "{:?}", result.errors);
+            assert_eq!(result.directories_cleaned, 2);
+            assert!(!project.join("target").exists());
+            assert!(!root.join("site/target").exists());
+        }
+
+        // With the target directory configured elsewhere, `cargo clean` would empty that instead
+        let temp_dir = TempDir::new().unwrap();
+        let project = project_in(&temp_dir);
+        let shared = temp_dir.path().join("shared-target");
+        std::fs::create_dir_all(&shared).unwrap();
+        let cargo = Arc::new(MockCargo::new(true, "../shared-target"));
+        let cleaner = CacheCleaner::new(project.clone(), vec![CacheType::Rust], config.clone()).with_runner(cargo.clone());
+        let result = cleaner.clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0))).await.unwrap();
+        assert!(cargo.calls.lock().unwrap().is_empty());
+        assert_eq!(result.directories_cleaned, 1);
+        assert!(!project.join("target").exists());
+        assert!(shared.exists());
+
+        // A failing `cargo clean` is an error, not a cleaned item
+        let temp_dir = TempDir::new().unwrap();
+        let project = project_in(&temp_dir);
+        let cargo = Arc::new(MockCargo {
+            clean_fails: true,
+            ..MockCargo::new(true, "target")
+        });
+        let cleaner = CacheCleaner::new(project.clone(), vec![CacheType::Rust], config).with_runner(cargo);
+        let result = cleaner.clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0))).await.unwrap();
+        assert_eq!(result.directories_cleaned, 0);
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].to_string().contains("`cargo clean` failed"), "{}", result.errors[0]);
+    }
+
+    #[tokio::test]
+    async fn test_no_parallel_is_deterministic_and_matches_parallel() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for project in ["zeta", "alpha", "mid", "beta/nested", "omega"] {
+            std::fs::create_dir_all(root.join(project).join("__pycache__")).unwrap();
+            std::fs::write(root.join(project).join("__pycache__/mod.pyc"), project).unwrap();
+            std::fs::create_dir_all(root.join(project).join(".pytest_cache")).unwrap();
+        }
+
+        let run = |sequential: bool| {
+            let temp_dir = TempDir::new().unwrap();
+            let copy = temp_dir.path().join("tree");
+            copy_dir(root, &copy);
+            let config = CleanerConfig {
+                recursive: true,
+                parallel_threads: 4,
+                sequential,
+                ..CleanerConfig::default()
+            };
+            async move {
+                let _temp_dir = temp_dir;
+                let result = run_clean(&copy, vec![CacheType::Python], config).await;
+                let paths: Vec<PathBuf> = result
+                    .items
+                    .iter()
+                    .map(|item| item.path.strip_prefix(&copy).unwrap().to_path_buf())
+                    .collect();
+                (paths, result.space_freed, result.files_deleted)
+            }
+        };
+
+        let (sequential, freed, files) = run(true).await;
+        let mut sorted = sequential.clone();
+        sorted.sort();
+        assert_eq!(sequential, sorted);
+        // Each project's two cache directories, plus the `.pyc` matched inside `__pycache__`
+        assert_eq!(sequential.len(), 15);
+        assert_eq!(run(true).await.0, sequential);
+
+        let (mut parallel, parallel_freed, parallel_files) = run(false).await;
+        parallel.sort();
+        assert_eq!(parallel, sequential);
+        assert_eq!((parallel_freed, parallel_files), (freed, files));
+    }
+
+    fn copy_dir(from: &Path, to: &Path) {
+        std::fs::create_dir_all(to).unwrap();
+        for entry in std::fs::read_dir(from).unwrap() {
+            let entry = entry.unwrap();
+            let target = to.join(entry.file_name());
+            if entry.file_type().unwrap().is_dir() {
+                copy_dir(&entry.path(), &target);
+            } else {
+                std::fs::copy(entry.path(), target).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_clean_roots_reports_each_root_and_aggregates() {
+        let temp_dir = TempDir::new().unwrap();
+        let (web, api) = (temp_dir.path().join("web"), temp_dir.path().join("api"));
+        std::fs::create_dir_all(web.join(".next")).unwrap();
+        std::fs::write(web.join(".next/build.js"), vec![0u8; 100]).unwrap();
+        std::fs::create_dir_all(api.join("__pycache__")).unwrap();
+        std::fs::write(api.join("__pycache__/app.pyc"), vec![0u8; 250]).unwrap();
+
+        let config = CleanerConfig {
+            apparent_size: true,
+            ..CleanerConfig::default()
+        };
+        let roots = vec![web.clone(), api.clone()];
+        let types = [CacheType::Node, CacheType::Python];
+        let results = clean_roots(&roots, &types, &config, false, Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+            .await
+            .unwrap();
+
+        let per_root: Vec<(&PathBuf, Vec<CacheType>, u64)> = results
+            .roots
+            .iter()
+            .map(|(root, result)| (root, result.items.iter().map(|item| item.cache_type.clone()).collect(), result.space_freed))
+            .collect();
+        assert_eq!(per_root, vec![(&web, vec![CacheType::Node], 100), (&api, vec![CacheType::Python], 250)]);
+
+        let aggregate = results.aggregate();
+        assert_eq!(aggregate.space_freed, 350);
+        assert_eq!(aggregate.directories_cleaned, 2);
+        assert_eq!(aggregate.files_deleted, 2);
+        assert_eq!(aggregate.items.len(), 2);
+        assert_eq!(aggregate.status, CleanStatus::Success);
+    }
+
+    #[tokio::test]
+    async fn test_declining_a_later_root_keeps_earlier_results() {
+        let temp_dir = TempDir::new().unwrap();
+        let (web, api) = (temp_dir.path().join("web"), temp_dir.path().join("api"));
+        std::fs::create_dir_all(web.join("__pycache__")).unwrap();
+        std::fs::write(web.join("__pycache__/app.pyc"), vec![0u8; 100]).unwrap();
+        std::fs::create_dir_all(api.join("__pycache__")).unwrap();
+        std::fs::write(api.join("__pycache__/app.pyc"), vec![0u8; 5000]).unwrap();
+
+        let config = CleanerConfig {
+            apparent_size: true,
+            confirm_large: Some(1000),
+            ..CleanerConfig::default()
+        };
+        let confirm = Arc::new(MockConfirm::new(false));
+        let run = RunState {
+            confirmer: confirm.clone(),
+            ..RunState::new(&config, false)
+        };
+        let roots = vec![web.clone(), api.clone()];
+        let results = clean_roots_with(run, &roots, &[CacheType::Python], &config, Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+            .await
+            .unwrap();
+
+        assert_eq!(confirm.asked.load(Ordering::Relaxed), 1);
+        let aggregate = results.aggregate();
+        assert_eq!(aggregate.status, CleanStatus::Aborted);
+        assert_eq!(aggregate.items.len(), 1);
+        assert_eq!(aggregate.space_freed, 100);
+        assert!(!web.join("__pycache__").exists());
+        assert!(api.join("__pycache__").exists());
+    }
+
+    #[tokio::test]
+    async fn test_cargo_registry_is_pruned_once_through_the_trash() {
+        let temp_dir = TempDir::new().unwrap();
+        let roots: Vec<PathBuf> = ["cli", "server"].iter().map(|name| temp_dir.path().join(name)).collect();
+        for root in &roots {
+            std::fs::create_dir_all(root.join("__pycache__")).unwrap();
+        }
+        std::fs::write(
+            roots[0].join("Cargo.lock"),
+            "version = 3\n\n[[package]]\nname = \"serde\"\nversion = \"1.0.200\"\n\
+             source = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+        )
+        .unwrap();
+        let registry = temp_dir.path().join("cargo/registry");
+        let index_dir = "index.crates.io-6f17d22bba15001f";
+        for name in ["serde-1.0.200", "rand-0.8.5"] {
+            std::fs::create_dir_all(registry.join("src").join(index_dir).join(name)).unwrap();
+        }
+
+        let trash_dir = temp_dir.path().join("trash");
+        let config = |cargo_registry: Option<PathBuf>| CleanerConfig {
+            dedup_stores: true,
+            trash_dir: Some(trash_dir.clone()),
+            cargo_registry,
+            ..CleanerConfig::default()
+        };
+        let clean = |config: CleanerConfig| {
+            let roots = roots.clone();
+            async move {
+                clean_roots(&roots, &[CacheType::Python], &config, false, Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+                    .await
+                    .unwrap()
+            }
+        };
+
+        // Without --prune-cargo-registry, cleaning the roots leaves the registry alone
+        let results = clean(config(None)).await;
+        assert_eq!(results.aggregate().directories_cleaned, 2);
+        assert!(results.global.items.is_empty());
+        assert!(registry.join("src").join(index_dir).join("rand-0.8.5").exists());
+        undo_last(&trash_dir).unwrap().unwrap();
+
+        let results = clean(config(Some(registry.clone()))).await;
+        let pruned: Vec<&PathBuf> = results.global.items.iter().map(|item| &item.path).collect();
+        assert_eq!(pruned, vec![&registry.join("src").join(index_dir).join("rand-0.8.5")]);
+        assert!(registry.join("src").join(index_dir).join("serde-1.0.200").exists());
+
+        // The registry entry went to the same trash run as the roots' items, so one undo restores all
+        let restored = undo_last(&trash_dir).unwrap().unwrap();
+        assert_eq!(restored.restored.len(), 3);
+        assert!(registry.join("src").join(index_dir).join("rand-0.8.5").exists());
+        assert!(roots.iter().all(|root| root.join("__pycache__").exists()));
+    }
+
+    #[tokio::test]
+    async fn test_root_in_nix_store_is_refused() {
+        let root = PathBuf::from("/nix/store/0000000000000000-clearcache-test");
+        let cleaner = CacheCleaner::new(root, vec![CacheType::Python], CleanerConfig::default());
+        let error = cleaner
+            .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "refusing to touch a directory managed by Nix");
+        assert_eq!(ErrorKind::of(&error), ErrorKind::Protected);
+    }
+
+    #[tokio::test]
+    async fn test_max_total_deletions_stops_before_deleting() {
+        let temp_dir = TempDir::new().unwrap();
+        let roots = [temp_dir.path().join("one"), temp_dir.path().join("two")];
+        for root in &roots {
+            for project in ["a", "b"] {
+                std::fs::create_dir_all(root.join(project).join("__pycache__")).unwrap();
+            }
+        }
+        let config = CleanerConfig {
+            recursive: true,
+            max_total_deletions: Some(3),
+            ..CleanerConfig::default()
+        };
+
+        // One root over the cap deletes nothing, and doesn't ask first
+        let confirm = Arc::new(MockConfirm::new(true));
+        let prompting = CleanerConfig {
+            confirm_large: Some(0),
+            ..config.clone()
+        };
+        let cleaner = CacheCleaner::new(temp_dir.path().to_path_buf(), vec![CacheType::Python], prompting).with_confirmer(confirm.clone());
+        let result = cleaner.clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0))).await.unwrap();
+        assert_eq!(result.status, CleanStatus::Aborted);
+        assert_eq!(confirm.asked.load(Ordering::Relaxed), 0);
+        assert_eq!(result.errors, vec![CleanError::Other(
+            "refusing to delete 4 items, more than --max-total-deletions 3; nothing was deleted".to_string()
+        )]);
+        assert!(roots.iter().all(|root| root.join("a/__pycache__").exists() && root.join("b/__pycache__").exists()));
+
+        // The cap spans the run: every root is scanned first, so neither is touched
+        for parallel_roots in [1, 2] {
+            let config = CleanerConfig {
+                parallel_roots,
+                ..config.clone()
+            };
+            let results = clean_roots(&roots, &[CacheType::Python], &config, false, Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+                .await
+                .unwrap();
+            let aggregate = results.aggregate();
+            assert_eq!(aggregate.status, CleanStatus::Aborted);
+            assert_eq!(aggregate.errors.len(), 1);
+            assert!(aggregate.items.is_empty());
+            assert!(roots.iter().all(|root| root.join("a/__pycache__").exists() && root.join("b/__pycache__").exists()));
+        }
+
+        // Within the cap, both roots are cleaned
+        let config = CleanerConfig {
+            max_total_deletions: Some(4),
+            ..config
+        };
+        let results = clean_roots(&roots, &[CacheType::Python], &config, false, Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+            .await
+            .unwrap();
+        assert_eq!(results.aggregate().directories_cleaned, 4);
+    }
+
+    #[tokio::test]
+    async fn test_devtools_caches_found_from_home_and_xdg() {
+        let temp_dir = TempDir::new().unwrap();
+        let home = temp_dir.path();
+        for dir in [
+            ".cache/ms-playwright/chromium-1091",
+            ".cache/Cypress/13.6.0",
+            "Library/Caches/electron",
+            "AppData/Local/Cypress/Cache/13.6.0",
+            // Not a download cache: a project that happens to use the name
+            "code/ms-playwright",
+        ] {
+            std::fs::create_dir_all(home.join(dir)).unwrap();
+            std::fs::write(home.join(dir).join("blob"), "x").unwrap();
+        }
+
+        let config = CleanerConfig {
+            recursive: true,
+            dry_run: true,
+            ..CleanerConfig::default()
+        };
+        let result = run_clean(home, vec![CacheType::from_name("playwright").unwrap()], config.clone()).await;
+        let mut found: Vec<(PathBuf, String, bool)> = result
+            .items
+            .into_iter()
+            .map(|item| (item.path.strip_prefix(home).unwrap().to_path_buf(), item.pattern_name, item.is_library))
+            .collect();
+        found.sort();
+        assert_eq!(found, vec![
+            (PathBuf::from(".cache/Cypress"), "cypress_binaries".to_string(), false),
+            (PathBuf::from(".cache/ms-playwright"), "playwright_browsers".to_string(), false),
+            (PathBuf::from("AppData/Local/Cypress/Cache"), "cypress_binaries".to_string(), false),
+            (PathBuf::from("Library/Caches/electron"), "electron_downloads".to_string(), false),
+        ]);
+
+        // With --xdg the root is the cache directory itself
+        let xdg = CleanerConfig {
+            recursive: false,
+            xdg: true,
+            ..config
+        };
+        let result = run_clean(&home.join(".cache"), vec![CacheType::DevTools], xdg).await;
+        let mut names: Vec<String> = result
+            .items
+            .iter()
+            .map(|item| item.path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["Cypress", "ms-playwright"]);
+        assert!(result.items.iter().all(|item| item.pattern_name == "xdg_devtools" && !item.is_library));
+    }
+
+    #[tokio::test]
+    async fn test_baseline_marks_items_missing_from_earlier_report() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("code");
+        std::fs::create_dir_all(root.join("api/__pycache__")).unwrap();
+        std::fs::create_dir_all(root.join("web/.pytest_cache")).unwrap();
+
+        // The earlier run's report, as `--summary-json-to` would have saved it
+        let manifest = temp_dir.path().join("last.json");
+        std::fs::write(
+            &manifest,
+            format!(
+                r#"{{"root":"{}","items":[{{"path":"{}","cache_type":"Python","size":0}}]}}"#,
+                root.display(),
+                root.join("api/__pycache__").display()
+            ),
+        )
+        .unwrap();
+        std::fs::create_dir_all(root.join("cli/.mypy_cache")).unwrap();
+
+        let config = CleanerConfig {
+            recursive: true,
+            dry_run: true,
+            baseline: Some(Arc::new(Baseline::load(&manifest).unwrap())),
+            ..CleanerConfig::default()
+        };
+        let recorder = Arc::new(Mutex::new(Vec::<u8>::new()));
+        let result = CacheCleaner::new(root.clone(), vec![CacheType::Python], config)
+            .with_output(recorder.clone())
+            .clean(Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)))
+            .await
+            .unwrap();
+
+        let mut flagged: Vec<(PathBuf, Option<bool>)> = result
+            .items
+            .iter()
+            .map(|item| (item.path.strip_prefix(&root).unwrap().to_path_buf(), item.new))
+            .collect();
+        flagged.sort();
+        assert_eq!(flagged, vec![
+            (PathBuf::from("api/__pycache__"), Some(false)),
+            (PathBuf::from("cli/.mypy_cache"), Some(true)),
+            (PathBuf::from("web/.pytest_cache"), Some(true)),
+        ]);
+
+        let listing = String::from_utf8_lossy(&recorder.lock().unwrap()).to_string();
+        let new_lines: Vec<&str> = listing.lines().filter(|line| line.contains("(new)")).collect();
+        assert_eq!(new_lines.len(), 2);
+        assert!(!listing.lines().any(|line| line.contains("api") && line.contains("(new)")));
+        assert!(serde_json::to_string(&result.items[0]).unwrap().contains(r#""new":"#));
+    }
+
+    #[tokio::test]
+    async fn test_archive_instead_compresses_then_deletes() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("code");
+        let pycache = root.join("api/__pycache__");
+        std::fs::create_dir_all(pycache.join("sub")).unwrap();
+        std::fs::write(pycache.join("mod.pyc"), vec![7u8; 10_000]).unwrap();
+        std::fs::write(pycache.join("sub/util.pyc"), "bytecode").unwrap();
+        let archives = temp_dir.path().join("archives");
+
+        let config = CleanerConfig {
+            recursive: true,
+            apparent_size: true,
+            archive_dir: Some(archives.clone()),
+            ..CleanerConfig::default()
+        };
+        let result = run_clean(&root, vec![CacheType::Python], config).await;
+
+        let archive = archives.join("api/__pycache__.tar.zst");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert!(!pycache.exists());
+        // The archive's own size is not counted as reclaimed
+        let archived = std::fs::metadata(&archive).unwrap().len();
+        assert!(archived < 1000);
+        assert_eq!(result.space_freed, 10_008 - archived);
+
+        // Extracting the archive restores the directory as it was
+        let decoder = zstd::Decoder::new(std::fs::File::open(&archive).unwrap()).unwrap();
+        tar::Archive::new(decoder).unpack(root.join("api")).unwrap();
+        assert_eq!(std::fs::read(pycache.join("mod.pyc")).unwrap(), vec![7u8; 10_000]);
+        assert_eq!(std::fs::read_to_string(pycache.join("sub/util.pyc")).unwrap(), "bytecode");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_special_files_in_matched_items_do_not_hang() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("app/__pycache__")).unwrap();
+        std::fs::write(root.join("app/__pycache__/mod.pyc"), "bytecode").unwrap();
+        let mkfifo = |path: PathBuf| {
+            let path = std::ffi::CString::new(path.into_os_string().into_encoded_bytes()).unwrap();
+            assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o644) }, 0);
+        };
+        // One pipe inside a matched directory, and one matched by name
+        mkfifo(root.join("app/__pycache__/server.pipe"));
+        mkfifo(root.join("worker.pyc"));
+        let _socket = std::os::unix::net::UnixListener::bind(root.join("app/__pycache__/debug.sock")).unwrap();
+
+        for dry_run in [true, false] {
+            let config = CleanerConfig {
+                recursive: true,
+                dry_run,
+                skip_active_builds: true,
+                ..CleanerConfig::default()
+            };
+            let result = tokio::time::timeout(
+                Duration::from_secs(30),
+                run_clean(root, vec![CacheType::Python], config),
+            )
+            .await
+            .expect("cleaning special files hung");
+
+            assert!(result.errors.is_empty(), "{:?}", result.errors);
+            if dry_run {
+                // Only the regular file has a size
+                let apparent = |path: PathBuf| result.items.iter().find(|item| item.path == path).unwrap().apparent_size;
+                assert_eq!(apparent(root.join("app/__pycache__")), "bytecode".len() as u64);
+                assert_eq!(apparent(root.join("worker.pyc")), 0);
+            }
+        }
+        assert!(!root.join("app/__pycache__").exists());
+        assert!(std::fs::symlink_metadata(root.join("worker.pyc")).is_err());
+    }
+}
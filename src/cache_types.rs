@@ -1,13 +1,19 @@
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// Variants are declared in display order; `Ord` follows it, so banners, breakdowns and
+/// reports list types the same way whatever order they were selected or found in
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum CacheType {
     Node,
     Rust,
     Go,
     Python,
     Docker,
+    Editor,
+    DevTools,
     General,
 }
 
@@ -19,9 +25,110 @@ pub struct CachePattern {
     pub is_directory: bool,
     pub recursive_safe: bool, // Safe to delete recursively
     pub is_library: bool, // True if this is a library/dependency that requires reinstallation
+    pub truncatable: bool, // Matched files may be emptied in place with --truncate-logs
+}
+
+/// Normalize a user-provided pattern so equivalent spellings match the same paths:
+/// `node_modules/` becomes `node_modules` and `.yarn//cache` becomes `.yarn/cache`
+pub fn normalize_pattern(pattern: &str) -> String {
+    let mut normalized = String::with_capacity(pattern.len());
+    for c in pattern.trim().chars() {
+        if c == '/' && normalized.ends_with('/') {
+            continue;
+        }
+        normalized.push(c);
+    }
+    normalized.trim_end_matches('/').to_string()
+}
+
+/// Cache types and individual pattern names enabled for a run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CacheSelection {
+    pub types: Vec<CacheType>,
+    pub pattern_names: Vec<String>,
+}
+
+impl CacheSelection {
+    /// Merge another selection into this one, skipping duplicates
+    pub fn merge(&mut self, other: CacheSelection) {
+        for cache_type in other.types {
+            if !self.types.contains(&cache_type) {
+                self.types.push(cache_type);
+            }
+        }
+        for name in other.pattern_names {
+            if !self.pattern_names.contains(&name) {
+                self.pattern_names.push(name);
+            }
+        }
+    }
 }
 
 impl CacheType {
+    /// All cache types, in display order
+    pub fn all() -> Vec<CacheType> {
+        vec![
+            CacheType::Node,
+            CacheType::Rust,
+            CacheType::Go,
+            CacheType::Python,
+            CacheType::Docker,
+            CacheType::Editor,
+            CacheType::DevTools,
+            CacheType::General,
+        ]
+    }
+
+    /// Resolve a user-facing type name or alias (e.g. "npm", "cargo")
+    pub fn from_name(name: &str) -> Option<CacheType> {
+        match name.trim().to_lowercase().as_str() {
+            "node" | "nodejs" | "npm" | "yarn" | "pnpm" => Some(CacheType::Node),
+            "rust" | "cargo" => Some(CacheType::Rust),
+            "go" | "golang" => Some(CacheType::Go),
+            "python" | "py" | "pip" => Some(CacheType::Python),
+            "docker" => Some(CacheType::Docker),
+            "editor" | "lsp" | "ide" => Some(CacheType::Editor),
+            "devtools" | "playwright" | "puppeteer" | "cypress" | "electron" => Some(CacheType::DevTools),
+            "general" | "cache" => Some(CacheType::General),
+            _ => None,
+        }
+    }
+
+    /// What this ecosystem's caches hold, for `clearcache types-info`
+    pub fn summary(&self) -> &'static str {
+        match self {
+            CacheType::Node => "Installed packages and bundler/framework build caches of JavaScript projects",
+            CacheType::Rust => "Cargo build output; dependencies are recompiled from the registry cache",
+            CacheType::Go => "Downloaded modules and compiled packages",
+            CacheType::Python => "Bytecode and tool caches (pytest, mypy, pip); all regenerate on the next run",
+            CacheType::Docker => "Stopped containers, unused images and volumes; images are pulled again when needed",
+            CacheType::Editor => "Editor and language server indexes and histories (clangd, ccls, Metals); rebuilt when the project is reopened",
+            CacheType::DevTools => "Browsers and binaries downloaded by Playwright, Puppeteer, Cypress and Electron; often several GB, fetched again on install",
+            CacheType::General => "Common cache, temp, build and log directories of any project",
+        }
+    }
+
+    /// Command that restores this type's library items after they are deleted, if any
+    pub fn reinstall_hint(&self) -> Option<&'static str> {
+        match self {
+            CacheType::Node => Some("npm install"),
+            CacheType::Rust => Some("cargo build"),
+            CacheType::Go => Some("go mod download"),
+            CacheType::Python | CacheType::Docker | CacheType::Editor | CacheType::DevTools | CacheType::General => None,
+        }
+    }
+
+    /// Markers of a build running in one of this type's matched directories, as globs relative
+    /// to it. Files count while another process holds a lock on them (Cargo keeps `.cargo-lock`
+    /// between builds); directories only exist while the tool runs.
+    pub fn build_lockfiles(&self) -> &'static [&'static str] {
+        match self {
+            CacheType::Rust => &["*/.cargo-lock", "*/*/.cargo-lock"], // `debug/`, or `<triple>/debug/` when cross-compiling
+            CacheType::Node => &[".staging"], // npm's in-progress install directory
+            CacheType::Go | CacheType::Python | CacheType::Docker | CacheType::Editor | CacheType::DevTools | CacheType::General => &[],
+        }
+    }
+
     pub fn get_patterns(&self) -> Vec<CachePattern> {
         match self {
             CacheType::Node => vec![
@@ -33,6 +140,7 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: true,
+                    truncatable: false,
                 },
                 // Safe caches (can be removed without reinstallation)
                 CachePattern {
@@ -42,6 +150,7 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: false,
+                    truncatable: false,
                 },
                 CachePattern {
                     name: "next_build".to_string(),
@@ -50,6 +159,7 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: false,
+                    truncatable: false,
                 },
                 CachePattern {
                     name: "nuxt_build".to_string(),
@@ -58,6 +168,7 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: false,
+                    truncatable: false,
                 },
                 CachePattern {
                     name: "yarn_cache".to_string(),
@@ -66,6 +177,7 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: false,
+                    truncatable: false,
                 },
                 CachePattern {
                     name: "pnpm_cache".to_string(),
@@ -74,6 +186,7 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: false,
+                    truncatable: false,
                 },
                 CachePattern {
                     name: "turbo_cache".to_string(),
@@ -82,6 +195,7 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: false,
+                    truncatable: false,
                 },
                 CachePattern {
                     name: "parcel_cache".to_string(),
@@ -90,6 +204,7 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: false,
+                    truncatable: false,
                 },
             ],
             CacheType::Rust => vec![
@@ -101,6 +216,7 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: true,
+                    truncatable: false,
                 },
                 // Safe caches (lock files are generally safe to regenerate but be careful)
                 CachePattern {
@@ -110,6 +226,7 @@ impl CacheType {
                     is_directory: false,
                     recursive_safe: false, // Be careful with lock files
                     is_library: false,
+                    truncatable: false,
                 },
             ],
             CacheType::Go => vec![
@@ -121,6 +238,7 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: true,
+                    truncatable: false,
                 },
                 // Safe caches
                 CachePattern {
@@ -130,6 +248,7 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: false,
+                    truncatable: false,
                 },
             ],
             CacheType::Python => vec![
@@ -141,6 +260,7 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: false,
+                    truncatable: false,
                 },
                 CachePattern {
                     name: "python_bytecode".to_string(),
@@ -149,6 +269,7 @@ impl CacheType {
                     is_directory: false,
                     recursive_safe: true,
                     is_library: false,
+                    truncatable: false,
                 },
                 CachePattern {
                     name: "pytest_cache".to_string(),
@@ -157,6 +278,7 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: false,
+                    truncatable: false,
                 },
                 CachePattern {
                     name: "mypy_cache".to_string(),
@@ -165,6 +287,7 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: false,
+                    truncatable: false,
                 },
                 CachePattern {
                     name: "pip_cache".to_string(),
@@ -173,6 +296,7 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: false,
+                    truncatable: false,
                 },
             ],
             CacheType::Docker => vec![
@@ -184,6 +308,111 @@ impl CacheType {
                     is_directory: false,
                     recursive_safe: false,
                     is_library: false,
+                    truncatable: false,
+                },
+            ],
+            CacheType::Editor => vec![
+                // All editor caches are safe - indexes are rebuilt when the project is reopened
+                CachePattern {
+                    name: "clangd_cache".to_string(),
+                    patterns: vec!["**/.cache/clangd".to_string()],
+                    description: "clangd index".to_string(),
+                    is_directory: true,
+                    recursive_safe: true,
+                    is_library: false,
+                    truncatable: false,
+                },
+                CachePattern {
+                    name: "ccls_cache".to_string(),
+                    patterns: vec![".ccls-cache".to_string()],
+                    description: "ccls index".to_string(),
+                    is_directory: true,
+                    recursive_safe: true,
+                    is_library: false,
+                    truncatable: false,
+                },
+                CachePattern {
+                    name: "vscode_test".to_string(),
+                    patterns: vec![".vscode-test".to_string()],
+                    description: "VS Code builds downloaded for extension tests".to_string(),
+                    is_directory: true,
+                    recursive_safe: true,
+                    is_library: false,
+                    truncatable: false,
+                },
+                CachePattern {
+                    name: "local_history".to_string(),
+                    patterns: vec![".history".to_string()],
+                    description: "Editor local history snapshots".to_string(),
+                    is_directory: true,
+                    recursive_safe: true,
+                    is_library: false,
+                    truncatable: false,
+                },
+                CachePattern {
+                    name: "metals_cache".to_string(),
+                    patterns: vec![".metals".to_string(), ".bloop".to_string()],
+                    description: "Metals and Bloop Scala build server state".to_string(),
+                    is_directory: true,
+                    recursive_safe: true,
+                    is_library: false,
+                    truncatable: false,
+                },
+            ],
+            CacheType::DevTools => vec![
+                // All safe - each tool downloads them again on its next install. These live in the
+                // user's cache directory, so they are found by scanning home: ~/.cache on Linux,
+                // ~/Library/Caches on macOS and ~/AppData/Local on Windows.
+                CachePattern {
+                    name: "playwright_browsers".to_string(),
+                    patterns: vec![
+                        "**/.cache/ms-playwright".to_string(),
+                        "**/Library/Caches/ms-playwright".to_string(),
+                        "**/AppData/Local/ms-playwright".to_string(),
+                    ],
+                    description: "Playwright browser downloads".to_string(),
+                    is_directory: true,
+                    recursive_safe: true,
+                    is_library: false,
+                    truncatable: false,
+                },
+                CachePattern {
+                    name: "puppeteer_browsers".to_string(),
+                    patterns: vec!["**/.cache/puppeteer".to_string()],
+                    description: "Puppeteer browser downloads".to_string(),
+                    is_directory: true,
+                    recursive_safe: true,
+                    is_library: false,
+                    truncatable: false,
+                },
+                CachePattern {
+                    name: "cypress_binaries".to_string(),
+                    patterns: vec![
+                        "**/.cache/Cypress".to_string(),
+                        "**/Library/Caches/Cypress".to_string(),
+                        "**/AppData/Local/Cypress/Cache".to_string(),
+                    ],
+                    description: "Cypress app binaries".to_string(),
+                    is_directory: true,
+                    recursive_safe: true,
+                    is_library: false,
+                    truncatable: false,
+                },
+                CachePattern {
+                    name: "electron_downloads".to_string(),
+                    patterns: vec![
+                        "**/.cache/electron".to_string(),
+                        "**/.cache/electron-builder".to_string(),
+                        "**/Library/Caches/electron".to_string(),
+                        "**/Library/Caches/electron-builder".to_string(),
+                        "**/AppData/Local/electron/Cache".to_string(),
+                        "**/AppData/Local/electron-builder/Cache".to_string(),
+                    ],
+                    description: "Electron and electron-builder downloads".to_string(),
+                    is_directory: true,
+                    recursive_safe: true,
+                    is_library: false,
+                    truncatable: false,
                 },
             ],
             CacheType::General => vec![
@@ -199,6 +428,7 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: false,
+                    truncatable: false,
                 },
                 CachePattern {
                     name: "temp_dirs".to_string(),
@@ -213,6 +443,7 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: false,
+                    truncatable: false,
                 },
                 CachePattern {
                     name: "build_dirs".to_string(),
@@ -226,6 +457,7 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: false,
+                    truncatable: false,
                 },
                 CachePattern {
                     name: "log_files".to_string(),
@@ -238,6 +470,23 @@ impl CacheType {
                     is_directory: false,
                     recursive_safe: true,
                     is_library: false,
+                    truncatable: true,
+                },
+                CachePattern {
+                    name: "artifact_archives".to_string(),
+                    patterns: vec![
+                        "*.cache.tar".to_string(),
+                        "*.cache.tar.gz".to_string(),
+                        "*.cache.zip".to_string(),
+                        "artifacts.zip".to_string(),
+                        "artifacts.tar".to_string(),
+                        "artifacts.tar.gz".to_string(),
+                    ],
+                    description: "CI artifact and cache archives".to_string(),
+                    is_directory: false,
+                    recursive_safe: true,
+                    is_library: false,
+                    truncatable: false,
                 },
                 CachePattern {
                     name: "exporter_dirs".to_string(),
@@ -248,11 +497,68 @@ impl CacheType {
                     is_directory: true,
                     recursive_safe: true,
                     is_library: false,
+                    truncatable: false,
                 },
             ],
         }
     }
 
+    /// Lockfiles removed by `--reset-locks` to force fresh dependency resolution.
+    /// `Cargo.lock` is deliberately never included.
+    pub fn get_lockfile_patterns(&self) -> Vec<CachePattern> {
+        let (name, files, description): (&str, &[&str], &str) = match self {
+            CacheType::Node => (
+                "node_lockfiles",
+                &["package-lock.json", "npm-shrinkwrap.json", "yarn.lock", "pnpm-lock.yaml"],
+                "Node.js lockfiles",
+            ),
+            CacheType::Python => ("python_lockfiles", &["poetry.lock", "Pipfile.lock", "uv.lock"], "Python lockfiles"),
+            CacheType::Rust
+            | CacheType::Go
+            | CacheType::Docker
+            | CacheType::Editor
+            | CacheType::DevTools
+            | CacheType::General => return Vec::new(),
+        };
+
+        vec![CachePattern {
+            name: name.to_string(),
+            patterns: files.iter().map(|f| f.to_string()).collect(),
+            description: description.to_string(),
+            is_directory: false,
+            recursive_safe: false,
+            is_library: true,
+            truncatable: false,
+        }]
+    }
+
+    /// Known safe tool cache subdirectories under `$XDG_CACHE_HOME`
+    pub fn get_xdg_patterns(&self) -> Vec<CachePattern> {
+        let (name, dirs, description): (&str, &[&str], &str) = match self {
+            CacheType::Node => ("xdg_node", &["yarn", "node-gyp", "typescript"], "Node.js tool caches (XDG)"),
+            CacheType::Rust => ("xdg_rust", &["sccache"], "Rust compiler cache (XDG)"),
+            CacheType::Go => ("xdg_go", &["go-build", "golangci-lint"], "Go build caches (XDG)"),
+            CacheType::Python => ("xdg_python", &["pip", "pypoetry", "pre-commit"], "Python tool caches (XDG)"),
+            CacheType::Editor => ("xdg_editor", &["clangd"], "Language server caches (XDG)"),
+            CacheType::DevTools => (
+                "xdg_devtools",
+                &["ms-playwright", "puppeteer", "Cypress", "electron", "electron-builder"],
+                "Browser and tool downloads (XDG)",
+            ),
+            CacheType::Docker | CacheType::General => return Vec::new(),
+        };
+
+        vec![CachePattern {
+            name: name.to_string(),
+            patterns: dirs.iter().map(|d| d.to_string()).collect(),
+            description: description.to_string(),
+            is_directory: true,
+            recursive_safe: true,
+            is_library: false,
+            truncatable: false,
+        }]
+    }
+
     pub fn get_safe_patterns(&self) -> Vec<CachePattern> {
         self.get_patterns()
             .into_iter()
@@ -269,19 +575,136 @@ impl CacheType {
 
     pub fn get_all_patterns() -> HashMap<CacheType, Vec<CachePattern>> {
         let mut patterns = HashMap::new();
-        let cache_types = vec![
-            CacheType::Node,
-            CacheType::Rust,
-            CacheType::Go,
-            CacheType::Python,
-            CacheType::Docker,
-            CacheType::General,
-        ];
 
-        for cache_type in cache_types {
+        for cache_type in CacheType::all() {
             patterns.insert(cache_type.clone(), cache_type.get_patterns());
         }
 
         patterns
     }
+}
+
+/// Manifest files that mark a directory as a project of each type
+const MANIFESTS: &[(&str, CacheType)] = &[
+    ("package.json", CacheType::Node),
+    ("Cargo.toml", CacheType::Rust),
+    ("go.mod", CacheType::Go),
+    ("pyproject.toml", CacheType::Python),
+    ("requirements.txt", CacheType::Python),
+    ("setup.py", CacheType::Python),
+    ("setup.cfg", CacheType::Python),
+    ("Pipfile", CacheType::Python),
+];
+
+/// Cache types of the projects whose manifests sit directly in `root`, in display order.
+/// Docker is never detected, since pruning it affects more than the project.
+pub fn detect_cache_types(root: &Path) -> Vec<CacheType> {
+    CacheType::all()
+        .into_iter()
+        .filter(|cache_type| {
+            MANIFESTS
+                .iter()
+                .any(|(manifest, manifest_type)| manifest_type == cache_type && root.join(manifest).is_file())
+        })
+        .collect()
+}
+
+/// Load a newline-separated list of cache type names or pattern names.
+/// Blank lines and `#` comments are skipped.
+pub fn load_types_file(path: &Path) -> Result<CacheSelection> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read types file {}: {}", path.display(), e))?;
+    parse_types_list(&content)
+        .map_err(|e| anyhow::anyhow!("{} ({})", e, path.display()))
+}
+
+/// Parse the contents of a types file into a selection
+pub fn parse_types_list(content: &str) -> Result<CacheSelection> {
+    let known_patterns: Vec<String> = CacheType::get_all_patterns()
+        .into_values()
+        .flatten()
+        .map(|pattern| pattern.name)
+        .collect();
+
+    let mut selection = CacheSelection::default();
+    for (line_number, line) in content.lines().enumerate() {
+        let name = line.trim();
+        if name.is_empty() || name.starts_with('#') {
+            continue;
+        }
+
+        let mut entry = CacheSelection::default();
+        if name == "all" {
+            entry.types = CacheType::all();
+        } else if let Some(cache_type) = CacheType::from_name(name) {
+            entry.types.push(cache_type);
+        } else if known_patterns.iter().any(|known| known == name) {
+            entry.pattern_names.push(name.to_string());
+        } else {
+            return Err(anyhow::anyhow!(
+                "Unknown cache type or pattern '{}' on line {} (expected a cache type such as node, rust, go, python, docker, general, all, or a pattern name such as npm_cache)",
+                name,
+                line_number + 1
+            ));
+        }
+        selection.merge(entry);
+    }
+
+    Ok(selection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_normalize_pattern() {
+        assert_eq!(normalize_pattern("node_modules/"), "node_modules");
+        assert_eq!(normalize_pattern(" .yarn//cache/ "), ".yarn/cache");
+        assert_eq!(normalize_pattern("apps/*/node_modules"), "apps/*/node_modules");
+        assert_eq!(normalize_pattern("//"), "");
+    }
+
+    #[test]
+    fn test_detect_cache_types() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        assert!(detect_cache_types(root).is_empty());
+
+        std::fs::write(root.join("Cargo.toml"), "[package]\nname = \"demo\"\n").unwrap();
+        assert_eq!(detect_cache_types(root), vec![CacheType::Rust]);
+
+        // A directory named like a manifest doesn't count
+        std::fs::create_dir(root.join("package.json")).unwrap();
+        std::fs::write(root.join("requirements.txt"), "requests\n").unwrap();
+        std::fs::write(root.join("Dockerfile"), "FROM scratch\n").unwrap();
+        assert_eq!(detect_cache_types(root), vec![CacheType::Rust, CacheType::Python]);
+    }
+
+    #[test]
+    fn test_reinstall_hints_cover_library_types() {
+        assert!(CacheType::Node.reinstall_hint().is_some_and(|hint| hint.contains("npm install")));
+        // Every type with library patterns says how to restore them
+        for cache_type in CacheType::all() {
+            let has_libraries = !cache_type.get_library_patterns().is_empty();
+            assert_eq!(cache_type.reinstall_hint().is_some(), has_libraries, "{:?}", cache_type);
+        }
+    }
+
+    #[test]
+    fn test_load_types_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("cache-types.txt");
+        std::fs::write(&path, "# team defaults\nnode\n\ncargo\npython_cache\nnode\n").unwrap();
+
+        let selection = load_types_file(&path).unwrap();
+        assert_eq!(selection.types, vec![CacheType::Node, CacheType::Rust]);
+        assert_eq!(selection.pattern_names, vec!["python_cache".to_string()]);
+
+        std::fs::write(&path, "node\nruby\n").unwrap();
+        let err = load_types_file(&path).unwrap_err().to_string();
+        assert!(err.contains("'ruby'"));
+        assert!(err.contains("line 2"));
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,153 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Default checkpoint location (`~/.local/state/clearcache/checkpoints`)
+pub fn default_checkpoint_dir() -> Option<PathBuf> {
+    crate::utils::state_dir().map(|dir| dir.join("checkpoints"))
+}
+
+/// Items completed so far by a run over one root, one NUL-terminated path per entry, so
+/// names holding newlines or invalid UTF-8 survive. Each entry is flushed as it is
+/// written, so an interrupted run leaves an accurate record.
+pub struct Checkpoint {
+    path: PathBuf,
+    file: Mutex<File>,
+    completed: HashSet<PathBuf>,
+}
+
+impl Checkpoint {
+    /// Open the checkpoint for `root` in `dir`. With `resume`, the items completed by an
+    /// earlier run are kept; otherwise the checkpoint starts empty.
+    pub fn open(dir: &Path, root: &Path, resume: bool) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(checkpoint_name(root));
+        let completed = if resume {
+            File::open(&path)
+                .map(|file| BufReader::new(file).split(0).map_while(Result::ok).map(path_from_bytes).collect())
+                .unwrap_or_default()
+        } else {
+            HashSet::new()
+        };
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .truncate(false)
+            .open(&path)?;
+        if !resume {
+            file.set_len(0)?;
+        }
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+            completed,
+        })
+    }
+
+    /// Whether an earlier, interrupted run already finished `path`
+    pub fn is_completed(&self, path: &Path) -> bool {
+        self.completed.contains(path)
+    }
+
+    pub fn completed_count(&self) -> usize {
+        self.completed.len()
+    }
+
+    pub fn record(&self, path: &Path) -> std::io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+        file.write_all(&path_bytes(path))?;
+        file.write_all(&[0])?;
+        file.flush()
+    }
+
+    /// Remove the checkpoint once the run has finished
+    pub fn clear(&self) -> std::io::Result<()> {
+        std::fs::remove_file(&self.path)
+    }
+}
+
+/// One file per scan root, named by an FNV-1a hash of its canonical path. Unlike
+/// `DefaultHasher`, the name stays the same across Rust releases.
+fn checkpoint_name(root: &Path) -> String {
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let hash = path_bytes(&root)
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3));
+    format!("{:016x}", hash)
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().into()
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    path.to_string_lossy().into_owned().into_bytes().into()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    std::ffi::OsString::from_vec(bytes).into()
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    String::from_utf8_lossy(&bytes).into_owned().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_checkpoint_resumes_or_starts_over() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("checkpoints");
+        let root = temp_dir.path();
+
+        let checkpoint = Checkpoint::open(&dir, root, false).unwrap();
+        checkpoint.record(&root.join("a/node_modules")).unwrap();
+        checkpoint.record(&root.join("b/node_modules")).unwrap();
+        drop(checkpoint);
+
+        let resumed = Checkpoint::open(&dir, root, true).unwrap();
+        assert_eq!(resumed.completed_count(), 2);
+        assert!(resumed.is_completed(&root.join("a/node_modules")));
+        assert!(!resumed.is_completed(&root.join("c/node_modules")));
+        // Other roots keep their own checkpoints
+        assert_eq!(Checkpoint::open(&dir, &root.join("a"), true).unwrap().completed_count(), 0);
+        drop(resumed);
+
+        assert_eq!(Checkpoint::open(&dir, root, false).unwrap().completed_count(), 0);
+        assert_eq!(Checkpoint::open(&dir, root, true).unwrap().completed_count(), 0);
+    }
+    #[test]
+    fn test_checkpoint_name_is_stable() {
+        assert_eq!(checkpoint_name(Path::new("/nonexistent/clearcache/root")), "d966dd36c54101ec");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_checkpoint_keeps_unusual_names() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("checkpoints");
+        let root = temp_dir.path();
+        let unusual = root.join(std::ffi::OsStr::from_bytes(b"caf\xe9\nnew/node_modules"));
+
+        let checkpoint = Checkpoint::open(&dir, root, false).unwrap();
+        checkpoint.record(&unusual).unwrap();
+        drop(checkpoint);
+
+        let resumed = Checkpoint::open(&dir, root, true).unwrap();
+        assert_eq!(resumed.completed_count(), 1);
+        assert!(resumed.is_completed(&unusual));
+    }
+}
@@ -0,0 +1,169 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// User configuration loaded from `config.toml` in the config directory
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Extra paths that must never be deleted, in addition to the built-in list
+    pub protected_paths: Vec<PathBuf>,
+    /// Built-in protected paths to relax (e.g. on unusual layouts)
+    pub unprotected_paths: Vec<PathBuf>,
+    /// Extra path globs to clean, like `--path-glob`; `${VAR}` references are expanded
+    /// from the environment, e.g. `${GOPATH}/pkg/mod`. A pattern that expands to an absolute
+    /// path only matches inside the directory being cleaned.
+    pub patterns: Vec<String>,
+    /// Default for `--no-canonicalize`: dedup by logical path, for trees known to have no symlink loops
+    pub no_canonicalize: bool,
+    /// Problems that didn't stop the file from loading, such as patterns naming unset variables
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+}
+
+impl Config {
+    /// Load configuration from `path`, or the default location when `None`.
+    /// A missing file yields the default configuration.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let Some(path) = path.map(Path::to_path_buf).or_else(default_config_path) else {
+            return Ok(Self::default());
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(content) => Self::parse(&content)
+                .map_err(|e| anyhow::anyhow!("Invalid config file {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(anyhow::anyhow!("Failed to read config file {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        Self::parse_with_env(content, |name| std::env::var(name).ok())
+    }
+
+    /// Parse with `${VAR}` references resolved through `lookup`. Patterns that can't be
+    /// expanded are dropped with a warning; the rest of the file still applies.
+    pub fn parse_with_env(content: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut config: Self = toml::from_str(content)?;
+        let mut patterns = Vec::with_capacity(config.patterns.len());
+        for pattern in &config.patterns {
+            match expand_env(pattern, &lookup) {
+                Ok(expanded) => patterns.push(expanded),
+                Err(e) => config.warnings.push(format!("{}; skipping the pattern", e)),
+            }
+        }
+        config.patterns = patterns;
+        Ok(config)
+    }
+}
+
+/// Replace `${VAR}` references in `pattern` with the value `lookup` gives for the variable.
+/// Undefined or empty variables are an error rather than expanding to nothing,
+/// which would turn `${GOPATH}/pkg/mod` into a glob rooted at `/pkg/mod`.
+pub fn expand_env(pattern: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut expanded = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| anyhow::anyhow!("Unterminated '${{' in pattern '{}'", pattern))?;
+        let name = &after[..end];
+        let value = lookup(name)
+            .filter(|value| !value.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Environment variable '{}' in pattern '{}' is not set", name, pattern))?;
+        // Globs use `/` as the separator on every platform
+        if cfg!(windows) {
+            expanded.push_str(&value.replace('\\', "/"));
+        } else {
+            expanded.push_str(&value);
+        }
+        rest = &after[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Default config file location (`~/.config/clearcache/config.toml`)
+pub fn default_config_path() -> Option<PathBuf> {
+    crate::utils::config_dir().map(|dir| dir.join("config.toml"))
+}
+
+/// Per-user ignore file applied to every run (`~/.config/clearcache/ignore`)
+pub fn default_global_ignore_path() -> Option<PathBuf> {
+    crate::utils::config_dir().map(|dir| dir.join("ignore"))
+}
+
+/// Contents of a newly created global ignore file. It applies to every root, so it starts
+/// without rules: the per-project defaults (`src/`, `lib/`, ...) would hide caches everywhere.
+const GLOBAL_IGNORE_TEMPLATE: &str = "\
+# clearcache global ignore file, applied to every directory clearcache cleans.
+# Same syntax as .clearcacheignore; patterns are relative to the directory being cleaned.
+# Use `clearcache --generate-ignore` for a per-project starting point.
+";
+
+/// Create the global ignore file, holding only comments, if it doesn't exist. A file that still
+/// holds the per-project defaults earlier versions seeded it with is reset the same way.
+pub fn seed_global_ignore(path: &Path) -> Result<()> {
+    match std::fs::read_to_string(path) {
+        Ok(content) if content != crate::traversal::create_default_clearcacheignore() => return Ok(()),
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, GLOBAL_IGNORE_TEMPLATE)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_global_ignore_has_no_rules() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("clearcache/ignore");
+        seed_global_ignore(&path).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.lines().all(|line| line.is_empty() || line.starts_with('#')));
+
+        // Edits are kept; the old per-project defaults are not
+        std::fs::write(&path, "vendor/\n").unwrap();
+        seed_global_ignore(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "vendor/\n");
+        std::fs::write(&path, crate::traversal::create_default_clearcacheignore()).unwrap();
+        seed_global_ignore(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+    }
+
+    #[test]
+    fn test_parse_protected_paths() {
+        let config = Config::parse("protected_paths = [\"/nix/store\", \"/mnt/data\"]\n").unwrap();
+        assert_eq!(config.protected_paths, vec![PathBuf::from("/nix/store"), PathBuf::from("/mnt/data")]);
+        assert!(config.unprotected_paths.is_empty());
+
+        assert!(Config::parse("protected_path = []").is_err());
+    }
+
+    #[test]
+    fn test_patterns_expand_env_vars() {
+        let env = |name: &str| (name == "GOPATH").then(|| "/home/dev/go".to_string());
+        let config = Config::parse_with_env("patterns = [\"${GOPATH}/pkg/mod\", \"apps/*/dist\"]\n", env).unwrap();
+        assert_eq!(config.patterns, vec!["/home/dev/go/pkg/mod", "apps/*/dist"]);
+        assert!(config.warnings.is_empty());
+
+        // Only the pattern naming an unset variable is dropped
+        let config = Config::parse_with_env(
+            "patterns = [\"${CARGO_HOME}/registry/cache\", \"${GOPATH}/pkg/mod\", \"${GOPATH/x\"]\nno_canonicalize = true\n",
+            env,
+        )
+        .unwrap();
+        assert_eq!(config.patterns, vec!["/home/dev/go/pkg/mod"]);
+        assert!(config.no_canonicalize);
+        assert_eq!(config.warnings.len(), 2);
+        assert!(config.warnings[0].contains("'CARGO_HOME'"), "{}", config.warnings[0]);
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::Path;
+
+/// Asks the user to approve a destructive step
+pub trait Confirm: Send + Sync {
+    fn confirm(&self, message: &str) -> bool;
+
+    /// Whether anyone is there to answer. Without it, a prompt that guards a limit should
+    /// stop the run with an error rather than read "no" from a closed stdin.
+    fn can_prompt(&self) -> bool {
+        true
+    }
+}
+
+/// Prompts on stderr and reads a y/N answer from stdin. EOF or a
+/// non-interactive stdin counts as "no".
+pub struct StdinConfirm;
+
+/// Held while a prompt is on screen, so roots cleaned in parallel ask one at a time
+static PROMPT: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+impl Confirm for StdinConfirm {
+    fn confirm(&self, message: &str) -> bool {
+        let _prompt = PROMPT.lock().unwrap_or_else(|e| e.into_inner());
+        let stdin = std::io::stdin();
+        prompt_yes_no(message, &mut stdin.lock(), &mut std::io::stderr())
+    }
+
+    fn can_prompt(&self) -> bool {
+        std::io::stdin().is_terminal()
+    }
+}
+
+/// Write `message` with a `[y/N]` suffix and read a single answer line
+pub fn prompt_yes_no<R: BufRead, W: Write>(message: &str, input: &mut R, output: &mut W) -> bool {
+    let _ = write!(output, "{} [y/N] ", message);
+    let _ = output.flush();
+
+    let mut answer = String::new();
+    if input.read_line(&mut answer).unwrap_or(0) == 0 {
+        let _ = writeln!(output);
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Up to `limit` entry names from inside `dir`, sorted, with directories marked by a
+/// trailing `/`. Shown in prompts so the user can check an item really is a cache.
+pub fn sample_entries(dir: &Path, limit: usize) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    // Read a bounded number of entries; huge cache directories shouldn't stall the prompt
+    let mut names: Vec<String> = entries
+        .flatten()
+        .take(limit * 20)
+        .map(|entry| {
+            let mut name = entry.file_name().to_string_lossy().to_string();
+            if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                name.push('/');
+            }
+            name
+        })
+        .collect();
+    names.sort();
+    names.truncate(limit);
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_entries_lists_target_contents() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        std::fs::create_dir_all(dir.join("chunks")).unwrap();
+        for name in ["b.pack", "a.pack", "c.pack", "d.pack"] {
+            std::fs::write(dir.join(name), "data").unwrap();
+        }
+
+        assert_eq!(sample_entries(dir, 3), vec!["a.pack", "b.pack", "c.pack"]);
+        assert_eq!(sample_entries(dir, 10).len(), 5);
+        assert!(sample_entries(dir, 10).contains(&"chunks/".to_string()));
+        assert!(sample_entries(&dir.join("missing"), 3).is_empty());
+    }
+
+    #[test]
+    fn test_prompt_yes_no() {
+        let mut output = Vec::new();
+        assert!(prompt_yes_no("Delete?", &mut "y\n".as_bytes(), &mut output));
+        assert!(!prompt_yes_no("Delete?", &mut "\n".as_bytes(), &mut output));
+        assert!(!prompt_yes_no("Delete?", &mut "".as_bytes(), &mut output));
+        assert!(String::from_utf8(output).unwrap().starts_with("Delete? [y/N] "));
+    }
+}
@@ -0,0 +1,161 @@
+use crate::config::Config;
+use crate::runner::CommandRunner;
+use crate::utils::{check_writable_dir, ProtectedPaths};
+use ignore::gitignore::GitignoreBuilder;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// Outcome of one `doctor` check, with what to do about it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub check: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(check: &'static str, severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            check,
+            severity,
+            message: message.into(),
+        }
+    }
+}
+
+/// Read-only checks explaining why a run over `root` might clean nothing or fail.
+/// `config` is the config file to validate (`None` for the default location) and
+/// `global_ignore` the per-user ignore file, if any.
+pub fn run_checks(runner: &dyn CommandRunner, root: &Path, config: Option<&Path>, global_ignore: Option<&Path>) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![check_docker(runner, root), check_root(root)];
+
+    let config = match Config::load(config) {
+        Ok(config) => {
+            diagnostics.push(Diagnostic::new("config", Severity::Ok, "Configuration is valid"));
+            config
+        }
+        Err(e) => {
+            diagnostics.push(Diagnostic::new("config", Severity::Error, e.to_string()));
+            Config::default()
+        }
+    };
+
+    let protected = ProtectedPaths::with_overrides(&config.protected_paths, &config.unprotected_paths);
+    if protected.is_protected(root) {
+        diagnostics.push(Diagnostic::new(
+            "safety",
+            Severity::Warning,
+            format!(
+                "{} is a protected path; items close to it are refused. Clean a project directory instead",
+                root.display()
+            ),
+        ));
+    }
+
+    diagnostics.extend(check_ignore_file(&root.join(".clearcacheignore")));
+    if let Some(path) = global_ignore {
+        diagnostics.extend(check_ignore_file(path));
+    }
+    diagnostics
+}
+
+fn check_docker(runner: &dyn CommandRunner, root: &Path) -> Diagnostic {
+    if runner.run("docker", &["--version"], root).is_err() {
+        return Diagnostic::new(
+            "docker",
+            Severity::Warning,
+            "Docker is not installed; the docker cache type is skipped",
+        );
+    }
+    match runner.run("docker", &["info"], root) {
+        Ok(output) if output.success => Diagnostic::new("docker", Severity::Ok, "Docker daemon is running"),
+        _ => Diagnostic::new(
+            "docker",
+            Severity::Warning,
+            "Docker daemon is not responding; start it (or raise --docker-wait) to prune Docker caches",
+        ),
+    }
+}
+
+fn check_root(root: &Path) -> Diagnostic {
+    if !root.is_dir() {
+        return Diagnostic::new("permissions", Severity::Error, format!("{} is not a directory", root.display()));
+    }
+    match check_writable_dir(root) {
+        Ok(()) => Diagnostic::new("permissions", Severity::Ok, format!("{} is writable", root.display())),
+        Err(e) => Diagnostic::new(
+            "permissions",
+            Severity::Error,
+            format!("{}; items directly in it can't be deleted", e),
+        ),
+    }
+}
+
+/// Report an ignore file's invalid lines, or how many rules it holds; nothing when it doesn't exist
+fn check_ignore_file(path: &Path) -> Option<Diagnostic> {
+    if !path.exists() {
+        return None;
+    }
+    let mut builder = GitignoreBuilder::new(path.parent().unwrap_or(path));
+    if let Some(e) = builder.add(path) {
+        return Some(Diagnostic::new(
+            "ignore",
+            Severity::Error,
+            format!("{}: {}; invalid lines are ignored", path.display(), e),
+        ));
+    }
+    match builder.build() {
+        Ok(rules) if rules.num_ignores() > 0 => Some(Diagnostic::new(
+            "ignore",
+            Severity::Ok,
+            format!(
+                "{} excludes {} patterns; check it if an expected cache is not cleaned",
+                path.display(),
+                rules.num_ignores()
+            ),
+        )),
+        Ok(_) => Some(Diagnostic::new("ignore", Severity::Ok, format!("{} has no rules", path.display()))),
+        Err(e) => Some(Diagnostic::new("ignore", Severity::Error, format!("{}: {}", path.display(), e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::CommandOutput;
+    use anyhow::Result;
+    use tempfile::TempDir;
+
+    struct NoDocker;
+
+    impl CommandRunner for NoDocker {
+        fn run(&self, program: &str, _args: &[&str], _working_dir: &Path) -> Result<CommandOutput> {
+            Err(anyhow::anyhow!("Failed to run '{}'", program))
+        }
+    }
+
+    #[test]
+    fn test_doctor_reports_missing_docker_and_invalid_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let config = root.join("config.toml");
+        std::fs::write(&config, "protected_path = [\"/data\"]\n").unwrap();
+        std::fs::write(root.join(".clearcacheignore"), "important/\n").unwrap();
+
+        let diagnostics = run_checks(&NoDocker, root, Some(&config), None);
+        let find = |check: &str| diagnostics.iter().find(|d| d.check == check).unwrap();
+
+        assert_eq!(find("docker").severity, Severity::Warning);
+        assert!(find("docker").message.contains("not installed"));
+        assert_eq!(find("config").severity, Severity::Error);
+        assert!(find("config").message.contains("config.toml"));
+        assert_eq!(find("permissions").severity, Severity::Ok);
+        assert_eq!(find("ignore").severity, Severity::Ok);
+    }
+}
@@ -0,0 +1,148 @@
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::path::PathBuf;
+
+/// Broad cause of a failed item, used to group similar errors in the summary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ErrorKind {
+    PermissionDenied,
+    NotFound,
+    Protected,
+    Other,
+}
+
+impl ErrorKind {
+    /// Classify an error by the first I/O or protection error in its chain
+    pub fn of(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            if cause.is::<ProtectedPathError>()
+                || cause.is::<WorkingDirectoryError>()
+                || cause.is::<VcsDirectoryError>()
+                || cause.is::<NestedRepositoryError>()
+                || cause.is::<ManagedSystemPathError>()
+            {
+                return ErrorKind::Protected;
+            }
+            if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+                return match io.kind() {
+                    std::io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+                    std::io::ErrorKind::NotFound => ErrorKind::NotFound,
+                    _ => ErrorKind::Other,
+                };
+            }
+        }
+        ErrorKind::Other
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ErrorKind::PermissionDenied => "permission-denied",
+            ErrorKind::NotFound => "not-found",
+            ErrorKind::Protected => "protected-path",
+            ErrorKind::Other => "other",
+        }
+    }
+}
+
+/// An error recorded during a run. Serializes as its message, as reports always have.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CleanError {
+    /// Sizing or deleting a matched item failed
+    Item {
+        path: PathBuf,
+        kind: ErrorKind,
+        message: String,
+    },
+    /// Docker cleanup failed
+    Docker(String),
+    /// Pruning a content-addressed store failed
+    Store { path: PathBuf, message: String },
+    Other(String),
+}
+
+impl CleanError {
+    pub fn item(path: PathBuf, error: &anyhow::Error) -> Self {
+        CleanError::Item {
+            path,
+            kind: ErrorKind::of(error),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for CleanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CleanError::Item { path, message, .. } => write!(f, "Failed to clean {}: {}", path.display(), message),
+            CleanError::Docker(message) => write!(f, "Docker cleaning failed: {}", message),
+            CleanError::Store { path, message } => write!(f, "Failed to prune {}: {}", path.display(), message),
+            CleanError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Serialize for CleanError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Raised instead of deleting a path covered by the protected-path list
+#[derive(Debug)]
+pub struct ProtectedPathError;
+
+impl fmt::Display for ProtectedPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "refusing to delete protected path")
+    }
+}
+
+impl std::error::Error for ProtectedPathError {}
+
+/// Raised instead of deleting the process's working directory or one of its ancestors
+#[derive(Debug)]
+pub struct WorkingDirectoryError;
+
+impl fmt::Display for WorkingDirectoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "refusing to delete the current working directory (or a directory containing it)")
+    }
+}
+
+impl std::error::Error for WorkingDirectoryError {}
+
+/// Raised instead of deleting version-control metadata such as `.git`, or anything inside it
+#[derive(Debug)]
+pub struct VcsDirectoryError;
+
+impl fmt::Display for VcsDirectoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "refusing to delete version-control metadata")
+    }
+}
+
+impl std::error::Error for VcsDirectoryError {}
+
+/// Raised instead of deleting a directory that holds a repository, whose uncommitted work would be lost
+#[derive(Debug)]
+pub struct NestedRepositoryError(pub PathBuf);
+
+impl fmt::Display for NestedRepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "refusing to delete a directory containing a repository ({}); use --force to delete it", self.0.display())
+    }
+}
+
+impl std::error::Error for NestedRepositoryError {}
+
+/// Raised instead of scanning or deleting inside a tree a system package manager installs into
+#[derive(Debug)]
+pub struct ManagedSystemPathError(pub &'static str);
+
+impl fmt::Display for ManagedSystemPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "refusing to touch a directory managed by {}", self.0)
+    }
+}
+
+impl std::error::Error for ManagedSystemPathError {}
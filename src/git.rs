@@ -0,0 +1,230 @@
+use crate::runner::CommandRunner;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// Directories containing files changed since `reference`, as absolute paths.
+///
+/// Returns `Ok(None)` when `root` is not inside a git work tree (or git is missing),
+/// so callers can fall back to a full scan.
+pub fn changed_directories(runner: &dyn CommandRunner, root: &Path, reference: &str) -> Result<Option<Vec<PathBuf>>> {
+    let toplevel = match runner.run("git", &["rev-parse", "--show-toplevel"], root) {
+        Ok(output) if output.success => PathBuf::from(output.stdout.trim()),
+        _ => return Ok(None),
+    };
+
+    // `--` keeps a reference that looks like a path from being treated as one; `-z` keeps
+    // git from quoting and escaping names with unusual characters
+    let diff = runner.run("git", &["diff", "--name-only", "-z", reference, "--"], &toplevel)?;
+    if !diff.success {
+        return Err(anyhow::anyhow!(
+            "git diff against '{}' failed: {}",
+            reference,
+            diff.stderr.trim()
+        ));
+    }
+
+    let mut dirs: Vec<PathBuf> = diff
+        .stdout
+        .split('\0')
+        .filter(|file| !file.is_empty())
+        .filter_map(|file| toplevel.join(file).parent().map(Path::to_path_buf))
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    Ok(Some(dirs))
+}
+
+/// `root` mapped into every work tree of the repository containing it (the main one first),
+/// from `git worktree list --porcelain`. A root in a subdirectory stays that subdirectory in
+/// each tree, and trees where it doesn't exist are left out. Bare and prunable entries have
+/// no files to clean and are skipped.
+///
+/// Returns `Ok(None)` when `root` is not inside a git work tree (or git is missing).
+pub fn worktrees(runner: &dyn CommandRunner, root: &Path) -> Result<Option<Vec<PathBuf>>> {
+    let output = match runner.run("git", &["worktree", "list", "--porcelain"], root) {
+        Ok(output) if output.success => output,
+        _ => return Ok(None),
+    };
+    let trees: Vec<PathBuf> = output
+        .stdout
+        .split("\n\n")
+        .filter(|entry| !entry.lines().any(|line| line == "bare" || line.starts_with("prunable")))
+        .filter_map(|entry| entry.lines().find_map(|line| line.strip_prefix("worktree ")))
+        .map(PathBuf::from)
+        .collect();
+
+    // The tree holding `root` is the deepest one containing it; linked trees may sit inside the main one
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let root = canonical(root);
+    let Some(relative) = trees
+        .iter()
+        .filter_map(|tree| root.strip_prefix(canonical(tree)).ok())
+        .min_by_key(|relative| relative.components().count())
+        .map(Path::to_path_buf)
+    else {
+        return Ok(None);
+    };
+    let roots: Vec<PathBuf> = trees
+        .iter()
+        .map(|tree| tree.join(&relative))
+        .filter(|root| root.is_dir())
+        .collect();
+    Ok(Some(roots).filter(|roots| !roots.is_empty()))
+}
+
+/// Whether a cache at `path` belongs to a changed directory: it lives inside one,
+/// or sits next to it in a parent directory (e.g. `node_modules` beside a changed `src/`)
+pub fn is_affected(path: &Path, changed: &[PathBuf]) -> bool {
+    let parent = path.parent().unwrap_or(path);
+    changed.iter().any(|dir| path.starts_with(dir) || dir.starts_with(parent))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::CommandOutput;
+
+    struct FakeGit {
+        toplevel: Option<&'static str>,
+        diff: &'static str,
+    }
+
+    impl CommandRunner for FakeGit {
+        fn run(&self, program: &str, args: &[&str], _working_dir: &Path) -> Result<CommandOutput> {
+            assert_eq!(program, "git");
+            let output = match (args[0], self.toplevel) {
+                ("rev-parse", Some(toplevel)) => CommandOutput {
+                    success: true,
+                    stdout: format!("{}\n", toplevel),
+                    ..CommandOutput::default()
+                },
+                ("diff", _) => CommandOutput {
+                    success: true,
+                    stdout: self.diff.to_string(),
+                    ..CommandOutput::default()
+                },
+                _ => CommandOutput {
+                    stderr: "fatal: not a git repository".to_string(),
+                    ..CommandOutput::default()
+                },
+            };
+            Ok(output)
+        }
+    }
+
+    #[test]
+    fn test_changed_directories_from_git_diff() {
+        let git = FakeGit {
+            toplevel: Some("/repo"),
+            diff: "web/src/index.js\0web/src/app.js\0api/main.py\0",
+        };
+        let dirs = changed_directories(&git, Path::new("/repo/web"), "origin/main").unwrap().unwrap();
+        assert_eq!(dirs, vec![PathBuf::from("/repo/api"), PathBuf::from("/repo/web/src")]);
+
+        assert!(is_affected(Path::new("/repo/web/node_modules"), &dirs));
+        assert!(is_affected(Path::new("/repo/api/__pycache__"), &dirs));
+        assert!(!is_affected(Path::new("/repo/docs/node_modules"), &dirs));
+
+        let outside = FakeGit { toplevel: None, diff: "" };
+        assert!(changed_directories(&outside, Path::new("/tmp"), "HEAD").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_changed_directories_keep_unusual_names() {
+        let git = FakeGit {
+            toplevel: Some("/repo"),
+            diff: "caf\u{e9}/x\0tab\there/line\nbreak.txt\0",
+        };
+        let dirs = changed_directories(&git, Path::new("/repo"), "HEAD").unwrap().unwrap();
+        assert_eq!(dirs, vec![PathBuf::from("/repo/caf\u{e9}"), PathBuf::from("/repo/tab\there")]);
+    }
+
+    struct FakeWorktrees {
+        list: String,
+    }
+
+    impl CommandRunner for FakeWorktrees {
+        fn run(&self, _program: &str, args: &[&str], _working_dir: &Path) -> Result<CommandOutput> {
+            assert_eq!(args, ["worktree", "list", "--porcelain"]);
+            Ok(CommandOutput {
+                success: true,
+                stdout: self.list.clone(),
+                ..CommandOutput::default()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_worktrees_are_all_scanned() {
+        use crate::cache_cleaner::{clean_roots, CleanerConfig};
+        use crate::cache_types::CacheType;
+        use std::sync::atomic::AtomicU64;
+        use std::sync::Arc;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let main = temp_dir.path().join("repo");
+        let feature = temp_dir.path().join("repo-feature");
+        for tree in [&main, &feature] {
+            std::fs::create_dir_all(tree.join("src/__pycache__")).unwrap();
+            std::fs::write(tree.join("src/__pycache__/data.bin"), "x").unwrap();
+        }
+        let git = FakeWorktrees {
+            list: format!(
+                "worktree {}\nHEAD 1111\nbranch refs/heads/main\n\nworktree {}\nHEAD 2222\nbranch refs/heads/feature\n",
+                main.display(),
+                feature.display()
+            ),
+        };
+
+        let roots = worktrees(&git, &main).unwrap().unwrap();
+        assert_eq!(roots, vec![main.clone(), feature.clone()]);
+
+        let config = CleanerConfig {
+            recursive: true,
+            ..Default::default()
+        };
+        let total = Arc::new(AtomicU64::new(0));
+        let results = clean_roots(&roots, &[CacheType::Python], &config, true, total.clone(), total).await.unwrap();
+        assert_eq!(results.roots.len(), 2);
+        assert!(!main.join("src/__pycache__").exists());
+        assert!(!feature.join("src/__pycache__").exists());
+
+        let outside = FakeGit { toplevel: None, diff: "" };
+        assert!(worktrees(&outside, Path::new("/tmp")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_worktrees_keep_subdirectory_and_skip_bare_and_prunable() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let main = temp_dir.path().join("repo");
+        let feature = main.join(".worktrees/feature");
+        let old = temp_dir.path().join("repo-old");
+        for tree in [&main, &feature] {
+            std::fs::create_dir_all(tree.join("web/src")).unwrap();
+        }
+        std::fs::create_dir_all(temp_dir.path().join("repo.git")).unwrap();
+        std::fs::create_dir_all(old.join("web")).unwrap();
+        let git = FakeWorktrees {
+            list: format!(
+                "worktree {}\nbare\n\n\
+                 worktree {}\nHEAD 1111\nbranch refs/heads/main\n\n\
+                 worktree {}\nHEAD 2222\nbranch refs/heads/feature\n\n\
+                 worktree {}\nHEAD 3333\ndetached\nprunable gitdir file points to non-existent location\n",
+                temp_dir.path().join("repo.git").display(),
+                main.display(),
+                feature.display(),
+                old.display()
+            ),
+        };
+
+        assert_eq!(worktrees(&git, &main.join("web")).unwrap().unwrap(), vec![main.join("web"), feature.join("web")]);
+        // From inside the nested linked tree, the relative path is taken from that tree
+        assert_eq!(
+            worktrees(&git, &feature.join("web/src")).unwrap().unwrap(),
+            vec![main.join("web/src"), feature.join("web/src")]
+        );
+        // A directory that only exists in one tree is only cleaned there
+        std::fs::create_dir_all(main.join("docs")).unwrap();
+        assert_eq!(worktrees(&git, &main.join("docs")).unwrap().unwrap(), vec![main.join("docs")]);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::runner::CommandRunner;
+use std::path::Path;
+
+/// A package manager whose per-user cache is cleaned through its own command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlobalCacheTool {
+    pub name: &'static str,
+    pub args: &'static [&'static str],
+}
+
+/// Tools cleaned by `--global-caches`, in the order they run
+pub const GLOBAL_CACHE_TOOLS: &[GlobalCacheTool] = &[
+    GlobalCacheTool {
+        name: "npm",
+        args: &["cache", "clean", "--force"],
+    },
+    GlobalCacheTool {
+        name: "yarn",
+        args: &["cache", "clean"],
+    },
+    GlobalCacheTool {
+        name: "pnpm",
+        args: &["store", "prune"],
+    },
+];
+
+impl GlobalCacheTool {
+    /// The command as a user would type it
+    pub fn command(&self) -> String {
+        format!("{} {}", self.name, self.args.join(" "))
+    }
+
+    /// Executable to start; Node tools are installed as `.cmd` shims on Windows
+    fn program(&self) -> String {
+        if cfg!(windows) {
+            format!("{}.cmd", self.name)
+        } else {
+            self.name.to_string()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GlobalCacheOutcome {
+    Cleaned,
+    WouldClean, // Dry run: the tool is installed, its command was not run
+    NotInstalled,
+    Failed(String),
+}
+
+/// What happened to one tool's global cache
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobalCacheResult {
+    pub tool: GlobalCacheTool,
+    pub outcome: GlobalCacheOutcome,
+}
+
+/// Clean the global cache of every installed tool in [`GLOBAL_CACHE_TOOLS`]. Tools that can't be
+/// started are skipped; a dry run only checks which tools are installed.
+pub fn clean_global_caches(runner: &dyn CommandRunner, working_dir: &Path, dry_run: bool) -> Vec<GlobalCacheResult> {
+    GLOBAL_CACHE_TOOLS
+        .iter()
+        .map(|tool| GlobalCacheResult {
+            tool: *tool,
+            outcome: clean_tool(runner, tool, working_dir, dry_run),
+        })
+        .collect()
+}
+
+fn clean_tool(runner: &dyn CommandRunner, tool: &GlobalCacheTool, working_dir: &Path, dry_run: bool) -> GlobalCacheOutcome {
+    let program = tool.program();
+    if !runner.run(&program, &["--version"], working_dir).is_ok_and(|output| output.success) {
+        return GlobalCacheOutcome::NotInstalled;
+    }
+    if dry_run {
+        return GlobalCacheOutcome::WouldClean;
+    }
+    match runner.run(&program, tool.args, working_dir) {
+        Ok(output) if output.success => GlobalCacheOutcome::Cleaned,
+        Ok(output) => GlobalCacheOutcome::Failed(output.stderr.trim().to_string()),
+        Err(e) => GlobalCacheOutcome::Failed(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::CommandOutput;
+    use anyhow::Result;
+    use std::sync::Mutex;
+
+    /// npm works, yarn is missing and pnpm's prune fails
+    #[derive(Default)]
+    struct FakeTools {
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl CommandRunner for FakeTools {
+        fn run(&self, program: &str, args: &[&str], _working_dir: &Path) -> Result<CommandOutput> {
+            let program = program.trim_end_matches(".cmd");
+            self.calls.lock().unwrap().push(format!("{} {}", program, args.join(" ")));
+            match (program, args) {
+                ("yarn", _) => Err(anyhow::anyhow!("Failed to run 'yarn'")),
+                ("pnpm", ["store", "prune"]) => Ok(CommandOutput {
+                    success: false,
+                    stderr: "ERR_PNPM_NO_STORE\n".to_string(),
+                    ..CommandOutput::default()
+                }),
+                _ => Ok(CommandOutput {
+                    success: true,
+                    ..CommandOutput::default()
+                }),
+            }
+        }
+    }
+
+    #[test]
+    fn test_global_caches_run_each_tool_and_skip_missing() {
+        let runner = FakeTools::default();
+        let results = clean_global_caches(&runner, Path::new("."), false);
+        let outcomes: Vec<(&str, GlobalCacheOutcome)> =
+            results.into_iter().map(|result| (result.tool.name, result.outcome)).collect();
+        assert_eq!(outcomes, vec![
+            ("npm", GlobalCacheOutcome::Cleaned),
+            ("yarn", GlobalCacheOutcome::NotInstalled),
+            ("pnpm", GlobalCacheOutcome::Failed("ERR_PNPM_NO_STORE".to_string())),
+        ]);
+        assert_eq!(*runner.calls.lock().unwrap(), vec![
+            "npm --version",
+            "npm cache clean --force",
+            "yarn --version",
+            "pnpm --version",
+            "pnpm store prune",
+        ]);
+
+        let runner = FakeTools::default();
+        let results = clean_global_caches(&runner, Path::new("."), true);
+        assert_eq!(results[0].outcome, GlobalCacheOutcome::WouldClean);
+        assert!(runner.calls.lock().unwrap().iter().all(|call| call.ends_with("--version")));
+    }
+}
@@ -0,0 +1,23 @@
+pub mod attributes;
+pub mod cache_cleaner;
+pub mod cache_types;
+pub mod checkpoint;
+pub mod config;
+pub mod confirm;
+pub mod doctor;
+pub mod errors;
+pub mod git;
+pub mod global_caches;
+pub mod profile;
+pub mod provider;
+pub mod report;
+pub mod runner;
+pub mod scan_cache;
+pub mod stores;
+pub mod targets;
+pub mod throttle;
+pub mod trash;
+pub mod traversal;
+pub mod tui;
+pub mod utils;
+pub mod version;
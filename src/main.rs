@@ -1,30 +1,117 @@
-use clap::{Arg, Command};
+use anyhow::Context;
+use clap::parser::ValueSource;
+use clap::{Arg, ArgGroup, Command};
 use colored::*;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-mod cache_cleaner;
-mod cache_types;
-mod utils;
-mod traversal;
+use clearcache::cache_cleaner::{clean_roots, CacheCleaner, CleanOrder, CleanResult, CleanStatus, CleanerConfig, ProgressOutput, SkipReason};
+use clearcache::errors::CleanError;
+use clearcache::cache_types::{detect_cache_types, load_types_file, CachePattern, CacheSelection, CacheType};
+use clearcache::checkpoint::default_checkpoint_dir;
+use clearcache::config::{default_global_ignore_path, seed_global_ignore, Config};
+use clearcache::doctor::{run_checks, Severity};
+use clearcache::git::worktrees;
+use clearcache::global_caches::{clean_global_caches, GlobalCacheOutcome};
+use clearcache::profile::Profiler;
+use clearcache::provider::run_provider;
+use clearcache::report::{
+    age_histogram, append_ledger, duplicate_groups, format_disk_delta, format_markdown_report, format_prometheus_metrics, group_by_project,
+    render_tree, restore_hints, summarize_errors, type_breakdown, type_shares, write_metrics_file, BudgetCheck, JsonReport,
+    LedgerEntry, SCHEMA_VERSION,
+};
+use clearcache::runner::SystemRunner;
+use clearcache::stores::cargo_home;
+use clearcache::targets::{load_targets, Baseline, Target};
+use clearcache::trash::{default_trash_dir, undo_last};
+use tracing_subscriber::layer::SubscriberExt;
+use clearcache::traversal::create_default_clearcacheignore;
+use clearcache::utils::{available_space, normalize_path, parse_size, ProtectedPaths};
 
-use cache_cleaner::CacheCleaner;
-use cache_types::CacheType;
-use traversal::create_default_clearcacheignore;
+/// How long a --pattern-provider may run before it is killed
+const PROVIDER_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let matches = Command::new("clearcache")
-        .version("0.1.0")
+        .version(clearcache::version::VERSION)
+        .disable_version_flag(true)
         .author("Cache Cleaner")
         .about("Extremely efficient cache clearing system for development directories")
+        .arg(
+            Arg::new("version")
+                .long("version")
+                .short('V')
+                .help("Print version information (add --verbose for build details)")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("directory")
-                .help("Directory to clean (default: current directory)")
+                .help("Directories to clean (default: current directory)")
                 .value_name("DIR")
+                .num_args(1..)
                 .index(1),
         )
+        .arg(
+            Arg::new("from-stdin")
+                .long("from-stdin")
+                .help("Also clean the directories listed on stdin, one per line")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("normalize-paths")
+                .long("normalize-paths")
+                .help("Make DIR and --explain paths absolute and resolve . and .. without following symlinks")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("case-insensitive")
+                .long("case-insensitive")
+                .help("Match patterns regardless of case (e.g. Node_Modules), as on default macOS and Windows filesystems")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("follow-links")
+                .long("follow-links")
+                .help("Follow symlinks while scanning and empty the caches that matched links point to")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-canonicalize")
+                .long("no-canonicalize")
+                .help("Dedup matches by their logical path instead of resolving symlinks (faster; only for trees without symlink loops)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dereference")
+                .long("dereference")
+                .help("Resolve symlinked DIRs to their real paths first (by default paths are reported under DIR as given)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dedupe-across-roots")
+                .long("dedupe-across-roots")
+                .help("Process an item reachable from several directories once, under the first one")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("parallel-roots")
+                .long("parallel-roots")
+                .help("Clean up to N directories at once; directories inside one another still go in turn")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("all-worktrees")
+                .long("all-worktrees")
+                .help("Also clean the same directory in every other git worktree of its repository (bare and prunable entries are skipped)")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("dry-run")
                 .long("dry-run")
@@ -32,6 +119,12 @@ async fn main() -> anyhow::Result<()> {
                 .help("Show what would be deleted without actually deleting")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("no-recursive-size")
+                .long("no-recursive-size")
+                .help("In dry-run, list matches immediately and only calculate sizes if confirmed")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("recursive")
                 .long("recursive")
@@ -47,12 +140,108 @@ async fn main() -> anyhow::Result<()> {
                 .value_name("TYPES")
                 .default_value("all"),
         )
+        .arg(
+            Arg::new("auto")
+                .long("auto")
+                .help("Clean only the cache types whose manifests (package.json, Cargo.toml, go.mod, pyproject.toml, ...) are in DIR")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("types"),
+        )
+        .arg(
+            Arg::new("types-from")
+                .long("types-from")
+                .help("Read cache type or pattern names to enable from a file (one per line), merged with --types")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("pattern-provider")
+                .long("pattern-provider")
+                .help("Run CMD once at startup and merge the cache patterns it prints as JSON")
+                .value_name("CMD"),
+        )
+        .arg(
+            Arg::new("path-glob")
+                .long("path-glob")
+                .help("Also clean paths matching GLOB relative to the directory, e.g. 'apps/*/node_modules' (repeatable)")
+                .value_name("GLOB")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("max-errors-shown")
+                .long("max-errors-shown")
+                .help("Show at most N error lines in the summary; similar errors are grouped first")
+                .value_name("N")
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("explain")
+                .long("explain")
+                .help("Print a JSON trace of which patterns and ignore rules apply to PATH, without cleaning")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new("since-commit")
+                .long("since-commit")
+                .help("Only clean caches in or beside directories with files changed since REF (git diff --name-only REF)")
+                .value_name("REF"),
+        )
+        .arg(
+            Arg::new("priority")
+                .long("priority")
+                .help("Clean cache types in this order, each finishing before the next starts (e.g. python,general,node); unlisted types go last")
+                .value_name("ORDER"),
+        )
+        .arg(
+            Arg::new("targets")
+                .long("targets")
+                .help("Clean exactly the items listed in a JSON Lines file (e.g. saved --format json output) instead of scanning")
+                .value_name("FILE")
+                .conflicts_with("xdg"),
+        )
+        .arg(
+            Arg::new("baseline")
+                .long("baseline")
+                .help("Mark items missing from an earlier run's --format json report as (new), and set \"new\" in JSON output")
+                .value_name("FILE"),
+        )
         .arg(
             Arg::new("parallel")
                 .long("parallel")
                 .short('p')
                 .help("Number of parallel threads (default: CPU count)")
-                .value_name("NUM"),
+                .value_name("NUM")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        )
+        .arg(
+            Arg::new("no-parallel")
+                .long("no-parallel")
+                .help("Scan, size and delete on a single thread in path order, for reproducible bug reports")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("io-concurrency")
+                .long("io-concurrency")
+                .help("Maximum concurrent deletions, independent of --parallel (default: 1 on spinning disks, otherwise unlimited)")
+                .value_name("NUM")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        )
+        .arg(
+            Arg::new("profile-output")
+                .long("profile-output")
+                .help("Write scan, sizing and per-type deletion timings to FILE as a Chrome trace (chrome://tracing, Perfetto)")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("preserve-skeleton")
+                .long("preserve-skeleton")
+                .help("Recreate deleted cache directories empty, for tools that fail when they are missing (e.g. .next)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("throttle")
+                .long("throttle")
+                .help("Delete at most RATE bytes per second, to spare other jobs' disk I/O (e.g. 50M)")
+                .value_name("RATE"),
         )
         .arg(
             Arg::new("verbose")
@@ -65,7 +254,122 @@ async fn main() -> anyhow::Result<()> {
             Arg::new("force")
                 .long("force")
                 .short('f')
-                .help("Force deletion without confirmation")
+                .help("Force deletion without confirmation, including directories that contain a repository")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("confirm-large")
+                .long("confirm-large")
+                .help("Ask for confirmation before deleting more than SIZE in total, e.g. 500M or 20G (skipped with --force; fails the run when there is no terminal to ask on)")
+                .value_name("SIZE"),
+        )
+        .arg(
+            Arg::new("skip-network")
+                .long("skip-network")
+                .help("Skip cache items on network filesystems (NFS, SMB, sshfs) instead of only warning")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("check-permissions")
+                .long("check-permissions")
+                .help("With --dry-run, flag items that could not be deleted with the current permissions")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dedup-stores")
+                .long("dedup-stores")
+                .help("Prune pnpm stores of entries no project under DIRECTORY references, instead of deleting whole stores")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("store-max-age")
+                .long("store-max-age")
+                .help("Prune pnpm store entries not used in the last DAYS days, instead of deleting whole stores")
+                .value_name("DAYS")
+                .value_parser(parse_days),
+        )
+        .group(ArgGroup::new("store-pruning").args(["dedup-stores", "store-max-age"]).multiple(true))
+        .arg(
+            Arg::new("prune-cargo-registry")
+                .long("prune-cargo-registry")
+                .help("Also prune the shared $CARGO_HOME/registry once per run, as --dedup-stores or --store-max-age select (only Cargo.lock files under DIR count as references)")
+                .action(clap::ArgAction::SetTrue)
+                .requires("store-pruning")
+                // The review screen only re-cleans items under the roots
+                .conflicts_with("tui"),
+        )
+        .arg(
+            Arg::new("skip-active-builds")
+                .long("skip-active-builds")
+                .help("Skip cache directories whose build tool is running (a held target/*/.cargo-lock, npm's node_modules/.staging)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("skip-in-use")
+                .long("skip-in-use")
+                .help("On Linux, skip cache items containing files that a running process has open")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("trash")
+                .long("trash")
+                .help("Move items to clearcache's trash instead of deleting them, so `clearcache undo` can restore them")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("archive-instead")
+                .long("archive-instead")
+                .help("Compress each matched directory into a .tar.zst under DIR before deleting it")
+                .value_name("DIR")
+                .conflicts_with_all(["trash", "use-native-clean"]),
+        )
+        .arg(
+            Arg::new("target-free")
+                .long("target-free")
+                .help("Clean one item at a time until DIR's filesystem has SIZE free, then stop (e.g. 20G)")
+                .value_name("SIZE")
+                // Ordering by size and knowing when enough is freed both need every item measured
+                .conflicts_with_all(["trash", "rename-to-delete", "no-recursive-size"]),
+        )
+        .arg(
+            Arg::new("order")
+                .long("order")
+                .help("Which items --target-free cleans first")
+                .value_name("ORDER")
+                .value_parser(["largest", "oldest"])
+                .default_value("largest")
+                .requires("target-free"),
+        )
+        .arg(
+            Arg::new("exclude-larger-than-free-space")
+                .long("exclude-larger-than-free-space")
+                .help("With --trash on another filesystem, skip items that wouldn't fit in its free space instead of failing midway")
+                .action(clap::ArgAction::SetTrue)
+                .requires("trash"),
+        )
+        .arg(
+            Arg::new("resume")
+                .long("resume")
+                .help("Skip items an interrupted run over the same DIR already cleaned (progress is checkpointed as items complete)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("rename-to-delete")
+                .long("rename-to-delete")
+                .help("Rename items into a staging directory under DIR first, then delete it at the end (items on other filesystems are deleted directly)")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("trash"),
+        )
+        .arg(
+            Arg::new("truncate-logs")
+                .long("truncate-logs")
+                .help("Empty matched log files in place instead of deleting them, so processes writing to them keep working")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("clear-readonly")
+                .long("clear-readonly")
+                .help("On Windows, clear read-only attributes and retry when deletion is denied (implied by --force)")
                 .action(clap::ArgAction::SetTrue),
         )
         .arg(
@@ -75,12 +379,20 @@ async fn main() -> anyhow::Result<()> {
                 .help("Include libraries/dependencies that require reinstallation (node_modules, target, etc.)")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("reset-locks")
+                .long("reset-locks")
+                .help("Also remove lockfiles (package-lock.json, yarn.lock, poetry.lock, ...) of the selected types; never Cargo.lock")
+                .requires("include-libraries")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("max-depth")
                 .long("max-depth")
                 .short('d')
                 .help("Maximum directory depth to traverse (default: 20)")
-                .value_name("DEPTH"),
+                .value_name("DEPTH")
+                .value_parser(clap::value_parser!(usize)),
         )
         .arg(
             Arg::new("no-ignore")
@@ -94,24 +406,307 @@ async fn main() -> anyhow::Result<()> {
                 .help("Respect .gitignore files (by default, .gitignore is ignored for cache cleaning)")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("no-global-ignore")
+                .long("no-global-ignore")
+                .help("Don't apply the per-user ignore file (~/.config/clearcache/ignore)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("xdg")
+                .long("xdg")
+                .help("Clean known tool caches under $XDG_CACHE_HOME (default ~/.cache; Linux only)")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["directory", "from-stdin"]),
+        )
+        .arg(
+            Arg::new("scan-cache")
+                .long("scan-cache")
+                .help("Skip subtrees that were clean on a recent run and are unchanged since")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("scan-cache-ttl")
+                .long("scan-cache-ttl")
+                .help("How long scan cache records stay valid, in seconds (default: 3600)")
+                .value_name("SECS"),
+        )
+        .arg(
+            Arg::new("analyze")
+                .long("analyze")
+                .help("Report what would be cleaned, with a cache-age histogram (implies --dry-run)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dedupe-report")
+                .long("dedupe-report")
+                .help("Report cache directory names found more than once (e.g. every node_modules) with their combined size (implies --dry-run)")
+                .conflicts_with_all(["count-only", "no-recursive-size"])
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("budget")
+                .long("budget")
+                .help("Report whether the caches found exceed SIZE (e.g. 5G) and exit non-zero if they do (implies --dry-run)")
+                .value_name("SIZE")
+                .conflicts_with_all(["count-only", "no-recursive-size"]),
+        )
+        .arg(
+            Arg::new("count-only")
+                .long("count-only")
+                .help("Only count the cache items and their total size; nothing is deleted or listed (implies --dry-run)")
+                .conflicts_with("tui")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("min-age")
+                .long("min-age")
+                .help("Skip items modified within the last DAYS days (e.g. caches of a running build)")
+                .value_name("DAYS")
+                .value_parser(parse_days),
+        )
+        .arg(
+            Arg::new("show-skipped")
+                .long("show-skipped")
+                .help("List the matched items that were left in place, grouped by reason (also shown with --verbose)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Output format (markdown prints a table of the items, e.g. for a PR comment)")
+                .value_name("FORMAT")
+                .value_parser(["human", "json", "markdown"])
+                .default_value("human"),
+        )
+        .arg(
+            Arg::new("apparent-size")
+                .long("apparent-size")
+                .help("Report logical file sizes instead of disk usage (sparse files and hard links inflate these)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("global-caches")
+                .long("global-caches")
+                .help("Also clean the per-user npm, yarn and pnpm caches with their own commands, when installed")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("use-native-clean")
+                .long("use-native-clean")
+                .help("Run 'cargo clean' and 'go clean -cache' instead of deleting their output directly, when installed")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("docker-wait")
+                .long("docker-wait")
+                .help("Seconds to wait for a Docker daemon that is still starting")
+                .value_name("SECS")
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("max-total-deletions")
+                .long("max-total-deletions")
+                .help("Fail without deleting if the run would remove more than N items, across all directories")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("max-items-per-type")
+                .long("max-items-per-type")
+                .help("List at most N cleaned items of each cache type; the rest are still cleaned and shown as one total")
+                .value_name("N"),
+        )
+        .arg(
+            Arg::new("max-file-count")
+                .long("max-file-count")
+                .help("Skip matched directories holding more than N files (slow to delete, and often not a cache)")
+                .value_name("N"),
+        )
+        .arg(
+            Arg::new("ignore-errors")
+                .long("ignore-errors")
+                .help("Don't list failed items or exit non-zero because of them; only their count is shown (runs with failed items now exit 1 by default)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("report-disk")
+                .long("report-disk")
+                .help("Also show how much free space the filesystem of DIR gained, next to the bytes freed")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("tui")
+                .long("tui")
+                .help("Review found items in an interactive screen and delete only the selected ones (one directory only)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("tree")
+                .long("tree")
+                .help("Show cleaned items as a directory tree with sizes instead of a flat list")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("group-by")
+                .long("group-by")
+                .help("Group the summary by enclosing project (nearest directory with a manifest)")
+                .value_name("GROUPING")
+                .value_parser(["project"]),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .help("Configuration file (default: ~/.config/clearcache/config.toml)")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("json-pretty")
+                .long("json-pretty")
+                .help("Indent the JSON report (from --format json or --summary-json-to) instead of printing it on one line")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("summary-json-to")
+                .long("summary-json-to")
+                .help("Also write the JSON report to PATH, or to stderr with '-', while stdout stays human-readable")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new("metrics-file")
+                .long("metrics-file")
+                .help("Write Prometheus text-format metrics to PATH (for node_exporter's textfile collector)")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new("log-file")
+                .long("log-file")
+                .help("Append a one-line JSON record of each run (time, roots, freed, items, errors, duration) to PATH")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new("log-file-max-size")
+                .long("log-file-max-size")
+                .help("Rotate the --log-file to PATH.1 once it would exceed SIZE (e.g. 1M, 512K)")
+                .value_name("SIZE")
+                .default_value("1M"),
+        )
+        .arg(
+            Arg::new("machine-summary")
+                .long("machine-summary")
+                .help("Print a one-line key=value summary to stderr on exit")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("generate-ignore")
                 .long("generate-ignore")
                 .help("Generate a default .clearcacheignore file in the current directory")
                 .action(clap::ArgAction::SetTrue),
         )
+        .subcommand(Command::new("undo").about("Restore the items moved to the trash by the most recent --trash run"))
+        .subcommand(Command::new("types-info").about("Describe each cache type: what it holds, what is safe, and how to restore libraries"))
+        .subcommand(
+            Command::new("doctor")
+                .about("Check Docker, permissions, config and ignore files, and safety guards for a directory")
+                .arg(Arg::new("directory").help("Directory to check (default: current directory)").value_name("DIR"))
+                .arg(
+                    Arg::new("config")
+                        .long("config")
+                        .help("Configuration file (default: ~/.config/clearcache/config.toml)")
+                        .value_name("FILE"),
+                ),
+        )
         .get_matches();
 
-    let directory = matches
-        .get_one::<String>("directory")
-        .map(PathBuf::from)
-        .unwrap_or_else(|| std::env::current_dir().unwrap());
+    if matches.subcommand_matches("undo").is_some() {
+        return run_undo();
+    }
+    if matches.subcommand_matches("types-info").is_some() {
+        print_types_info();
+        return Ok(());
+    }
+    if let Some(doctor) = matches.subcommand_matches("doctor") {
+        let directory = match doctor.get_one::<String>("directory") {
+            Some(dir) => PathBuf::from(dir),
+            None => std::env::current_dir()?,
+        };
+        return run_doctor(&directory, doctor.get_one::<String>("config").map(Path::new));
+    }
+
+    // Before any root is resolved, so --version works wherever it is run
+    if matches.get_flag("version") {
+        println!("{}", clearcache::version::version_string(matches.get_flag("verbose")));
+        return Ok(());
+    }
+
+    let xdg = matches.get_flag("xdg");
+    let mut roots: Vec<PathBuf> = if xdg {
+        vec![resolve_xdg_cache_home()?]
+    } else {
+        matches
+            .get_many::<String>("directory")
+            .map(|dirs| dirs.map(PathBuf::from).collect())
+            .unwrap_or_default()
+    };
+    if matches.get_flag("from-stdin") {
+        roots.extend(read_roots(std::io::stdin().lock())?);
+    }
+    if roots.is_empty() {
+        roots.push(std::env::current_dir()?);
+    }
+    let normalize_paths = matches.get_flag("normalize-paths");
+    if normalize_paths {
+        roots = roots.iter().map(|root| absolute_normalized(root)).collect::<anyhow::Result<_>>()?;
+    }
+    if matches.get_flag("dereference") {
+        roots = roots
+            .iter()
+            .map(|root| root.canonicalize().with_context(|| format!("Cannot resolve {}", root.display())))
+            .collect::<anyhow::Result<_>>()?;
+    }
+    let all_worktrees = matches.get_flag("all-worktrees");
+    if all_worktrees {
+        let mut expanded: Vec<PathBuf> = Vec::new();
+        for root in &roots {
+            // Outside a repository the root is scanned on its own
+            let trees = worktrees(&SystemRunner, root)?.unwrap_or_else(|| vec![root.clone()]);
+            for tree in trees {
+                if !expanded.contains(&tree) {
+                    expanded.push(tree);
+                }
+            }
+        }
+        roots = expanded;
+    }
+    // The review screen cleans its selection under one root; items from others would be lost
+    if matches.get_flag("tui") && roots.len() > 1 {
+        anyhow::bail!("--tui reviews a single directory, but {} were given", roots.len());
+    }
+    // Single-root features (ignore generation, explain, the review screen) use the first
+    let directory = roots[0].clone();
 
-    let dry_run = matches.get_flag("dry-run");
-    let recursive = matches.get_flag("recursive");
-    let verbose = matches.get_flag("verbose");
-    let _force = matches.get_flag("force");
+    let analyze = matches.get_flag("analyze");
+    let count_only = matches.get_flag("count-only");
+    let budget = matches.get_one::<String>("budget").map(|size| parse_size(size)).transpose()?;
+    let dedupe_report = matches.get_flag("dedupe-report");
+    let dry_run = matches.get_flag("dry-run") || analyze || dedupe_report || count_only || budget.is_some();
+    let explain = match matches.get_one::<String>("explain").map(PathBuf::from) {
+        Some(path) if normalize_paths => Some(absolute_normalized(&path)?),
+        explain => explain,
+    };
+    let format = matches.get_one::<String>("format").map(String::as_str).unwrap_or("human");
+    let human = format == "human" && explain.is_none();
+    // XDG cache subdirectories are matched at the top level only
+    let recursive = matches.get_flag("recursive") && !xdg;
+    let verbose = matches.get_flag("verbose") && human;
+    let force = matches.get_flag("force");
+    let clear_readonly = matches.get_flag("clear-readonly") || force;
     let include_libraries = matches.get_flag("include-libraries");
+    let confirm_large = matches
+        .get_one::<String>("confirm-large")
+        .filter(|_| !force && !dry_run)
+        .map(|size| parse_size(size))
+        .transpose()?;
 
     // Handle generate-ignore option
     if matches.get_flag("generate-ignore") {
@@ -128,118 +723,898 @@ async fn main() -> anyhow::Result<()> {
         return Ok(());
     }
 
-    let cache_types = parse_cache_types(matches.get_one::<String>("types").unwrap())?;
+    let user_config = Config::load(matches.get_one::<String>("config").map(Path::new))?;
+    for warning in &user_config.warnings {
+        eprintln!("{} {}", "Warning:".bright_yellow(), warning);
+    }
+
+    let (mut selection, docker_named) = resolve_cache_selection(
+        matches.get_one::<String>("types").unwrap(),
+        matches.value_source("types") == Some(ValueSource::CommandLine),
+        matches.get_one::<String>("types-from").map(Path::new),
+    )?;
+    if matches.get_flag("auto") {
+        selection.types = CacheType::all()
+            .into_iter()
+            .filter(|cache_type| roots.iter().any(|root| detect_cache_types(root).contains(cache_type)))
+            .collect();
+    }
+    let cache_types = selection.types.clone();
+
+    // --no-parallel wins over --parallel and --parallel-roots
+    let sequential = matches.get_flag("no-parallel");
+    let parallel_threads = if sequential {
+        1
+    } else {
+        matches.get_one::<usize>("parallel").copied().unwrap_or_else(num_cpus::get)
+    };
 
-    let parallel_threads = matches
-        .get_one::<String>("parallel")
-        .map(|s| s.parse::<usize>().unwrap_or(num_cpus::get()))
-        .unwrap_or(num_cpus::get());
+    // Concurrent deletes thrash spinning disks; default to sequential I/O there
+    let io_concurrency = matches
+        .get_one::<usize>("io-concurrency")
+        .copied()
+        .or_else(|| match clearcache::utils::is_rotational(&directory) {
+            Some(true) => Some(1),
+            _ => None,
+        });
 
-    let max_depth = matches
-        .get_one::<String>("max-depth")
-        .map(|s| s.parse::<usize>().unwrap_or(20))
-        .unwrap_or(20);
+    let max_depth = matches.get_one::<usize>("max-depth").copied().unwrap_or(20);
 
     let no_ignore = matches.get_flag("no-ignore");
     let respect_gitignore = matches.get_flag("respect-gitignore");
 
-    println!(
-        "{}",
-        "🧹 ClearCache - Extremely Efficient Cache Cleaner".bright_cyan().bold()
-    );
-    println!("Directory: {}", directory.display().to_string().bright_yellow());
-    println!("Cache types: {}", format_cache_types(&cache_types).bright_green());
-    println!("Threads: {}", parallel_threads.to_string().bright_blue());
-    println!("Max depth: {}", max_depth.to_string().bright_blue());
-    
-    if no_ignore {
-        println!("{}", "🚫 Ignoring .clearcacheignore files".bright_red());
-    } else {
-        println!("{}", "📋 Respecting .clearcacheignore files".bright_cyan());
-    }
-    
-    if respect_gitignore {
-        println!("{}", "📋 Respecting .gitignore files".bright_cyan());
+    let scan_cache = if matches.get_flag("scan-cache") {
+        clearcache::utils::state_dir().map(|dir| dir.join("scan-cache.json"))
     } else {
-        println!("{}", "🔍 Ignoring .gitignore files (cache directories are often in .gitignore)".bright_yellow());
-    }
-    
-    if dry_run {
-        println!("{}", "🔍 DRY RUN MODE - No files will be deleted".bright_yellow().bold());
+        None
+    };
+    let scan_cache_ttl = matches
+        .get_one::<String>("scan-cache-ttl")
+        .map(|s| s.parse::<u64>().unwrap_or(3600))
+        .unwrap_or(3600);
+
+    if human {
+        println!(
+            "{}",
+            "🧹 ClearCache - Extremely Efficient Cache Cleaner".bright_cyan().bold()
+        );
+        if roots.len() == 1 {
+            println!("Directory: {}", directory.display().to_string().bright_yellow());
+        } else {
+            let names: Vec<String> = roots.iter().map(|root| root.display().to_string()).collect();
+            println!("Directories: {}", names.join(", ").bright_yellow());
+        }
+        if cache_types.is_empty() && matches.get_flag("auto") {
+            println!("Cache types: {}", "none detected (no project manifests found)".bright_yellow());
+        } else {
+            println!("Cache types: {}", format_cache_types(&cache_types).bright_green());
+        }
+        if !selection.pattern_names.is_empty() {
+            println!("Extra patterns: {}", selection.pattern_names.join(", ").bright_green());
+        }
+        println!("Threads: {}", parallel_threads.to_string().bright_blue());
+        if let Some(io_concurrency) = io_concurrency {
+            println!("I/O concurrency: {}", io_concurrency.to_string().bright_blue());
+        }
+        println!("Max depth: {}", max_depth.to_string().bright_blue());
+
+        if no_ignore {
+            println!("{}", "🚫 Ignoring .clearcacheignore files".bright_red());
+        } else {
+            println!("{}", "📋 Respecting .clearcacheignore files".bright_cyan());
+        }
+
+        if respect_gitignore {
+            println!("{}", "📋 Respecting .gitignore files".bright_cyan());
+        } else {
+            println!("{}", "🔍 Ignoring .gitignore files (cache directories are often in .gitignore)".bright_yellow());
+        }
+
+        if dry_run {
+            println!("{}", "🔍 DRY RUN MODE - No files will be deleted".bright_yellow().bold());
+        }
+
+        if include_libraries {
+            println!("{}", "📦 LIBRARY MODE - Including dependencies that require reinstallation".bright_red().bold());
+        } else {
+            println!("{}", "🔒 SAFE MODE - Only cleaning temporary caches (use --include-libraries for full clean)".bright_green().bold());
+        }
     }
 
-    if include_libraries {
-        println!("{}", "📦 LIBRARY MODE - Including dependencies that require reinstallation".bright_red().bold());
+    // The global ignore file is created, without rules, on the first run that may write to disk
+    let global_ignore = if no_ignore || matches.get_flag("no-global-ignore") {
+        None
     } else {
-        println!("{}", "🔒 SAFE MODE - Only cleaning temporary caches (use --include-libraries for full clean)".bright_green().bold());
+        default_global_ignore_path().inspect(|path| {
+            if dry_run {
+                return;
+            }
+            if let Err(e) = seed_global_ignore(path) {
+                if verbose {
+                    println!("Could not create global ignore file {}: {}", path.display(), e);
+                }
+            }
+        })
+    };
+
+    // The review screen needs a terminal; otherwise fall back to the normal CLI flow
+    let tui = matches.get_flag("tui") && human && clearcache::tui::is_interactive();
+    if matches.get_flag("tui") && !tui && human {
+        println!("{}", "⚠️  --tui needs an interactive terminal; continuing without it".bright_yellow());
     }
 
-    let cleaner = CacheCleaner::new(
-        directory,
-        cache_types,
+    // Spans are only timed while profiling; with no subscriber installed they cost next to nothing
+    let profiler = matches.contains_id("profile-output").then(Profiler::new);
+    if let Some(profiler) = &profiler {
+        tracing::subscriber::set_global_default(tracing_subscriber::registry().with(profiler.clone()))?;
+    }
+    let mut config = CleanerConfig {
         parallel_threads,
         recursive,
+        max_depth,
         dry_run,
         verbose,
         include_libraries,
         no_ignore,
+        follow_links: matches.get_flag("follow-links"),
+        no_canonicalize: matches.get_flag("no-canonicalize") || user_config.no_canonicalize,
+        case_insensitive: matches.get_flag("case-insensitive"),
         respect_gitignore,
-    );
+        pattern_names: selection.pattern_names,
+        scan_cache,
+        scan_cache_ttl: Duration::from_secs(scan_cache_ttl),
+        xdg,
+        clear_readonly,
+        truncate_logs: matches.get_flag("truncate-logs"),
+        stage_deletes: matches.get_flag("rename-to-delete"),
+        check_free_space: matches.get_flag("exclude-larger-than-free-space"),
+        checkpoint_dir: default_checkpoint_dir().filter(|_| !dry_run),
+        resume: matches.get_flag("resume"),
+        reset_locks: matches.get_flag("reset-locks"),
+        list_items: human && !matches.get_flag("tree") && !tui,
+        io_concurrency,
+        protected_paths: ProtectedPaths::with_overrides(
+            &user_config.protected_paths,
+            &user_config.unprotected_paths,
+        ),
+        confirm_large,
+        targets: matches
+            .get_one::<String>("targets")
+            .map(|path| load_targets(Path::new(path)))
+            .transpose()?,
+        extra_patterns: match matches.get_one::<String>("pattern-provider") {
+            Some(command) => run_provider(command, &directory, PROVIDER_TIMEOUT)?,
+            None => Vec::new(),
+        },
+        defer_sizing: dry_run && matches.get_flag("no-recursive-size"),
+        skip_network: matches.get_flag("skip-network"),
+        global_ignore,
+        apparent_size: matches.get_flag("apparent-size"),
+        path_globs: matches
+            .get_many::<String>("path-glob")
+            .into_iter()
+            .flatten()
+            .chain(&user_config.patterns)
+            .cloned()
+            .collect(),
+        trash_dir: if matches.get_flag("trash") {
+            Some(default_trash_dir().ok_or_else(|| anyhow::anyhow!("Cannot locate a state directory for the trash"))?)
+        } else {
+            None
+        },
+        since_commit: matches.get_one::<String>("since-commit").cloned(),
+        skip_in_use: matches.get_flag("skip-in-use"),
+        skip_active_builds: matches.get_flag("skip-active-builds"),
+        // `all` and `--auto` must never prune Docker as a side effect
+        docker: docker_named && !matches.get_flag("auto"),
+        dedup_stores: matches.get_flag("dedup-stores"),
+        store_max_age: matches.get_one::<Duration>("store-max-age").copied(),
+        cargo_registry: matches
+            .get_flag("prune-cargo-registry")
+            .then(cargo_home)
+            .flatten()
+            .map(|home| home.join("registry"))
+            .filter(|registry| registry.is_dir()),
+        check_permissions: matches.get_flag("check-permissions"),
+        confirm_libraries: include_libraries && !force && !dry_run,
+        priority: matches
+            .get_one::<String>("priority")
+            .map(|order| parse_cache_types(order))
+            .transpose()?
+            .unwrap_or_default(),
+        docker_wait: Duration::from_secs(
+            matches
+                .get_one::<String>("docker-wait")
+                .unwrap()
+                .parse()
+                .map_err(|_| anyhow::anyhow!("--docker-wait expects a number of seconds"))?,
+        ),
+        // The review screen needs every item to choose from
+        max_items_per_type: matches
+            .get_one::<String>("max-items-per-type")
+            .map(|n| n.parse().map_err(|_| anyhow::anyhow!("--max-items-per-type expects a number")))
+            .transpose()?
+            .filter(|_| !tui),
+        max_file_count: matches
+            .get_one::<String>("max-file-count")
+            .map(|n| n.parse().map_err(|_| anyhow::anyhow!("--max-file-count expects a number")))
+            .transpose()?,
+        count_only,
+        target_free: matches.get_one::<String>("target-free").map(|size| parse_size(size)).transpose()?,
+        order: match matches.get_one::<String>("order").map(String::as_str) {
+            Some("oldest") => CleanOrder::Oldest,
+            _ => CleanOrder::Largest,
+        },
+        min_age: matches.get_one::<Duration>("min-age").copied(),
+        parallel_roots: if sequential { 1 } else { *matches.get_one::<usize>("parallel-roots").unwrap() },
+        preserve_skeleton: matches.get_flag("preserve-skeleton"),
+        allow_nested_repos: force,
+        use_native_clean: matches.get_flag("use-native-clean"),
+        sequential,
+        max_total_deletions: matches.get_one::<usize>("max-total-deletions").copied(),
+        progress_target: ProgressOutput::default(),
+        archive_dir: matches
+            .get_one::<String>("archive-instead")
+            .map(std::path::absolute)
+            .transpose()?,
+        baseline: matches
+            .get_one::<String>("baseline")
+            .map(|path| Baseline::load(Path::new(path)).map(Arc::new))
+            .transpose()?,
+        throttle: matches
+            .get_one::<String>("throttle")
+            .map(|rate| parse_size(rate.trim_end_matches("/s")))
+            .transpose()?,
+    };
+
+    if let Some(path) = explain {
+        let explanation = CacheCleaner::new(directory, cache_types, config).explain(&path);
+        println!("{}", serde_json::to_string_pretty(&explanation)?);
+        return Ok(());
+    }
+
+    let mut total_size = Arc::new(AtomicU64::new(0));
+    let mut total_files = Arc::new(AtomicU64::new(0));
+
+    // In TUI mode, scan as a dry run first and delete only what the user selects
+    let scan_config = CleanerConfig {
+        dry_run: dry_run || tui,
+        ..config.clone()
+    };
+    // Free space is compared only for real runs; a dry run changes nothing
+    let report_disk = matches.get_flag("report-disk") && human && !dry_run;
+    let available_before = report_disk.then(|| available_space(&directory)).flatten();
+
+    // Parsed up front so a bad size is reported before anything is deleted
+    let log_file_max_size = parse_size(matches.get_one::<String>("log-file-max-size").unwrap())?;
+    let started = Instant::now();
+    let per_root = clean_roots(
+        &roots,
+        &cache_types,
+        &scan_config,
+        // Linked worktrees often live inside the main one
+        matches.get_flag("dedupe-across-roots") || all_worktrees,
+        total_size.clone(),
+        total_files.clone(),
+    )
+    .await?;
+    let mut root_totals: Vec<(PathBuf, usize, u64)> = per_root
+        .roots
+        .iter()
+        .map(|(root, result)| (root.clone(), result.directories_cleaned, result.space_freed))
+        .collect();
+    let mut result = per_root.aggregate();
+
+    if tui && result.status != CleanStatus::Aborted {
+        total_size = Arc::new(AtomicU64::new(0));
+        total_files = Arc::new(AtomicU64::new(0));
+        root_totals.clear();
+        match clearcache::tui::run_review(result.items)? {
+            Some(chosen) => {
+                config.targets = Some(
+                    chosen
+                        .into_iter()
+                        .map(|item| Target {
+                            path: item.path,
+                            cache_type: item.cache_type,
+                        })
+                        .collect(),
+                );
+                // Selecting items in the review screen is the confirmation
+                config.confirm_large = None;
+                config.confirm_libraries = false;
+
+                let cleaner = CacheCleaner::new(directory.clone(), cache_types, config);
+                result = cleaner.clean(total_size.clone(), total_files.clone()).await?;
+            }
+            // Reported like a declined prompt, so the summary and reports are still written
+            None => {
+                result = CleanResult {
+                    status: CleanStatus::Aborted,
+                    ..CleanResult::default()
+                }
+            }
+        }
+    }
+    // Per-user package manager caches live outside every root, so they are cleaned once per run
+    let global_caches = if matches.get_flag("global-caches") && result.status != CleanStatus::Aborted {
+        clean_global_caches(&SystemRunner, &directory, dry_run)
+    } else {
+        Vec::new()
+    };
+    for global in &global_caches {
+        match &global.outcome {
+            GlobalCacheOutcome::Cleaned | GlobalCacheOutcome::WouldClean => {
+                result.status = result.status.max(CleanStatus::Success);
+            }
+            GlobalCacheOutcome::Failed(message) => {
+                result.errors.push(CleanError::Other(format!("`{}` failed: {}", global.tool.command(), message)));
+                result.status = CleanStatus::CompletedWithErrors;
+            }
+            GlobalCacheOutcome::NotInstalled => {}
+        }
+    }
+    if let Some(path) = matches.get_one::<String>("log-file") {
+        let entry = LedgerEntry {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            roots: &roots,
+            status: result.status,
+            dry_run,
+            bytes_freed: result.space_freed,
+            items: result.directories_cleaned,
+            errors: result.errors.len(),
+            duration_ms: started.elapsed().as_millis() as u64,
+        };
+        append_ledger(Path::new(path), &entry, log_file_max_size)?;
+    }
+    if let (Some(path), Some(profiler)) = (matches.get_one::<String>("profile-output"), &profiler) {
+        profiler.write(Path::new(path))?;
+    }
+    let budget = budget.map(|budget| BudgetCheck::new(budget, result.space_freed));
+    let ignore_errors = matches.get_flag("ignore-errors");
+    let status = exit_status(result.status, ignore_errors)
+        .max(budget_status(budget.as_ref()))
+        .max(abort_status(&result));
+    let age_buckets = analyze.then(|| age_histogram(&result.items, SystemTime::now()));
+    let duplicates = dedupe_report.then(|| duplicate_groups(&result.items));
+
+    let apparent_freed: u64 = result.items.iter().map(|item| item.apparent_size).sum();
+    let by_type = type_breakdown(&result.items, &result.omitted);
+    let report = JsonReport {
+        schema_version: SCHEMA_VERSION,
+        root: &directory,
+        roots: &roots,
+        dry_run,
+        status: result.status,
+        items_cleaned: result.directories_cleaned,
+        files: result.files_deleted,
+        bytes_freed: result.space_freed,
+        apparent_bytes_freed: apparent_freed,
+        items: &result.items,
+        omitted: &result.omitted,
+        by_type: &by_type,
+        errors: &result.errors,
+        age_histogram: age_buckets.as_deref(),
+        budget,
+        duplicates: duplicates.as_deref(),
+    };
+    let report_json = report.to_json(matches.get_flag("json-pretty"))?;
+    if let Some(path) = matches.get_one::<String>("metrics-file") {
+        let metrics = format_prometheus_metrics(&result.items, result.errors.len(), dry_run, SystemTime::now());
+        write_metrics_file(Path::new(path), &metrics)?;
+    }
+    if let Some(destination) = matches.get_one::<String>("summary-json-to") {
+        write_summary_json(&report_json, destination, &mut std::io::stderr())?;
+    }
+    // Every way out of the run below prints the machine summary, even a cancelled run
+    let machine_summary = matches
+        .get_flag("machine-summary")
+        .then(|| format_machine_summary(result.space_freed, result.directories_cleaned, result.errors.len()));
+    let finish = |status: i32| {
+        if let Some(line) = &machine_summary {
+            eprintln!("{}", line);
+        }
+        exit_with(status)
+    };
+
+    // Roots cleaned before a declined prompt keep their results; only a run that deleted
+    // nothing is reported as cancelled outright
+    if result.status == CleanStatus::Aborted && result.items.is_empty() && human {
+        for error in &result.errors {
+            eprintln!("{} {}", "Error:".bright_red(), error);
+        }
+        println!("{}", "Cancelled - nothing was deleted".bright_yellow());
+        return finish(status);
+    }
+    if count_only && human {
+        println!("\n{}", "📊 Count".bright_cyan().bold());
+        println!("Cache items: {}", result.directories_cleaned.to_string().bright_green());
+        if scan_config.defer_sizing {
+            println!("Total size: {}", "? (sizes not calculated)".bright_yellow());
+        } else {
+            println!("Files: {}", result.files_deleted.to_string().bright_green());
+            println!("Total size: {}", humansize::format_size(result.space_freed, humansize::BINARY).bright_green());
+        }
+        return finish(status);
+    }
 
-    let total_size = Arc::new(AtomicU64::new(0));
-    let total_files = Arc::new(AtomicU64::new(0));
 
-    let result = cleaner.clean(total_size.clone(), total_files.clone()).await?;
+    if !human {
+        if format == "markdown" {
+            print!("{}", format_markdown_report(&result.items, &result.omitted, &directory, dry_run));
+        } else {
+            println!("{}", report_json);
+        }
+        return finish(status);
+    }
 
     println!("\n{}", "📊 Summary".bright_cyan().bold());
     println!("Files processed: {}", total_files.load(Ordering::Relaxed).to_string().bright_green());
-    println!("Space freed: {}", humansize::format_size(total_size.load(Ordering::Relaxed), humansize::BINARY).bright_green());
+    if result.items.iter().any(|item| !item.size_known) {
+        println!("Space freed: {}", "? (sizes not calculated)".bright_yellow());
+    } else {
+        println!("Space freed: {}", humansize::format_size(total_size.load(Ordering::Relaxed), humansize::BINARY).bright_green());
+        // Hard links and sparse files make disk usage differ from the logical total
+        if verbose && apparent_freed != result.space_freed {
+            println!("Apparent size: {} (logical file lengths)", humansize::format_size(apparent_freed, humansize::BINARY).bright_blue());
+        }
+    }
+    if let Some((before, after)) = available_before.zip(available_space(&directory).filter(|_| report_disk)) {
+        for line in format_disk_delta(result.space_freed, before, after) {
+            println!("{}", line.bright_blue());
+        }
+    }
     println!("Directories cleaned: {}", result.directories_cleaned.to_string().bright_green());
+    if root_totals.len() > 1 {
+        for (root, items, size) in &root_totals {
+            println!(
+                "  {} ({} items, {})",
+                root.display().to_string().bright_yellow(),
+                items.to_string().bright_cyan(),
+                humansize::format_size(*size, humansize::BINARY).bright_green()
+            );
+        }
+    }
+
+    if !global_caches.is_empty() {
+        println!("\n{}", "🌐 Global caches".bright_cyan().bold());
+        for global in &global_caches {
+            let outcome = match &global.outcome {
+                GlobalCacheOutcome::Cleaned => "cleaned".bright_green(),
+                GlobalCacheOutcome::WouldClean => "would clean".bright_green(),
+                GlobalCacheOutcome::NotInstalled => "not installed, skipped".dimmed(),
+                GlobalCacheOutcome::Failed(_) => "failed".bright_red(),
+            };
+            println!("  {} ({}): {}", global.tool.name.bright_yellow(), global.tool.command(), outcome);
+        }
+    }
+
+    if by_type.len() > 1 {
+        println!("\n{}", "🗂️  By type".bright_cyan().bold());
+        for (total, percent) in type_shares(&by_type) {
+            println!(
+                "  {:<10} {:>10} {:>6}  ({} items, {} files)",
+                format!("{:?}", total.cache_type).bright_yellow(),
+                humansize::format_size(total.size, humansize::BINARY).bright_green(),
+                format!("{:.1}%", percent).bright_green(),
+                total.items.to_string().bright_cyan(),
+                total.files.to_string().bright_cyan()
+            );
+        }
+    }
+
+    if let Some(buckets) = &age_buckets {
+        println!("\n{}", "⏳ Cache age (by last modification)".bright_cyan().bold());
+        for bucket in buckets {
+            println!(
+                "  {:<8} {:>6} items  {}",
+                bucket.label,
+                bucket.items.to_string().bright_cyan(),
+                humansize::format_size(bucket.size, humansize::BINARY).bright_green()
+            );
+        }
+    }
+
+    if let Some(duplicates) = &duplicates {
+        println!("\n{}", "🔁 Duplicate caches (by name)".bright_cyan().bold());
+        if duplicates.is_empty() {
+            println!("  No cache directory name appears more than once");
+        }
+        for group in duplicates {
+            println!(
+                "  {:<20} {:>6}x  {} files  {}",
+                group.name.bright_yellow(),
+                group.items.to_string().bright_cyan(),
+                group.files.to_string().bright_cyan(),
+                humansize::format_size(group.size, humansize::BINARY).bright_green()
+            );
+        }
+    }
+
+    if matches.get_flag("tree") && !result.items.is_empty() {
+        println!("\n{}", "🌳 Cleaned items".bright_cyan().bold());
+        for line in render_tree(&result.items, &directory, verbose, true) {
+            println!("{}", line);
+        }
+    }
+
+    if matches.get_one::<String>("group-by").map(String::as_str) == Some("project") && !result.items.is_empty() {
+        println!("\n{}", "📁 By project".bright_cyan().bold());
+        for group in group_by_project(&result.items, &directory) {
+            println!(
+                "  {} ({} items, {} files, {})",
+                group.project.display().to_string().bright_yellow(),
+                group.items.to_string().bright_cyan(),
+                group.files.to_string().bright_cyan(),
+                humansize::format_size(group.size, humansize::BINARY).bright_green()
+            );
+        }
+    }
+
+    if (verbose || matches.get_flag("show-skipped")) && !result.skipped.is_empty() {
+        println!("\n{}", "⏭️  Skipped".bright_cyan().bold());
+        let mut by_reason: BTreeMap<SkipReason, Vec<&Path>> = BTreeMap::new();
+        for (path, reason) in &result.skipped {
+            by_reason.entry(*reason).or_default().push(path);
+        }
+        for (reason, paths) in by_reason {
+            println!("{} ({}):", reason.to_string().bright_yellow(), paths.len());
+            for path in paths {
+                println!("  {}", path.display());
+            }
+        }
+    }
+
+    // Deleted libraries leave projects broken until they are reinstalled
+    let hints = if dry_run { Vec::new() } else { restore_hints(&result.items, &directory) };
+    if !hints.is_empty() {
+        println!("\n{}", "🔧 To restore libraries".bright_cyan().bold());
+        for hint in hints {
+            println!("  {}", hint);
+        }
+    }
     
-    if result.errors.is_empty() {
+    if result.status == CleanStatus::Aborted {
+        println!("{}", "⚠️  Cancelled - the remaining items were left in place".bright_yellow().bold());
+        for error in &result.errors {
+            println!("  • {}", error.to_string().bright_red());
+        }
+    } else if result.status != CleanStatus::CompletedWithErrors {
         println!("{}", "✅ All operations completed successfully!".bright_green().bold());
     } else {
-        println!("{}", "⚠️  Some errors occurred:".bright_yellow().bold());
-        for error in &result.errors {
-            println!("  • {}", error.bright_red());
+        let max_errors = matches
+            .get_one::<String>("max-errors-shown")
+            .and_then(|n| n.parse::<usize>().ok())
+            .unwrap_or(10);
+        let lines = error_lines(&result.errors, max_errors, ignore_errors);
+        if lines.is_empty() {
+            println!("{}", format!("✅ Completed ({} errors ignored)", result.errors.len()).bright_green().bold());
+        } else {
+            println!("{}", "⚠️  Some errors occurred:".bright_yellow().bold());
+            for line in lines {
+                println!("  • {}", line.bright_red());
+            }
         }
     }
 
+    if let Some(budget) = &budget {
+        let message = budget.message();
+        if budget.is_over() {
+            println!("{}", format!("💸 {}", message).bright_red().bold());
+        } else {
+            println!("{}", format!("💰 {}", message).bright_green());
+        }
+    }
+
+    finish(status)
+}
+
+/// Errors listed under the summary; with `--ignore-errors` only their count is shown
+fn error_lines(errors: &[CleanError], max_shown: usize, ignore_errors: bool) -> Vec<String> {
+    if ignore_errors {
+        return Vec::new();
+    }
+    summarize_errors(errors, max_shown)
+}
+
+/// Status for a finished run: 1 if anything failed, unless errors are ignored
+fn exit_status(status: CleanStatus, ignore_errors: bool) -> i32 {
+    match status {
+        CleanStatus::CompletedWithErrors if !ignore_errors => 1,
+        _ => 0,
+    }
+}
+
+/// 1 when the run was stopped by a safety limit such as `--max-total-deletions`, which records
+/// why as an error; a declined prompt alone is not a failure
+fn abort_status(result: &CleanResult) -> i32 {
+    i32::from(result.status == CleanStatus::Aborted && !result.errors.is_empty())
+}
+
+/// 1 when `--budget` is exceeded, so a preview can gate CI like a linter
+fn budget_status(budget: Option<&BudgetCheck>) -> i32 {
+    i32::from(budget.is_some_and(BudgetCheck::is_over))
+}
+
+fn exit_with(status: i32) -> anyhow::Result<()> {
+    if status != 0 {
+        std::io::stdout().flush()?;
+        std::process::exit(status);
+    }
     Ok(())
 }
 
+/// Single-line key=value summary for scripts, e.g. `clearcache: freed=1024 items=3 errors=0`
+fn format_machine_summary(freed: u64, items: usize, errors: usize) -> String {
+    format!("clearcache: freed={} items={} errors={}", freed, items, errors)
+}
+
+/// `path` against the working directory, with `.` and `..` resolved lexically
+fn absolute_normalized(path: &Path) -> anyhow::Result<PathBuf> {
+    Ok(normalize_path(&std::env::current_dir()?.join(path)))
+}
+
+/// Directories listed one per line; blank lines are skipped
+fn read_roots(input: impl std::io::BufRead) -> anyhow::Result<Vec<PathBuf>> {
+    let mut roots = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() {
+            roots.push(PathBuf::from(line));
+        }
+    }
+    Ok(roots)
+}
+
+#[cfg(target_os = "linux")]
+fn resolve_xdg_cache_home() -> anyhow::Result<PathBuf> {
+    clearcache::utils::xdg_cache_home()
+        .ok_or_else(|| anyhow::anyhow!("Could not determine $XDG_CACHE_HOME (is $HOME set?)"))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn resolve_xdg_cache_home() -> anyhow::Result<PathBuf> {
+    Err(anyhow::anyhow!("--xdg is only supported on Linux"))
+}
+
+/// A number of days, possibly fractional, for `--min-age` and `--store-max-age`
+fn parse_days(value: &str) -> Result<Duration, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|days| *days >= 0.0)
+        .and_then(|days| Duration::try_from_secs_f64(days * 86400.0).ok())
+        .ok_or_else(|| format!("expected a number of days, got '{}'", value))
+}
+
 fn parse_cache_types(types_str: &str) -> anyhow::Result<Vec<CacheType>> {
     if types_str == "all" {
-        return Ok(vec![
-            CacheType::Node,
-            CacheType::Rust,
-            CacheType::Go,
-            CacheType::Python,
-            CacheType::Docker,
-            CacheType::General,
-        ]);
+        return Ok(CacheType::all());
     }
 
     let mut types = Vec::new();
     for type_str in types_str.split(',') {
-        match type_str.trim().to_lowercase().as_str() {
-            "node" | "nodejs" | "npm" | "yarn" | "pnpm" => types.push(CacheType::Node),
-            "rust" | "cargo" => types.push(CacheType::Rust),
-            "go" | "golang" => types.push(CacheType::Go),
-            "python" | "py" | "pip" => types.push(CacheType::Python),
-            "docker" => types.push(CacheType::Docker),
-            "general" | "cache" => types.push(CacheType::General),
-            _ => return Err(anyhow::anyhow!("Unknown cache type: {}", type_str)),
+        match CacheType::from_name(type_str) {
+            Some(cache_type) => types.push(cache_type),
+            None => return Err(anyhow::anyhow!("Unknown cache type: {}", type_str)),
         }
     }
 
     Ok(types)
 }
 
+/// Combine `--types` with an optional `--types-from` file. The default `--types all`
+/// only applies when no types file is given. Also returns whether Docker was named in
+/// either list, rather than only included through `all`.
+fn resolve_cache_selection(
+    types_str: &str,
+    types_explicit: bool,
+    types_file: Option<&Path>,
+) -> anyhow::Result<(CacheSelection, bool)> {
+    let named = |types: &[CacheType]| types_str != "all" && types.contains(&CacheType::Docker);
+    let Some(types_file) = types_file else {
+        let types = parse_cache_types(types_str)?;
+        let docker_named = named(&types);
+        return Ok((
+            CacheSelection {
+                types,
+                pattern_names: Vec::new(),
+            },
+            docker_named,
+        ));
+    };
+
+    let mut selection = CacheSelection::default();
+    if types_explicit {
+        selection.types = parse_cache_types(types_str)?;
+    }
+    let mut docker_named = named(&selection.types);
+    let from_file = load_types_file(types_file)?;
+    docker_named |= from_file.types.contains(&CacheType::Docker);
+    selection.merge(from_file);
+    Ok((selection, docker_named))
+}
+
 fn format_cache_types(types: &[CacheType]) -> String {
+    let mut types = types.to_vec();
+    types.sort();
+    types.dedup();
     types
         .iter()
         .map(|t| format!("{:?}", t))
         .collect::<Vec<_>>()
         .join(", ")
-} 
\ No newline at end of file
+}
+
+/// Describe every cache type and its patterns, split into safe caches and libraries
+fn print_types_info() {
+    for cache_type in CacheType::all() {
+        println!("{}", format!("{:?}", cache_type).bright_cyan().bold());
+        println!("  {}", cache_type.summary());
+        let names = |patterns: Vec<CachePattern>| {
+            patterns.into_iter().map(|pattern| pattern.name).collect::<Vec<_>>().join(", ")
+        };
+        let safe = names(cache_type.get_safe_patterns());
+        if !safe.is_empty() {
+            println!("  {} {} (regenerated automatically)", "Safe:".bright_green(), safe);
+        }
+        let libraries = names(cache_type.get_library_patterns());
+        if !libraries.is_empty() {
+            let restore = cache_type
+                .reinstall_hint()
+                .map(|hint| format!("; restore with `{}`", hint))
+                .unwrap_or_default();
+            println!(
+                "  {} {} (only with --include-libraries{})",
+                "Libraries:".bright_red(),
+                libraries,
+                restore
+            );
+        }
+        println!();
+    }
+}
+
+/// Restore the most recent trashed run
+fn run_undo() -> anyhow::Result<()> {
+    let trash_dir = default_trash_dir().ok_or_else(|| anyhow::anyhow!("Cannot locate a state directory for the trash"))?;
+    let Some(result) = undo_last(&trash_dir)? else {
+        println!("{}", "Nothing to undo - the trash is empty".bright_yellow());
+        return Ok(());
+    };
+
+    for path in &result.restored {
+        println!("  Restored: {}", path.display().to_string().bright_green());
+    }
+    if result.failed.is_empty() {
+        println!("{}", format!("✅ Restored {} items", result.restored.len()).bright_green());
+        return Ok(());
+    }
+
+    println!("\n{}", "⚠️  Some items could not be restored (they remain in the trash):".bright_yellow());
+    for (path, error) in &result.failed {
+        println!("  {} {}: {}", "•".bright_red(), path.display(), error);
+    }
+    Err(anyhow::anyhow!("{} of {} items could not be restored", result.failed.len(), result.failed.len() + result.restored.len()))
+}
+
+fn run_doctor(directory: &Path, config: Option<&Path>) -> anyhow::Result<()> {
+    let global_ignore = default_global_ignore_path();
+    let diagnostics = run_checks(&SystemRunner, directory, config, global_ignore.as_deref());
+    for diagnostic in &diagnostics {
+        let line = format!("{}: {}", diagnostic.check, diagnostic.message);
+        match diagnostic.severity {
+            Severity::Ok => println!("✅ {}", line.bright_green()),
+            Severity::Warning => println!("⚠️  {}", line.bright_yellow()),
+            Severity::Error => println!("❌ {}", line.bright_red()),
+        }
+    }
+    let problems = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+    if problems > 0 {
+        return Err(anyhow::anyhow!("{} checks failed", problems));
+    }
+    Ok(())
+}
+
+/// Write the JSON report to a file, or to `stderr` when the destination is `-`
+fn write_summary_json(report_json: &str, destination: &str, stderr: &mut impl Write) -> anyhow::Result<()> {
+    if destination == "-" {
+        writeln!(stderr, "{}", report_json)?;
+    } else {
+        std::fs::write(destination, format!("{}\n", report_json))
+            .map_err(|e| anyhow::anyhow!("Failed to write summary to {}: {}", destination, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_machine_summary_format() {
+        assert_eq!(
+            format_machine_summary(1288490188, 42, 0),
+            "clearcache: freed=1288490188 items=42 errors=0"
+        );
+
+        let line = format_machine_summary(0, 0, 3);
+        let fields: Vec<&str> = line.trim_start_matches("clearcache: ").split(' ').collect();
+        assert_eq!(fields, vec!["freed=0", "items=0", "errors=3"]);
+    }
+
+    #[test]
+    fn test_parse_days() {
+        assert_eq!(parse_days("2"), Ok(Duration::from_secs(2 * 86400)));
+        assert_eq!(parse_days("0.5"), Ok(Duration::from_secs(43200)));
+        assert_eq!(parse_days("0"), Ok(Duration::ZERO));
+        for bad in ["-1", "abc", "", "inf", "NaN", "1e300"] {
+            assert!(parse_days(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_abort_fails_only_when_a_limit_stopped_the_run() {
+        let declined = CleanResult {
+            status: CleanStatus::Aborted,
+            ..CleanResult::default()
+        };
+        assert_eq!(abort_status(&declined), 0);
+        let refused = CleanResult {
+            errors: vec![CleanError::Other("refusing to delete 4 items".to_string())],
+            ..declined
+        };
+        assert_eq!(abort_status(&refused), 1);
+        assert_eq!(abort_status(&CleanResult::default()), 0);
+    }
+
+    #[test]
+    fn test_ignore_errors_counts_without_listing() {
+        let errors = vec![
+            CleanError::Other("disk full".to_string()),
+            CleanError::Docker("daemon gone".to_string()),
+        ];
+        assert_eq!(error_lines(&errors, 10, false).len(), 2);
+        assert_eq!(exit_status(CleanStatus::CompletedWithErrors, false), 1);
+
+        assert!(error_lines(&errors, 10, true).is_empty());
+        assert_eq!(exit_status(CleanStatus::CompletedWithErrors, true), 0);
+        assert_eq!(format_machine_summary(0, 0, errors.len()), "clearcache: freed=0 items=0 errors=2");
+
+        assert_eq!(exit_status(CleanStatus::Success, false), 0);
+        assert_eq!(exit_status(CleanStatus::NothingFound, false), 0);
+    }
+
+    #[test]
+    fn test_budget_reports_excess_and_fails_only_when_over() {
+        let over = BudgetCheck::new(5 * 1024 * 1024 * 1024, 7 * 1024 * 1024 * 1024);
+        assert_eq!(over.message(), "Cache usage 7 GiB exceeds the 5 GiB budget by 2 GiB");
+        assert_eq!(budget_status(Some(&over)), 1);
+        assert_eq!(exit_status(CleanStatus::Success, false).max(budget_status(Some(&over))), 1);
+
+        let under = BudgetCheck::new(5 * 1024 * 1024 * 1024, 1024 * 1024 * 1024);
+        assert_eq!(under.message(), "Cache usage 1 GiB is within the 5 GiB budget (4 GiB to spare)");
+        assert_eq!(budget_status(Some(&under)), 0);
+        assert_eq!(budget_status(Some(&BudgetCheck::new(1024, 1024))), 0);
+        assert_eq!(budget_status(None), 0);
+    }
+
+    #[test]
+    fn test_summary_json_destinations() {
+        let report_json = r#"{"items_cleaned":2,"bytes_freed":2048}"#;
+
+        let mut stderr = Vec::new();
+        write_summary_json(report_json, "-", &mut stderr).unwrap();
+        let written: serde_json::Value = serde_json::from_slice(&stderr).unwrap();
+        assert_eq!(written["bytes_freed"], 2048);
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("summary.json");
+        let mut stderr = Vec::new();
+        write_summary_json(report_json, path.to_str().unwrap(), &mut stderr).unwrap();
+        assert!(stderr.is_empty());
+        let written: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written["items_cleaned"], 2);
+    }
+}
@@ -0,0 +1,130 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::Context as LayerContext;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// A tracing layer that times every span for `--profile-output` and writes them in Chrome's
+/// trace event format, viewable in `chrome://tracing`, Perfetto or speedscope. A span is timed
+/// from its creation until it closes; entering it is not required.
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    started: Instant,
+    events: Arc<Mutex<Vec<TraceEvent>>>,
+}
+
+/// One complete ("X") event; times are in microseconds since the profiler started
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceEvent {
+    pub name: String,
+    pub cat: &'static str,
+    pub ph: &'static str,
+    pub ts: u64,
+    pub dur: u64,
+    pub pid: u32,
+    pub tid: u64,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub args: BTreeMap<String, String>,
+}
+
+/// Kept in a span's extensions while it is open
+struct Timing {
+    started: Instant,
+    tid: u64,
+    args: BTreeMap<String, String>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            events: Arc::default(),
+        }
+    }
+
+    pub fn events(&self) -> Vec<TraceEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let trace = serde_json::json!({
+            "traceEvents": self.events(),
+            "displayTimeUnit": "ms",
+        });
+        std::fs::write(path, serde_json::to_vec(&trace)?)
+            .with_context(|| format!("Failed to write profile to {}", path.display()))
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Layer<S> for Profiler
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: LayerContext<'_, S>) {
+        let mut args = BTreeMap::new();
+        attrs.record(&mut FieldStrings(&mut args));
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Timing {
+                started: Instant::now(),
+                tid: thread_id(),
+                args,
+            });
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(timing) = span.extensions_mut().remove::<Timing>() else {
+            return;
+        };
+        let event = TraceEvent {
+            name: span.name().to_string(),
+            cat: "clearcache",
+            ph: "X",
+            ts: timing.started.duration_since(self.started).as_micros() as u64,
+            dur: timing.started.elapsed().as_micros() as u64,
+            pid: std::process::id(),
+            tid: timing.tid,
+            args: timing.args,
+        };
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+/// Span fields as strings, shown as the event's arguments in the viewer
+struct FieldStrings<'a>(&'a mut BTreeMap<String, String>);
+
+impl Visit for FieldStrings<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+/// Small stable per-thread numbers, so the viewer shows one row per worker thread
+fn thread_id() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static ID: u64 = NEXT.fetch_add(1, Ordering::Relaxed);
+    }
+    ID.with(|id| *id)
+}
@@ -0,0 +1,200 @@
+use crate::cache_types::{normalize_pattern, CachePattern, CacheType};
+use crate::traversal::expand_braces;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Largest provider output accepted, to keep a misbehaving provider from exhausting memory
+const MAX_OUTPUT_BYTES: u64 = 1024 * 1024;
+
+/// One pattern as emitted by an external provider
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProviderPattern {
+    cache_type: String,
+    name: String,
+    patterns: Vec<String>,
+    description: String,
+    #[serde(default = "default_true")]
+    is_directory: bool,
+    #[serde(default = "default_true")]
+    recursive_safe: bool,
+    #[serde(default)]
+    is_library: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Run an external pattern provider and parse the patterns it prints on stdout.
+///
+/// The command runs through the platform shell in `working_dir` with a minimal
+/// environment, no stdin, and is killed if it does not finish within `timeout`.
+pub fn run_provider(command: &str, working_dir: &Path, timeout: Duration) -> Result<Vec<(CacheType, CachePattern)>> {
+    let mut cmd = shell_command(command);
+    cmd.current_dir(working_dir)
+        .env_clear()
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit());
+    for var in ["PATH", "HOME", "USERPROFILE", "SYSTEMROOT", "TMPDIR", "TEMP"] {
+        if let Some(value) = std::env::var_os(var) {
+            cmd.env(var, value);
+        }
+    }
+
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("Failed to run pattern provider '{}'", command))?;
+
+    // Read on a separate thread so a chatty provider can't block on a full pipe
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let reader = std::thread::spawn(move || {
+        let mut output = String::new();
+        stdout.take(MAX_OUTPUT_BYTES).read_to_string(&mut output).map(|_| output)
+    });
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() > timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow::anyhow!(
+                "Pattern provider '{}' timed out after {}s",
+                command,
+                timeout.as_secs()
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+
+    if !status.success() {
+        return Err(anyhow::anyhow!("Pattern provider '{}' exited with {}", command, status));
+    }
+
+    let output = reader
+        .join()
+        .map_err(|_| anyhow::anyhow!("Failed to read pattern provider output"))??;
+    parse_provider_output(&output).with_context(|| format!("Invalid output from pattern provider '{}'", command))
+}
+
+/// Parse and validate a provider's JSON array of patterns
+pub fn parse_provider_output(output: &str) -> Result<Vec<(CacheType, CachePattern)>> {
+    let raw: Vec<ProviderPattern> = serde_json::from_str(output)?;
+    let mut patterns = Vec::with_capacity(raw.len());
+
+    for entry in raw {
+        let cache_type = CacheType::from_name(&entry.cache_type)
+            .ok_or_else(|| anyhow::anyhow!("Unknown cache type '{}' in pattern '{}'", entry.cache_type, entry.name))?;
+        if entry.name.trim().is_empty() {
+            return Err(anyhow::anyhow!("Pattern names must not be empty"));
+        }
+        if entry.patterns.is_empty() {
+            return Err(anyhow::anyhow!("Pattern '{}' has no patterns", entry.name));
+        }
+        // Patterns match a single file name; anything broader could delete arbitrary files
+        for pattern in entry.patterns.iter().flat_map(|p| expand_braces(&normalize_pattern(p))) {
+            if pattern.is_empty() || pattern.contains(['/', '\\']) || pattern.chars().all(|c| c == '*' || c == '.') {
+                return Err(anyhow::anyhow!("Pattern '{}' in '{}' is too broad or not a file name", pattern, entry.name));
+            }
+        }
+
+        patterns.push((cache_type, CachePattern {
+            name: entry.name,
+            patterns: entry.patterns.iter().map(|p| normalize_pattern(p)).collect(),
+            description: entry.description,
+            is_directory: entry.is_directory,
+            recursive_safe: entry.recursive_safe,
+            is_library: entry.is_library,
+            truncatable: false,
+        }));
+    }
+
+    Ok(patterns)
+}
+
+#[cfg(unix)]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_provider_output_validates_schema() {
+        let patterns = parse_provider_output(
+            r#"[{"cache_type":"node","name":"turbo","patterns":[".turbo"],"description":"Turborepo cache"}]"#,
+        )
+        .unwrap();
+        assert_eq!(patterns.len(), 1);
+        assert_eq!(patterns[0].0, CacheType::Node);
+        assert!(patterns[0].1.is_directory);
+        assert!(!patterns[0].1.is_library);
+
+        // A trailing slash names the same directory
+        let patterns = parse_provider_output(
+            r#"[{"cache_type":"node","name":"deps","patterns":["node_modules/"],"description":""}]"#,
+        )
+        .unwrap();
+        assert_eq!(patterns[0].1.patterns, vec!["node_modules".to_string()]);
+
+        for bad in [
+            r#"[{"cache_type":"cobol","name":"x","patterns":["x"],"description":""}]"#,
+            r#"[{"cache_type":"node","name":"x","patterns":["*"],"description":""}]"#,
+            r#"[{"cache_type":"node","name":"x","patterns":["../src"],"description":""}]"#,
+            r#"[{"cache_type":"node","name":"x","patterns":["//"],"description":""}]"#,
+            r#"[{"cache_type":"node","name":"x","patterns":["{build,*}"],"description":""}]"#,
+            r#"[{"cache_type":"node","name":"x","patterns":["x"],"description":"","extra":1}]"#,
+            r#"{"not":"an array"}"#,
+        ] {
+            assert!(parse_provider_output(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_provider_script_patterns_match_during_scan() {
+        use crate::traversal::{CacheTraversal, TraversalConfig};
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("app/.turbo")).unwrap();
+
+        let script = root.join("provider.sh");
+        std::fs::write(
+            &script,
+            "#!/bin/sh\necho '[{\"cache_type\":\"node\",\"name\":\"turbo\",\"patterns\":[\".turbo\"],\"description\":\"Turborepo cache\"}]'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let patterns = run_provider(&script.display().to_string(), root, Duration::from_secs(10)).unwrap();
+        let results = CacheTraversal::new(TraversalConfig::default(), patterns)
+            .find_cache_items(root)
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].path.ends_with("app/.turbo"));
+
+        let err = run_provider("sleep 5", root, Duration::from_millis(100)).unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+    }
+}
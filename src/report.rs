@@ -0,0 +1,905 @@
+use crate::cache_cleaner::{CleanStatus, CleanedItem, OmittedItems};
+use crate::cache_types::CacheType;
+use crate::errors::{CleanError, ErrorKind};
+use crate::utils::find_project_root;
+use colored::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Totals for the cleaned items attributed to one project
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectSummary {
+    pub project: PathBuf,
+    pub items: usize,
+    pub files: u64,
+    pub size: u64,
+}
+
+/// Totals for matched items sharing a directory name, e.g. every `node_modules` of a monorepo
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DuplicateGroup {
+    pub name: String,
+    pub items: usize,
+    pub files: u64,
+    pub size: u64,
+}
+
+/// Totals for the cleaned items of one cache type
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TypeTotal {
+    pub cache_type: CacheType,
+    pub items: usize,
+    pub files: u64,
+    pub size: u64,
+}
+
+/// Per-type totals of listed and omitted items, in the canonical [`CacheType`] order
+pub fn type_breakdown(items: &[CleanedItem], omitted: &[OmittedItems]) -> Vec<TypeTotal> {
+    let mut totals: BTreeMap<CacheType, TypeTotal> = BTreeMap::new();
+    let listed = items.iter().map(|item| (&item.cache_type, 1, item.files, item.size));
+    let unlisted = omitted.iter().map(|group| (&group.cache_type, group.items, group.files, group.size));
+    for (cache_type, items, files, size) in listed.chain(unlisted) {
+        let total = totals.entry(cache_type.clone()).or_insert(TypeTotal {
+            cache_type: cache_type.clone(),
+            items: 0,
+            files: 0,
+            size: 0,
+        });
+        total.items += items;
+        total.files += files;
+        total.size += size;
+    }
+    totals.into_values().collect()
+}
+
+/// Each type's percentage of the total size, largest first. Equal sizes keep the canonical order.
+pub fn type_shares(totals: &[TypeTotal]) -> Vec<(&TypeTotal, f64)> {
+    let overall: u64 = totals.iter().map(|total| total.size).sum();
+    let mut shares: Vec<(&TypeTotal, f64)> = totals
+        .iter()
+        .map(|total| {
+            let percent = if overall == 0 { 0.0 } else { total.size as f64 * 100.0 / overall as f64 };
+            (total, percent)
+        })
+        .collect();
+    shares.sort_by_key(|(total, _)| std::cmp::Reverse(total.size));
+    shares
+}
+
+/// Attribute each cleaned item to its nearest enclosing project (a directory with a
+/// manifest), falling back to the scan root. Sorted by size, largest first.
+pub fn group_by_project(items: &[CleanedItem], root: &Path) -> Vec<ProjectSummary> {
+    let canonical_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let mut groups: HashMap<PathBuf, ProjectSummary> = HashMap::new();
+
+    for item in items {
+        // Items may be reported canonicalized depending on the traversal engine
+        let stop_at = if item.path.starts_with(root) { root } else { canonical_root.as_path() };
+        let project = find_project_root(&item.path, stop_at).unwrap_or_else(|| stop_at.to_path_buf());
+
+        let summary = groups.entry(project.clone()).or_insert(ProjectSummary {
+            project,
+            items: 0,
+            files: 0,
+            size: 0,
+        });
+        summary.items += 1;
+        summary.files += item.files;
+        summary.size += item.size;
+    }
+
+    let mut summaries: Vec<ProjectSummary> = groups.into_values().collect();
+    summaries.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.project.cmp(&b.project)));
+    summaries
+}
+
+/// Group items by file name for `--dedupe-report`, keeping names that appear more than once.
+/// Sorted by combined size, largest first.
+pub fn duplicate_groups(items: &[CleanedItem]) -> Vec<DuplicateGroup> {
+    let mut groups: HashMap<String, DuplicateGroup> = HashMap::new();
+    for item in items {
+        let name = item.path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let group = groups.entry(name.clone()).or_insert(DuplicateGroup {
+            name,
+            items: 0,
+            files: 0,
+            size: 0,
+        });
+        group.items += 1;
+        group.files += item.files;
+        group.size += item.size;
+    }
+
+    let mut duplicates: Vec<DuplicateGroup> = groups.into_values().filter(|group| group.items > 1).collect();
+    duplicates.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    duplicates
+}
+
+/// Commands that restore the library items among `items`, one per project and type, in the
+/// order the items were cleaned, e.g. "run `npm install` in /work/web"
+pub fn restore_hints(items: &[CleanedItem], root: &Path) -> Vec<String> {
+    let canonical_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let mut hints = Vec::new();
+    for item in items.iter().filter(|item| item.is_library) {
+        let Some(hint) = item.cache_type.reinstall_hint() else {
+            continue;
+        };
+        let stop_at = if item.path.starts_with(root) { root } else { canonical_root.as_path() };
+        let project = find_project_root(&item.path, stop_at)
+            .or_else(|| item.path.parent().map(Path::to_path_buf))
+            .unwrap_or_else(|| stop_at.to_path_buf());
+        let line = format!("run `{}` in {}", hint, project.display());
+        if !hints.contains(&line) {
+            hints.push(line);
+        }
+    }
+    hints
+}
+
+/// Space held by caches within an age range, by last modification time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AgeBucket {
+    pub label: &'static str,
+    pub items: usize,
+    pub size: u64,
+}
+
+/// Bucket item sizes by age (<1d, 1-7d, 7-30d, >30d, unknown). Buckets are disjoint
+/// so their sizes sum to the total.
+pub fn age_histogram(items: &[CleanedItem], now: SystemTime) -> Vec<AgeBucket> {
+    const DAY: u64 = 86_400;
+    let mut buckets = vec![
+        AgeBucket { label: "<1d", items: 0, size: 0 },
+        AgeBucket { label: "1-7d", items: 0, size: 0 },
+        AgeBucket { label: "7-30d", items: 0, size: 0 },
+        AgeBucket { label: ">30d", items: 0, size: 0 },
+        AgeBucket { label: "unknown", items: 0, size: 0 },
+    ];
+    let now_secs = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+
+    for item in items {
+        let index = match item.modified.map(|modified| now_secs.saturating_sub(modified)) {
+            Some(age) if age < DAY => 0,
+            Some(age) if age < 7 * DAY => 1,
+            Some(age) if age < 30 * DAY => 2,
+            Some(_) => 3,
+            None => 4,
+        };
+        buckets[index].items += 1;
+        buckets[index].size += item.size;
+    }
+
+    buckets
+}
+
+/// Cache usage compared with a `--budget`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BudgetCheck {
+    pub budget: u64,
+    pub usage: u64,
+    pub excess: u64, // Zero when within budget
+}
+
+impl BudgetCheck {
+    pub fn new(budget: u64, usage: u64) -> Self {
+        Self {
+            budget,
+            usage,
+            excess: usage.saturating_sub(budget),
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.excess > 0
+    }
+
+    pub fn message(&self) -> String {
+        let size = |bytes| humansize::format_size(bytes, humansize::BINARY);
+        if self.is_over() {
+            format!(
+                "Cache usage {} exceeds the {} budget by {}",
+                size(self.usage),
+                size(self.budget),
+                size(self.excess)
+            )
+        } else {
+            format!(
+                "Cache usage {} is within the {} budget ({} to spare)",
+                size(self.usage),
+                size(self.budget),
+                size(self.budget - self.usage)
+            )
+        }
+    }
+}
+
+/// Version of the `--format json` report layout. Bumped when a field is removed, renamed or
+/// changes meaning; new optional fields don't bump it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Machine-readable run summary for `--format json`
+#[derive(Debug, Serialize)]
+pub struct JsonReport<'a> {
+    pub schema_version: u32, // Always SCHEMA_VERSION
+    pub root: &'a Path, // The first of `roots`, kept for single-root consumers
+    pub roots: &'a [PathBuf],
+    pub dry_run: bool,
+    pub status: CleanStatus,
+    pub items_cleaned: usize,
+    pub files: u64,
+    pub bytes_freed: u64,
+    pub apparent_bytes_freed: u64, // Before de-duplicating hard links
+    pub items: &'a [CleanedItem],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub omitted: &'a [OmittedItems], // Counted in the totals but not listed in `items`
+    pub by_type: &'a [TypeTotal],
+    pub errors: &'a [CleanError],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age_histogram: Option<&'a [AgeBucket]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget: Option<BudgetCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicates: Option<&'a [DuplicateGroup]>,
+}
+
+impl JsonReport<'_> {
+    /// Serialize on one line for piping, or indented with `pretty` (`--json-pretty`)
+    pub fn to_json(&self, pretty: bool) -> serde_json::Result<String> {
+        if pretty {
+            serde_json::to_string_pretty(self)
+        } else {
+            serde_json::to_string(self)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct TreeNode {
+    children: BTreeMap<String, TreeNode>,
+    size: u64,
+    files: u64,
+    is_item: bool,
+}
+
+/// Render cleaned items as a directory tree relative to `root`, with aggregate sizes
+/// per directory (similar to `du --tree`). `verbose` adds file counts.
+pub fn render_tree(items: &[CleanedItem], root: &Path, verbose: bool, color: bool) -> Vec<String> {
+    let canonical_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let mut tree = TreeNode::default();
+
+    for item in items {
+        let relative = item
+            .path
+            .strip_prefix(root)
+            .or_else(|_| item.path.strip_prefix(&canonical_root))
+            .unwrap_or(&item.path);
+
+        let mut node = &mut tree;
+        node.size += item.size;
+        node.files += item.files;
+        for component in relative.components() {
+            let name = component.as_os_str().to_string_lossy().to_string();
+            node = node.children.entry(name).or_default();
+            node.size += item.size;
+            node.files += item.files;
+        }
+        node.is_item = true;
+    }
+
+    let mut lines = vec![format_tree_label(&root.display().to_string(), &tree, verbose, color)];
+    render_children(&tree, "", verbose, color, &mut lines);
+    lines
+}
+
+fn render_children(node: &TreeNode, prefix: &str, verbose: bool, color: bool, lines: &mut Vec<String>) {
+    let count = node.children.len();
+    for (index, (name, child)) in node.children.iter().enumerate() {
+        let last = index + 1 == count;
+        let branch = if last { "└── " } else { "├── " };
+        lines.push(format!("{}{}{}", prefix, branch, format_tree_label(name, child, verbose, color)));
+
+        let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+        render_children(child, &child_prefix, verbose, color, lines);
+    }
+}
+
+fn format_tree_label(name: &str, node: &TreeNode, verbose: bool, color: bool) -> String {
+    let size = humansize::format_size(node.size, humansize::BINARY);
+    let details = if verbose {
+        format!("{}, {} files", size, node.files)
+    } else {
+        size
+    };
+
+    match (color, node.is_item) {
+        (true, true) => format!("{} ({})", name.bright_green(), details.bright_cyan()),
+        (true, false) => format!("{} ({})", name, details.bright_cyan()),
+        (false, _) => format!("{} ({})", name, details),
+    }
+}
+
+/// Format Prometheus text-exposition metrics for a run, for node_exporter's textfile collector
+pub fn format_prometheus_metrics(items: &[CleanedItem], errors: usize, dry_run: bool, now: SystemTime) -> String {
+    let mut out = String::new();
+
+    out.push_str("# HELP clearcache_bytes_freed Bytes freed by the last clearcache run.\n");
+    // Gauges: each run overwrites the file with its own values, which a counter would read as resets
+    out.push_str("# TYPE clearcache_bytes_freed gauge\n");
+    for cache_type in CacheType::all() {
+        let bytes: u64 = items.iter().filter(|i| i.cache_type == cache_type).map(|i| i.size).sum();
+        out.push_str(&format!("clearcache_bytes_freed{{cache_type=\"{}\"}} {}\n", type_label(&cache_type), bytes));
+    }
+
+    out.push_str("# HELP clearcache_items_removed Cache items removed by the last clearcache run.\n");
+    out.push_str("# TYPE clearcache_items_removed gauge\n");
+    for cache_type in CacheType::all() {
+        let count = items.iter().filter(|i| i.cache_type == cache_type).count();
+        out.push_str(&format!("clearcache_items_removed{{cache_type=\"{}\"}} {}\n", type_label(&cache_type), count));
+    }
+
+    out.push_str("# HELP clearcache_errors Errors encountered by the last clearcache run.\n");
+    out.push_str("# TYPE clearcache_errors gauge\n");
+    out.push_str(&format!("clearcache_errors {}\n", errors));
+
+    out.push_str("# HELP clearcache_dry_run Whether the last run was a dry run (nothing deleted).\n");
+    out.push_str("# TYPE clearcache_dry_run gauge\n");
+    out.push_str(&format!("clearcache_dry_run {}\n", u8::from(dry_run)));
+
+    out.push_str("# HELP clearcache_last_run_timestamp_seconds Unix time the last run finished.\n");
+    out.push_str("# TYPE clearcache_last_run_timestamp_seconds gauge\n");
+    let timestamp = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    out.push_str(&format!("clearcache_last_run_timestamp_seconds {}\n", timestamp));
+
+    out
+}
+
+/// GitHub-flavored markdown table of the items (path relative to `root`, type, size) with a
+/// total row, for CI to post as a PR comment
+pub fn format_markdown_report(items: &[CleanedItem], omitted: &[OmittedItems], root: &Path, dry_run: bool) -> String {
+    let canonical_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let count = items.len() + omitted.iter().map(|o| o.items).sum::<usize>();
+    let total = items.iter().map(|i| i.size).sum::<u64>() + omitted.iter().map(|o| o.size).sum::<u64>();
+
+    let verb = if dry_run { "would be cleaned" } else { "cleaned" };
+    let mut out = format!("### clearcache: {} items {}\n\n", count, verb);
+    out.push_str("| Path | Type | Size |\n");
+    out.push_str("| --- | --- | ---: |\n");
+    for item in items {
+        let relative = item
+            .path
+            .strip_prefix(root)
+            .or_else(|_| item.path.strip_prefix(&canonical_root))
+            .unwrap_or(&item.path);
+        out.push_str(&format!(
+            "| `{}` | {} | {} |\n",
+            relative.display().to_string().replace('|', "\\|"),
+            type_label(&item.cache_type),
+            humansize::format_size(item.size, humansize::BINARY)
+        ));
+    }
+    for group in omitted {
+        out.push_str(&format!(
+            "| _{} more items_ | {} | {} |\n",
+            group.items,
+            type_label(&group.cache_type),
+            humansize::format_size(group.size, humansize::BINARY)
+        ));
+    }
+    out.push_str(&format!("| **Total** | | **{}** |\n", humansize::format_size(total, humansize::BINARY)));
+    out
+}
+
+/// Write metrics atomically so the collector never reads a partial file
+pub fn write_metrics_file(path: &Path, metrics: &str) -> anyhow::Result<()> {
+    let tmp = path.with_extension("prom.tmp");
+    std::fs::write(&tmp, metrics)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// One line of the `--log-file` run ledger
+#[derive(Debug, Serialize)]
+pub struct LedgerEntry<'a> {
+    pub timestamp: u64, // Seconds since the Unix epoch when the run finished
+    pub roots: &'a [PathBuf],
+    pub status: CleanStatus,
+    pub dry_run: bool,
+    pub bytes_freed: u64,
+    pub items: usize,
+    pub errors: usize,
+    pub duration_ms: u64,
+}
+
+/// Append `entry` to the ledger at `path` as one JSON line. A ledger that would grow past
+/// `max_size` is first renamed to `<path>.1`, replacing the previous rotated file.
+pub fn append_ledger(path: &Path, entry: &LedgerEntry, max_size: u64) -> anyhow::Result<()> {
+    let line = format!("{}\n", serde_json::to_string(entry)?);
+    let current = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    if current > 0 && current + line.len() as u64 > max_size {
+        let mut rotated = path.as_os_str().to_owned();
+        rotated.push(".1");
+        std::fs::rename(path, rotated)?;
+    }
+
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    std::io::Write::write_all(&mut file, line.as_bytes())?;
+    Ok(())
+}
+
+fn type_label(cache_type: &CacheType) -> String {
+    format!("{:?}", cache_type).to_lowercase()
+}
+
+/// Free-space changes within this fraction of the freed total (or 1 MiB) need no explanation
+const DISK_DELTA_TOLERANCE: f64 = 0.1;
+const DISK_DELTA_MIN: u64 = 1024 * 1024;
+
+/// Summary lines comparing the bytes freed by deleted files with the change in the
+/// filesystem's free space, measured before and after the run
+pub fn format_disk_delta(freed: u64, available_before: u64, available_after: u64) -> Vec<String> {
+    let recovered = available_after as i128 - available_before as i128;
+    let recovered_text = if recovered < 0 {
+        format!("-{}", humansize::format_size(recovered.unsigned_abs() as u64, humansize::BINARY))
+    } else {
+        humansize::format_size(recovered as u64, humansize::BINARY)
+    };
+    let mut lines = vec![format!(
+        "Disk space recovered: {} (files freed: {})",
+        recovered_text,
+        humansize::format_size(freed, humansize::BINARY)
+    )];
+
+    let tolerance = ((freed as f64 * DISK_DELTA_TOLERANCE) as u64).max(DISK_DELTA_MIN);
+    if recovered.abs_diff(freed as i128) > tolerance as u128 {
+        lines.push(
+            "Note: free space changed by a different amount than the files' size. Filesystem overhead, \
+             snapshots, shared (cloned or hard-linked) blocks and other programs writing to the disk all \
+             affect how much space a deletion returns."
+                .to_string(),
+        );
+    }
+    lines
+}
+
+/// Item errors of one kind are collapsed into a single line from this many onwards
+const ERROR_GROUP_MIN: usize = 3;
+
+/// Summary lines for the errors of a run: similar item errors are grouped under their
+/// common directory, repeated messages are counted once, and at most `max_shown` lines
+/// are returned before a "... and K more" tail
+pub fn summarize_errors(errors: &[CleanError], max_shown: usize) -> Vec<String> {
+    let mut by_kind: BTreeMap<ErrorKind, Vec<&CleanError>> = BTreeMap::new();
+    let mut other: Vec<(String, usize)> = Vec::new();
+    for error in errors {
+        match error {
+            CleanError::Item { kind, .. } => by_kind.entry(*kind).or_default().push(error),
+            _ => {
+                let message = error.to_string();
+                match other.iter_mut().find(|(m, _)| *m == message) {
+                    Some((_, count)) => *count += 1,
+                    None => other.push((message, 1)),
+                }
+            }
+        }
+    }
+
+    let mut lines = Vec::new();
+    for (kind, group) in by_kind {
+        if group.len() < ERROR_GROUP_MIN {
+            lines.extend(group.iter().map(|error| error.to_string()));
+            continue;
+        }
+        let paths: Vec<&Path> = group
+            .iter()
+            .filter_map(|error| match error {
+                CleanError::Item { path, .. } => Some(path.as_path()),
+                _ => None,
+            })
+            .collect();
+        lines.push(format!(
+            "{} {} errors under {}",
+            group.len(),
+            kind.label(),
+            common_ancestor(&paths).display()
+        ));
+    }
+    lines.extend(other.into_iter().map(|(message, count)| {
+        if count > 1 {
+            format!("{} (x{})", message, count)
+        } else {
+            message
+        }
+    }));
+
+    if lines.len() > max_shown {
+        let hidden = lines.len() - max_shown;
+        lines.truncate(max_shown);
+        lines.push(format!("... and {} more", hidden));
+    }
+    lines
+}
+
+/// Deepest directory containing every path
+fn common_ancestor(paths: &[&Path]) -> PathBuf {
+    let mut ancestor = paths
+        .first()
+        .and_then(|path| path.parent())
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    for path in paths.iter().skip(1) {
+        while !path.starts_with(&ancestor) && ancestor.pop() {}
+    }
+    ancestor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn item(path: PathBuf, cache_type: CacheType, size: u64) -> CleanedItem {
+        CleanedItem {
+            path,
+            cache_type,
+            pattern_name: "test".to_string(),
+            is_library: false,
+            files: 1,
+            size,
+            apparent_size: size,
+            modified: None,
+            duration_secs: 0.0,
+            size_known: true,
+            new: None,
+        }
+    }
+
+    #[test]
+    fn test_group_by_project() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("packages/web/src")).unwrap();
+        fs::create_dir_all(root.join("packages/api")).unwrap();
+        fs::write(root.join("packages/web/package.json"), "{}").unwrap();
+        fs::write(root.join("packages/api/pyproject.toml"), "").unwrap();
+
+        let items = vec![
+            item(root.join("packages/web/.next"), CacheType::Node, 300),
+            item(root.join("packages/web/src/.turbo"), CacheType::Node, 100),
+            item(root.join("packages/api/__pycache__"), CacheType::Python, 50),
+            item(root.join("stray.log"), CacheType::General, 5),
+        ];
+
+        let groups = group_by_project(&items, root);
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[0].project, root.join("packages/web"));
+        assert_eq!((groups[0].items, groups[0].size), (2, 400));
+        assert_eq!(groups[1].project, root.join("packages/api"));
+        assert_eq!((groups[1].items, groups[1].size), (1, 50));
+        assert_eq!(groups[2].project, root.to_path_buf());
+    }
+
+    #[test]
+    fn test_restore_hints_for_cleaned_libraries() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("web/src")).unwrap();
+        fs::write(root.join("web/package.json"), "{}").unwrap();
+
+        let library = |path: PathBuf, cache_type| CleanedItem {
+            is_library: true,
+            ..item(path, cache_type, 100)
+        };
+        let items = vec![
+            library(root.join("web/node_modules"), CacheType::Node),
+            library(root.join("web/src/node_modules"), CacheType::Node),
+            library(root.join("cli/target"), CacheType::Rust),
+            item(root.join("web/.next"), CacheType::Node, 100),
+        ];
+        assert_eq!(
+            restore_hints(&items, root),
+            vec![
+                format!("run `npm install` in {}", root.join("web").display()),
+                format!("run `cargo build` in {}", root.join("cli").display()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_tree() {
+        let root = Path::new("/work/repo");
+        let items = vec![
+            item(root.join("apps/web/.next"), CacheType::Node, 2048),
+            item(root.join("apps/web/node_modules"), CacheType::Node, 1024),
+            item(root.join("libs/core/__pycache__"), CacheType::Python, 1024),
+        ];
+
+        let lines = render_tree(&items, root, false, false);
+        assert_eq!(
+            lines,
+            vec![
+                "/work/repo (4 KiB)",
+                "├── apps (3 KiB)",
+                "│   └── web (3 KiB)",
+                "│       ├── .next (2 KiB)",
+                "│       └── node_modules (1 KiB)",
+                "└── libs (1 KiB)",
+                "    └── core (1 KiB)",
+                "        └── __pycache__ (1 KiB)",
+            ]
+        );
+
+        let verbose = render_tree(&items, root, true, false);
+        assert_eq!(verbose[1], "├── apps (3 KiB, 2 files)");
+    }
+
+    #[test]
+    fn test_age_histogram_sums_to_total() {
+        let now = UNIX_EPOCH + std::time::Duration::from_secs(100 * 86_400);
+        let day = 86_400;
+        let now_secs = 100 * day;
+        let ages = [(3600, 10), (2 * day, 20), (6 * day, 30), (10 * day, 40), (90 * day, 50)];
+
+        let mut items: Vec<CleanedItem> = ages
+            .iter()
+            .map(|(age, size)| CleanedItem {
+                modified: Some(now_secs - age),
+                ..item(PathBuf::from("/work/cache"), CacheType::General, *size)
+            })
+            .collect();
+        items.push(item(PathBuf::from("/work/unknown"), CacheType::General, 60));
+
+        let buckets = age_histogram(&items, now);
+        let sizes: Vec<(&str, u64)> = buckets.iter().map(|b| (b.label, b.size)).collect();
+        assert_eq!(
+            sizes,
+            vec![("<1d", 10), ("1-7d", 50), ("7-30d", 40), (">30d", 50), ("unknown", 60)]
+        );
+
+        let total: u64 = items.iter().map(|i| i.size).sum();
+        assert_eq!(buckets.iter().map(|b| b.size).sum::<u64>(), total);
+        assert_eq!(buckets.iter().map(|b| b.items).sum::<usize>(), items.len());
+    }
+
+    #[test]
+    fn test_prometheus_metrics() {
+        let items = vec![
+            item(PathBuf::from("/work/app/node_modules"), CacheType::Node, 1000),
+            item(PathBuf::from("/work/lib/node_modules"), CacheType::Node, 500),
+            item(PathBuf::from("/work/app/target"), CacheType::Rust, 42),
+        ];
+        let metrics = format_prometheus_metrics(&items, 2, false, UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+
+        let value = |name: &str| -> f64 {
+            metrics
+                .lines()
+                .find(|line| line.starts_with(name))
+                .and_then(|line| line.rsplit(' ').next())
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(|| panic!("missing {}", name))
+        };
+        assert_eq!(value("clearcache_bytes_freed{cache_type=\"node\"}"), 1500.0);
+        assert_eq!(value("clearcache_items_removed{cache_type=\"rust\"}"), 1.0);
+        assert_eq!(value("clearcache_items_removed{cache_type=\"go\"}"), 0.0);
+        assert_eq!(value("clearcache_errors"), 2.0);
+        assert_eq!(value("clearcache_last_run_timestamp_seconds"), 1_700_000_000.0);
+        assert!(metrics.contains("# TYPE clearcache_bytes_freed gauge"));
+        assert!(!metrics.contains(" counter"));
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("clearcache.prom");
+        write_metrics_file(&path, &metrics).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), metrics);
+    }
+
+    #[test]
+    fn test_markdown_report_table() {
+        let items = vec![
+            item(PathBuf::from("/work/app/node_modules"), CacheType::Node, 1024 * 1024),
+            item(PathBuf::from("/work/app/target"), CacheType::Rust, 512 * 1024),
+        ];
+        let omitted = vec![OmittedItems {
+            cache_type: CacheType::Python,
+            items: 3,
+            files: 3,
+            size: 512 * 1024,
+        }];
+        let markdown = format_markdown_report(&items, &omitted, Path::new("/work"), true);
+        let lines: Vec<&str> = markdown.lines().collect();
+
+        assert_eq!(lines[0], "### clearcache: 5 items would be cleaned");
+        assert_eq!(lines[2], "| Path | Type | Size |");
+        assert_eq!(lines[3], "| --- | --- | ---: |");
+        assert_eq!(lines[4], "| `app/node_modules` | node | 1 MiB |");
+        assert_eq!(lines[6], "| _3 more items_ | python | 512 KiB |");
+        assert_eq!(lines.last(), Some(&"| **Total** | | **2 MiB** |"));
+    }
+
+    #[test]
+    fn test_disk_delta_explains_large_differences() {
+        const GIB: u64 = 1024 * 1024 * 1024;
+        let close = format_disk_delta(GIB, 10 * GIB, 11 * GIB + 50 * 1024 * 1024);
+        assert_eq!(close, vec!["Disk space recovered: 1.05 GiB (files freed: 1 GiB)".to_string()]);
+
+        // A snapshot holds on to the deleted blocks
+        let held = format_disk_delta(2 * GIB, 10 * GIB, 10 * GIB);
+        assert_eq!(held[0], "Disk space recovered: 0 B (files freed: 2 GiB)");
+        assert!(held[1].starts_with("Note: free space changed"));
+
+        // Another program wrote more than was freed
+        let shrunk = format_disk_delta(0, 5 * GIB, 4 * GIB);
+        assert_eq!(shrunk[0], "Disk space recovered: -1 GiB (files freed: 0 B)");
+        assert_eq!(shrunk.len(), 2);
+    }
+
+    #[test]
+    fn test_summarize_errors_groups_similar() {
+        let denied = |path: &str| CleanError::Item {
+            path: PathBuf::from(path),
+            kind: ErrorKind::PermissionDenied,
+            message: "Permission denied (os error 13)".to_string(),
+        };
+        let mut errors: Vec<CleanError> = (0..12).map(|i| denied(&format!("/foo/app{}/node_modules", i))).collect();
+        errors.push(denied("/foo/bar/node_modules"));
+        errors.push(CleanError::Item {
+            path: PathBuf::from("/srv/.cache"),
+            kind: ErrorKind::Protected,
+            message: "refusing to delete protected path".to_string(),
+        });
+        errors.push(CleanError::Docker("Docker is not available".to_string()));
+        errors.push(CleanError::Docker("Docker is not available".to_string()));
+
+        let lines = summarize_errors(&errors, 10);
+        assert_eq!(lines, vec![
+            "13 permission-denied errors under /foo".to_string(),
+            "Failed to clean /srv/.cache: refusing to delete protected path".to_string(),
+            "Docker cleaning failed: Docker is not available (x2)".to_string(),
+        ]);
+
+        let lines = summarize_errors(&errors, 1);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1], "... and 2 more");
+    }
+
+    #[test]
+    fn test_ledger_rotates_past_max_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let ledger = temp_dir.path().join("runs.log");
+        let rotated = temp_dir.path().join("runs.log.1");
+        let roots = [PathBuf::from("/home/me/code")];
+        let entry = |timestamp| LedgerEntry {
+            timestamp,
+            roots: &roots,
+            status: CleanStatus::Success,
+            dry_run: false,
+            bytes_freed: 1 << 20,
+            items: 3,
+            errors: 0,
+            duration_ms: 250,
+        };
+        let line_len = serde_json::to_string(&entry(1)).unwrap().len() as u64 + 1;
+
+        // Room for three lines: the fourth run rotates, the seventh rotates again
+        for timestamp in 1..=3 {
+            append_ledger(&ledger, &entry(timestamp), line_len * 3).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&ledger).unwrap().lines().count(), 3);
+        assert!(!rotated.exists());
+
+        for timestamp in 4..=7 {
+            append_ledger(&ledger, &entry(timestamp), line_len * 3).unwrap();
+        }
+        let current = fs::read_to_string(&ledger).unwrap();
+        let previous = fs::read_to_string(&rotated).unwrap();
+        assert_eq!(current.lines().count(), 1);
+        assert!(current.starts_with(r#"{"timestamp":7,"roots":["/home/me/code"],"status":"success""#));
+        assert_eq!(previous.lines().count(), 3);
+        assert!(previous.starts_with(r#"{"timestamp":4,"#));
+    }
+
+    #[test]
+    fn test_type_breakdown_uses_canonical_order() {
+        let mut items = vec![
+            item(PathBuf::from("/work/build"), CacheType::General, 10),
+            item(PathBuf::from("/work/app/__pycache__"), CacheType::Python, 20),
+            item(PathBuf::from("/work/web/.next"), CacheType::Node, 30),
+            item(PathBuf::from("/work/lib/__pycache__"), CacheType::Python, 40),
+        ];
+        let omitted = vec![OmittedItems {
+            cache_type: CacheType::Rust,
+            items: 2,
+            files: 5,
+            size: 50,
+        }];
+
+        let expected = [(CacheType::Node, 1, 30), (CacheType::Rust, 2, 50), (CacheType::Python, 2, 60), (CacheType::General, 1, 10)];
+        for _ in 0..2 {
+            let breakdown: Vec<(CacheType, usize, u64)> = type_breakdown(&items, &omitted)
+                .into_iter()
+                .map(|total| (total.cache_type, total.items, total.size))
+                .collect();
+            assert_eq!(breakdown, expected);
+            items.reverse();
+        }
+    }
+
+    #[test]
+    fn test_type_shares_sorted_by_size_with_percentages() {
+        let total = |cache_type, size| TypeTotal {
+            cache_type,
+            items: 1,
+            files: 1,
+            size,
+        };
+        let breakdown = vec![
+            total(CacheType::Node, 800),
+            total(CacheType::Rust, 50),
+            total(CacheType::Python, 100),
+            total(CacheType::General, 50),
+        ];
+
+        let shares: Vec<(CacheType, String)> = type_shares(&breakdown)
+            .into_iter()
+            .map(|(total, percent)| (total.cache_type.clone(), format!("{:.1}%", percent)))
+            .collect();
+        assert_eq!(shares, vec![
+            (CacheType::Node, "80.0%".to_string()),
+            (CacheType::Python, "10.0%".to_string()),
+            (CacheType::Rust, "5.0%".to_string()),
+            (CacheType::General, "5.0%".to_string()),
+        ]);
+
+        let empty = vec![total(CacheType::Node, 0)];
+        assert_eq!(type_shares(&empty)[0].1, 0.0);
+    }
+
+    #[test]
+    fn test_json_report_has_schema_version_and_pretty_differs_only_in_whitespace() {
+        let items = vec![item(PathBuf::from("/code/app/__pycache__"), CacheType::Python, 4096)];
+        let report = JsonReport {
+            schema_version: SCHEMA_VERSION,
+            root: Path::new("/code"),
+            roots: &[PathBuf::from("/code")],
+            dry_run: true,
+            status: CleanStatus::Success,
+            items_cleaned: 1,
+            files: 1,
+            bytes_freed: 4096,
+            apparent_bytes_freed: 4096,
+            items: &items,
+            omitted: &[],
+            by_type: &[],
+            errors: &[],
+            age_histogram: None,
+            budget: None,
+            duplicates: None,
+        };
+
+        let compact = report.to_json(false).unwrap();
+        let pretty = report.to_json(true).unwrap();
+        assert!(compact.starts_with(r#"{"schema_version":1,"#));
+        assert_eq!(compact.lines().count(), 1);
+        assert!(pretty.lines().count() > 1);
+        assert_eq!(pretty.split_whitespace().collect::<String>(), compact);
+    }
+
+    #[test]
+    fn test_duplicate_groups_combine_same_named_caches() {
+        let items = vec![
+            item(PathBuf::from("/mono/apps/web/node_modules"), CacheType::Node, 300),
+            item(PathBuf::from("/mono/apps/api/node_modules"), CacheType::Node, 200),
+            item(PathBuf::from("/mono/packages/ui/node_modules"), CacheType::Node, 100),
+            item(PathBuf::from("/mono/apps/web/.next"), CacheType::Node, 5000),
+            item(PathBuf::from("/mono/tools/__pycache__"), CacheType::Python, 10),
+            item(PathBuf::from("/mono/scripts/__pycache__"), CacheType::Python, 10),
+        ];
+        let groups = duplicate_groups(&items);
+        assert_eq!(groups.iter().map(|group| (group.name.as_str(), group.items, group.size)).collect::<Vec<_>>(), vec![
+            ("node_modules", 3, 600),
+            ("__pycache__", 2, 20),
+        ]);
+    }
+}
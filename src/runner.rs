@@ -0,0 +1,36 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Captured result of an external command
+#[derive(Debug, Clone, Default)]
+pub struct CommandOutput {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Runs external programs. Injectable so callers can be tested without the real tools.
+pub trait CommandRunner: Send + Sync {
+    /// Run `program` with `args` in `working_dir`. Errors only if the program could not be started.
+    fn run(&self, program: &str, args: &[&str], working_dir: &Path) -> Result<CommandOutput>;
+}
+
+/// Runs commands as child processes
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(&self, program: &str, args: &[&str], working_dir: &Path) -> Result<CommandOutput> {
+        let output = Command::new(program)
+            .args(args)
+            .current_dir(working_dir)
+            .stdin(Stdio::null())
+            .output()
+            .with_context(|| format!("Failed to run '{}'", program))?;
+        Ok(CommandOutput {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+}
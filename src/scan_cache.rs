@@ -0,0 +1,241 @@
+use crate::utils::mtime_nanos;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+/// On-disk record of subtrees that contained no cache items on a previous run.
+/// A subtree is skipped while the mtimes of all directories in it are unchanged and the
+/// record is younger than the TTL. Checking them only takes a `stat` per directory; a
+/// directory created anywhere below changes its parent's mtime.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ScanCache {
+    roots: HashMap<String, RootRecord>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RootRecord {
+    fingerprint: String,
+    recorded_at: u64,
+    clean_children: HashMap<String, DirMtimes>,
+}
+
+/// Mtimes of a subtree's directories, keyed by path relative to the subtree ("" is its top)
+type DirMtimes = HashMap<String, u64>;
+
+impl ScanCache {
+    /// Load the cache file, starting empty if it is missing or unreadable
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Find subtrees of `root` recorded as clean whose mtime is unchanged and within `ttl`
+    pub fn fresh_subtrees(&self, root: &Path, fingerprint: &str, ttl: Duration) -> HashSet<PathBuf> {
+        let mut fresh = HashSet::new();
+        let Some(record) = self.roots.get(&cache_key(root)) else {
+            return fresh;
+        };
+        if record.fingerprint != fingerprint || now_secs().saturating_sub(record.recorded_at) > ttl.as_secs() {
+            return fresh;
+        }
+
+        for (name, mtimes) in &record.clean_children {
+            let child = root.join(name);
+            if is_unchanged(&child, mtimes) {
+                fresh.insert(child);
+            }
+        }
+
+        fresh
+    }
+
+    /// Record which immediate subdirectories of `root` have no remaining cache items. Subtrees
+    /// still unchanged since the previous record keep it rather than being walked again.
+    pub fn record(&mut self, root: &Path, fingerprint: &str, remaining_items: &[PathBuf]) {
+        let previous = self
+            .roots
+            .remove(&cache_key(root))
+            .filter(|record| record.fingerprint == fingerprint)
+            .map(|record| record.clean_children)
+            .unwrap_or_default();
+        let mut clean_children = HashMap::new();
+        let canonical_root = PathBuf::from(cache_key(root));
+        if let Ok(entries) = std::fs::read_dir(root) {
+            for entry in entries.flatten() {
+                let child = entry.path();
+                if !entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                    continue;
+                }
+                // Items may be reported relative to the given root or canonicalized
+                let canonical_child = canonical_root.join(entry.file_name());
+                if remaining_items
+                    .iter()
+                    .any(|item| item.starts_with(&child) || item.starts_with(&canonical_child))
+                {
+                    continue;
+                }
+                let name = entry.file_name().to_string_lossy().to_string();
+                let mtimes = match previous.get(&name) {
+                    Some(mtimes) if is_unchanged(&child, mtimes) => mtimes.clone(),
+                    _ => dir_mtimes(&child),
+                };
+                clean_children.insert(name, mtimes);
+            }
+        }
+
+        self.roots.insert(
+            cache_key(root),
+            RootRecord {
+                fingerprint: fingerprint.to_string(),
+                recorded_at: now_secs(),
+                clean_children,
+            },
+        );
+    }
+}
+
+/// Mtimes of `dir` and every directory below it, without following symlinks. `.git`
+/// directories are left out; they change on every commit and never hold cache items.
+fn dir_mtimes(dir: &Path) -> DirMtimes {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| entry.file_type().is_dir() && entry.file_name() != ".git")
+        .flatten()
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(dir).ok()?.to_string_lossy().to_string();
+            Some((relative, mtime_nanos(entry.path())?))
+        })
+        .collect()
+}
+
+fn is_unchanged(dir: &Path, mtimes: &DirMtimes) -> bool {
+    !mtimes.is_empty()
+        && mtimes
+            .iter()
+            .all(|(relative, mtime)| mtime_nanos(&dir.join(relative)) == Some(*mtime))
+}
+
+fn cache_key(root: &Path) -> String {
+    root.canonicalize()
+        .unwrap_or_else(|_| root.to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache_types::{CachePattern, CacheType};
+    use crate::traversal::{CacheTraversal, TraversalConfig};
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn pycache_patterns() -> Vec<(CacheType, CachePattern)> {
+        vec![(CacheType::Python, CachePattern {
+            name: "python_cache".to_string(),
+            patterns: vec!["__pycache__".to_string()],
+            description: "Python cache".to_string(),
+            is_directory: true,
+            recursive_safe: true,
+            is_library: false,
+            truncatable: false,
+        })]
+    }
+
+    #[test]
+    fn test_unchanged_subtree_is_skipped() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("clean/nested")).unwrap();
+        fs::create_dir_all(root.join("dirty/__pycache__")).unwrap();
+
+        let mut cache = ScanCache::default();
+        cache.record(root, "python", &[root.join("dirty/__pycache__")]);
+
+        let fresh = cache.fresh_subtrees(root, "python", Duration::from_secs(3600));
+        assert!(fresh.contains(&root.join("clean")));
+        assert!(!fresh.contains(&root.join("dirty")));
+
+        let config = TraversalConfig {
+            skip_paths: fresh,
+            ..TraversalConfig::default()
+        };
+        let results = CacheTraversal::new(config, pycache_patterns()).find_cache_items(root).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].path.ends_with("dirty/__pycache__"));
+
+        // A different pattern set or an expired TTL invalidates the record
+        assert!(cache.fresh_subtrees(root, "node", Duration::from_secs(3600)).is_empty());
+        cache.roots.values_mut().for_each(|r| r.recorded_at -= 7200);
+        assert!(cache.fresh_subtrees(root, "python", Duration::from_secs(3600)).is_empty());
+    }
+
+    #[test]
+    fn test_mtime_change_invalidates_subtree() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("project")).unwrap();
+
+        let mut cache = ScanCache::default();
+        cache.record(root, "python", &[]);
+
+        std::thread::sleep(Duration::from_millis(20));
+        fs::create_dir_all(root.join("project/__pycache__")).unwrap();
+
+        let fresh = cache.fresh_subtrees(root, "python", Duration::from_secs(3600));
+        assert!(!fresh.contains(&root.join("project")));
+    }
+
+    #[test]
+    fn test_nested_cache_created_after_scan_is_found() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("web/packages/app/src")).unwrap();
+        fs::create_dir_all(root.join("api/src")).unwrap();
+
+        let mut cache = ScanCache::default();
+        cache.record(root, "python", &[]);
+        assert_eq!(cache.fresh_subtrees(root, "python", Duration::from_secs(3600)).len(), 2);
+
+        // Only the mtime of `web/packages/app` changes, not that of `web`
+        std::thread::sleep(Duration::from_millis(20));
+        fs::create_dir_all(root.join("web/packages/app/__pycache__")).unwrap();
+
+        let fresh = cache.fresh_subtrees(root, "python", Duration::from_secs(3600));
+        assert!(!fresh.contains(&root.join("web")));
+        assert!(fresh.contains(&root.join("api")));
+
+        let config = TraversalConfig {
+            skip_paths: fresh.clone(),
+            ..TraversalConfig::default()
+        };
+        let results = CacheTraversal::new(config, pycache_patterns()).find_cache_items(root).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].path.ends_with("web/packages/app/__pycache__"));
+
+        // The unchanged subtree keeps its record; the changed one is recorded afresh
+        cache.record(root, "python", &[root.join("web/packages/app/__pycache__")]);
+        let fresh = cache.fresh_subtrees(root, "python", Duration::from_secs(3600));
+        assert_eq!(fresh, HashSet::from([root.join("api")]));
+    }
+}
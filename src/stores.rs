@@ -0,0 +1,365 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+/// Packages that projects under a scan root depend on
+#[derive(Debug, Default)]
+pub struct StoreReferences {
+    pub pnpm: HashSet<String>,  // `name@version`, from `node_modules/.pnpm`
+    pub cargo: HashSet<String>, // `name-version`, from registry packages in `Cargo.lock`
+}
+
+/// Which store entries to prune. An entry goes only if it fails every check that is set.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StoreSelection<'a> {
+    pub referenced: Option<&'a HashSet<String>>, // Keep what a project references (--dedup-stores)
+    pub used_since: Option<SystemTime>, // Keep what was read or written after this (--store-max-age)
+}
+
+impl StoreSelection<'_> {
+    /// Whether the entry `id`, last used at `used`, may be removed
+    fn prunes(&self, id: Option<&str>, used: Option<SystemTime>) -> bool {
+        let unreferenced = self
+            .referenced
+            .is_none_or(|referenced| id.is_some_and(|id| !referenced.contains(id)));
+        unreferenced && self.used_since.is_none_or(|cutoff| used.is_some_and(|used| used < cutoff))
+    }
+}
+
+/// When an entry was last used: its access time, or its modification time if later. Under `relatime`
+/// (the Linux default) the access time moves at most once a day, which is precise enough for an age in
+/// days. Stores on `noatime` mounts are never pruned by age; see `records_access_time`.
+fn last_used(path: &Path) -> Option<SystemTime> {
+    let metadata = std::fs::symlink_metadata(path).ok()?;
+    let modified = metadata.modified().ok();
+    metadata.accessed().ok().max(modified)
+}
+
+#[derive(Deserialize)]
+struct CargoLock {
+    #[serde(default)]
+    package: Vec<LockedPackage>,
+}
+
+#[derive(Deserialize)]
+struct LockedPackage {
+    name: String,
+    version: String,
+    source: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PnpmIndex {
+    name: Option<String>,
+    version: Option<String>,
+    #[serde(default)]
+    files: HashMap<String, PnpmIndexFile>,
+}
+
+#[derive(Deserialize)]
+struct PnpmIndexFile {
+    integrity: String,
+}
+
+/// Cargo's home directory (`$CARGO_HOME`, or `~/.cargo`)
+pub fn cargo_home() -> Option<PathBuf> {
+    std::env::var_os("CARGO_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| crate::utils::home_dir().map(|home| home.join(".cargo")))
+}
+
+/// Collect the packages referenced by every project under `root`
+pub fn find_references(root: &Path) -> StoreReferences {
+    let mut references = StoreReferences::default();
+    let walker = WalkDir::new(root).into_iter().filter_entry(|entry| {
+        // Dependency and build trees hold no project manifests of their own
+        !entry.file_type().is_dir()
+            || !matches!(
+                entry.file_name().to_str(),
+                Some("node_modules" | "target" | ".git" | ".pnpm-store")
+            )
+    });
+
+    for entry in walker.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if entry.file_type().is_dir() {
+            if let Ok(packages) = std::fs::read_dir(path.join("node_modules/.pnpm")) {
+                references.pnpm.extend(
+                    packages
+                        .flatten()
+                        .filter_map(|package| parse_pnpm_dir_name(&package.file_name().to_string_lossy())),
+                );
+            }
+        } else if entry.file_name() == "Cargo.lock" {
+            let Ok(lock) = std::fs::read_to_string(path).map_err(anyhow::Error::from).and_then(|content| {
+                toml::from_str::<CargoLock>(&content).map_err(anyhow::Error::from)
+            }) else {
+                continue;
+            };
+            references.cargo.extend(
+                lock.package
+                    .into_iter()
+                    .filter(|p| p.source.as_deref().is_some_and(|s| s.starts_with("registry+") || s.starts_with("sparse+")))
+                    .map(|p| format!("{}-{}", p.name, p.version)),
+            );
+        }
+    }
+    references
+}
+
+/// `@scope+name@1.0.0_peer@2.0.0` or `name@1.0.0(peer@2.0.0)` to `@scope/name@1.0.0`
+fn parse_pnpm_dir_name(dir_name: &str) -> Option<String> {
+    let at = dir_name.get(1..)?.find('@')? + 1;
+    let name = dir_name[..at].replace('+', "/");
+    let version = dir_name[at + 1..].split(['_', '(']).next()?;
+    (!version.is_empty()).then(|| format!("{}@{}", name, version))
+}
+
+/// Entries of a pnpm store (`.pnpm-store/v*/files`) that `selection` prunes. A package's name and
+/// version come from its `-index.json`, and its last use is that of the index, which pnpm reads on
+/// every install.
+///
+/// A package's `-index.json` goes along with any content file that no kept package also lists;
+/// unreadable index files are left alone. Nothing is deleted here: the caller removes the entries.
+pub fn pnpm_store_entries(store: &Path, selection: StoreSelection) -> Result<Vec<PathBuf>> {
+    let mut keep = HashSet::new();
+    let mut orphans = Vec::new();
+
+    for files_dir in std::fs::read_dir(store)?.flatten().map(|v| v.path().join("files")).filter(|p| p.is_dir()) {
+        for index_path in WalkDir::new(&files_dir)
+            .max_depth(2)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .map(|e| e.into_path())
+            .filter(|p| p.to_string_lossy().ends_with("-index.json"))
+        {
+            // Reading the index updates its access time, so take it first
+            let used = last_used(&index_path);
+            let Some(index) = std::fs::read_to_string(&index_path)
+                .ok()
+                .and_then(|content| serde_json::from_str::<PnpmIndex>(&content).ok())
+            else {
+                continue;
+            };
+            let content: Vec<PathBuf> = index
+                .files
+                .values()
+                .filter_map(|file| pnpm_content_path(&files_dir, &file.integrity))
+                .collect();
+            let id = index.name.zip(index.version).map(|(name, version)| format!("{}@{}", name, version));
+            if selection.prunes(id.as_deref(), used) {
+                orphans.push((index_path, content));
+            } else {
+                keep.extend(content);
+            }
+        }
+    }
+
+    // Orphans may share content files; each is listed once
+    let mut listed = HashSet::new();
+    let mut entries = Vec::new();
+    for (index_path, content) in orphans {
+        for path in content.into_iter().filter(|p| !keep.contains(p)) {
+            // Executable files carry an `-exec` suffix in the store
+            let exec = PathBuf::from(format!("{}-exec", path.display()));
+            for candidate in [path, exec] {
+                if candidate.symlink_metadata().is_ok() && listed.insert(candidate.clone()) {
+                    entries.push(candidate);
+                }
+            }
+        }
+        entries.push(index_path);
+    }
+    Ok(entries)
+}
+
+/// Entries of a cargo registry (`$CARGO_HOME/registry`) that `selection` prunes, matched by
+/// `name-version` against `Cargo.lock` references. Both the downloaded `.crate` archives and their
+/// extracted sources are returned, each judged by its own last use.
+pub fn cargo_registry_entries(registry: &Path, selection: StoreSelection) -> Vec<PathBuf> {
+    let mut entries = Vec::new();
+    for area in ["cache", "src"] {
+        let Ok(indexes) = std::fs::read_dir(registry.join(area)) else {
+            continue;
+        };
+        for entry in indexes.flatten().filter_map(|index| std::fs::read_dir(index.path()).ok()).flatten().flatten() {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let id = file_name.strip_suffix(".crate").unwrap_or(&file_name);
+            if selection.prunes(Some(id), last_used(&entry.path())) {
+                entries.push(entry.path());
+            }
+        }
+    }
+    entries.sort();
+    entries
+}
+
+/// Content files are stored by the hex digest of their integrity hash: `files/ab/cdef...`
+fn pnpm_content_path(files_dir: &Path, integrity: &str) -> Option<PathBuf> {
+    let (_, digest) = integrity.split_once('-')?;
+    let hex: String = decode_base64(digest)?.iter().map(|b| format!("{:02x}", b)).collect();
+    (hex.len() > 2).then(|| files_dir.join(&hex[..2]).join(&hex[2..]))
+}
+
+fn decode_base64(input: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(input.len() * 3 / 4);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in input.trim_end_matches('=').bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn referenced(packages: &HashSet<String>) -> StoreSelection<'_> {
+        StoreSelection {
+            referenced: Some(packages),
+            used_since: None,
+        }
+    }
+
+    #[test]
+    fn test_prune_stores_removes_only_orphans() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("work");
+        fs::create_dir_all(root.join("web/node_modules/.pnpm/left-pad@1.3.0")).unwrap();
+        fs::create_dir_all(root.join("web/node_modules/.pnpm/@types+node@20.1.0_typescript@5.0.0")).unwrap();
+        fs::create_dir_all(root.join("cli")).unwrap();
+        fs::write(
+            root.join("cli/Cargo.lock"),
+            "version = 3\n\n[[package]]\nname = \"cli\"\nversion = \"0.1.0\"\n\n\
+             [[package]]\nname = \"serde\"\nversion = \"1.0.200\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+        )
+        .unwrap();
+
+        let references = find_references(&root);
+        assert!(references.pnpm.contains("left-pad@1.3.0"));
+        assert!(references.pnpm.contains("@types/node@20.1.0"));
+        assert_eq!(references.cargo, HashSet::from(["serde-1.0.200".to_string()]));
+
+        // pnpm store: left-pad is referenced; is-odd is not, and shares one file with left-pad
+        let store = root.join("web/.pnpm-store");
+        let files = store.join("v3/files");
+        let shared = "sha512-AAAA";
+        let index = |name: &str, own: &str| {
+            format!(
+                r#"{{"name":"{}","version":"{}","files":{{"package.json":{{"integrity":"{}"}},"LICENSE":{{"integrity":"{}"}}}}}}"#,
+                name,
+                if name == "left-pad" { "1.3.0" } else { "3.0.0" },
+                own,
+                shared
+            )
+        };
+        for dir in ["00", "ff", "11", "aa", "bb"] {
+            fs::create_dir_all(files.join(dir)).unwrap();
+        }
+        // Digests 000000, ffff and 1111 in base64
+        for file in ["00/0000", "ff/ff", "11/11"] {
+            fs::write(files.join(file), "content").unwrap();
+        }
+        fs::write(files.join("aa/left-pad-index.json"), index("left-pad", "sha512-//8=")).unwrap();
+        fs::write(files.join("bb/is-odd-index.json"), index("is-odd", "sha512-ERE=")).unwrap();
+
+        let entries = pnpm_store_entries(&store, referenced(&references.pnpm)).unwrap();
+        assert_eq!(entries, vec![files.join("11/11"), files.join("bb/is-odd-index.json")]);
+
+        // Cargo registry: serde is referenced, rand is not
+        let registry = temp_dir.path().join("cargo/registry");
+        let index_dir = "index.crates.io-6f17d22bba15001f";
+        for name in ["serde-1.0.200", "rand-0.8.5"] {
+            fs::create_dir_all(registry.join("src").join(index_dir).join(name)).unwrap();
+            fs::create_dir_all(registry.join("cache").join(index_dir)).unwrap();
+            fs::write(registry.join("cache").join(index_dir).join(format!("{}.crate", name)), "crate").unwrap();
+        }
+
+        assert_eq!(cargo_registry_entries(&registry, referenced(&references.cargo)), vec![
+            registry.join("cache").join(index_dir).join("rand-0.8.5.crate"),
+            registry.join("src").join(index_dir).join("rand-0.8.5"),
+        ]);
+        // Selecting deletes nothing
+        assert!(registry.join("src").join(index_dir).join("rand-0.8.5").exists());
+    }
+
+    #[test]
+    fn test_prune_stores_by_last_use_removes_only_stale_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let now = SystemTime::now();
+        let month_ago = now - Duration::from_secs(30 * 86400);
+        let age = |path: &Path, used: SystemTime| {
+            let times = fs::FileTimes::new().set_accessed(used).set_modified(used);
+            fs::File::open(path).unwrap().set_times(times).unwrap();
+        };
+        let stale = StoreSelection {
+            referenced: None,
+            used_since: Some(now - Duration::from_secs(7 * 86400)),
+        };
+
+        // pnpm store: old-pkg was last installed a month ago, new-pkg today; both share LICENSE
+        let store = temp_dir.path().join(".pnpm-store");
+        let files = store.join("v3/files");
+        for dir in ["00", "ff", "11", "aa", "bb"] {
+            fs::create_dir_all(files.join(dir)).unwrap();
+        }
+        for file in ["00/0000", "ff/ff", "11/11"] {
+            fs::write(files.join(file), "content").unwrap();
+        }
+        let index = |name: &str, own: &str| {
+            format!(
+                r#"{{"name":"{}","version":"1.0.0","files":{{"index.js":{{"integrity":"{}"}},"LICENSE":{{"integrity":"sha512-AAAA"}}}}}}"#,
+                name, own
+            )
+        };
+        fs::write(files.join("aa/new-pkg-index.json"), index("new-pkg", "sha512-//8=")).unwrap();
+        fs::write(files.join("bb/old-pkg-index.json"), index("old-pkg", "sha512-ERE=")).unwrap();
+        age(&files.join("bb/old-pkg-index.json"), month_ago);
+
+        let entries = pnpm_store_entries(&store, stale).unwrap();
+        assert_eq!(entries, vec![files.join("11/11"), files.join("bb/old-pkg-index.json")]);
+
+        // Cargo registry: each archive and source directory is judged by its own last use
+        let registry = temp_dir.path().join("registry");
+        let index_dir = "index.crates.io-6f17d22bba15001f";
+        for name in ["serde-1.0.200", "rand-0.8.5"] {
+            fs::create_dir_all(registry.join("src").join(index_dir).join(name)).unwrap();
+            fs::create_dir_all(registry.join("cache").join(index_dir)).unwrap();
+            fs::write(registry.join("cache").join(index_dir).join(format!("{}.crate", name)), "crate").unwrap();
+        }
+        age(&registry.join("cache").join(index_dir).join("rand-0.8.5.crate"), month_ago);
+        age(&registry.join("src").join(index_dir).join("rand-0.8.5"), month_ago);
+        age(&registry.join("src").join(index_dir).join("serde-1.0.200"), month_ago);
+
+        // A referenced crate stays however old it is, when both checks are set
+        let references = HashSet::from(["serde-1.0.200".to_string()]);
+        let both = StoreSelection {
+            referenced: Some(&references),
+            ..stale
+        };
+        assert_eq!(cargo_registry_entries(&registry, both), vec![
+            registry.join("cache").join(index_dir).join("rand-0.8.5.crate"),
+            registry.join("src").join(index_dir).join("rand-0.8.5"),
+        ]);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::cache_types::CacheType;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// An explicit cache item to clean, typically taken from a previous `--format json` run
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Target {
+    pub path: PathBuf,
+    pub cache_type: CacheType,
+}
+
+/// Load targets from a JSON Lines file
+pub fn load_targets(path: &Path) -> Result<Vec<Target>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read targets file {}", path.display()))?;
+    parse_targets(&content)
+}
+
+/// Item paths from an earlier run's report, for `--baseline` to tell which matches are new
+#[derive(Debug, Default)]
+pub struct Baseline {
+    paths: HashSet<PathBuf>,
+}
+
+impl Baseline {
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(Self::from_targets(&load_targets(path)?))
+    }
+
+    /// Each path is kept as written and canonicalized, since items deleted by that run can no
+    /// longer be resolved and items found now may be reported either way
+    pub fn from_targets(targets: &[Target]) -> Self {
+        let mut paths = HashSet::new();
+        for target in targets {
+            if let Ok(canonical) = target.path.canonicalize() {
+                paths.insert(canonical);
+            }
+            paths.insert(target.path.clone());
+        }
+        Self { paths }
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.paths.contains(path) || path.canonicalize().is_ok_and(|canonical| self.paths.contains(&canonical))
+    }
+}
+
+/// Parse a sequence of JSON records, normally one per line. Each is either a `{path, cache_type}`
+/// record or a whole `--format json` report, whose items are expanded. Blank lines are skipped.
+pub fn parse_targets(content: &str) -> Result<Vec<Target>> {
+    let mut targets = Vec::new();
+    // Records are usually one per line, but a `--json-pretty` report spans many
+    let mut records = serde_json::Deserializer::from_str(content).into_iter::<serde_json::Value>();
+    loop {
+        let rest = &content[records.byte_offset()..];
+        let start = content.len() - rest.trim_start().len();
+        let line = content[..start].matches('\n').count() + 1;
+        let invalid = || format!("Invalid target record on line {}", line);
+        let Some(value) = records.next() else {
+            break;
+        };
+
+        let mut value = value.with_context(invalid)?;
+        match value.get_mut("items").map(serde_json::Value::take) {
+            Some(items) => targets.extend(serde_json::from_value::<Vec<Target>>(items).with_context(invalid)?),
+            None => targets.push(serde_json::from_value(value).with_context(invalid)?),
+        }
+    }
+    Ok(targets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_targets_records_and_reports() {
+        let content = r#"{"path":"/a/node_modules","cache_type":"Node"}
+
+{"root":"/b","items":[{"path":"/b/__pycache__","cache_type":"Python","size":3}]}"#;
+        let targets = parse_targets(content).unwrap();
+        assert_eq!(targets, vec![
+            Target { path: PathBuf::from("/a/node_modules"), cache_type: CacheType::Node },
+            Target { path: PathBuf::from("/b/__pycache__"), cache_type: CacheType::Python },
+        ]);
+
+        let err = parse_targets("{\"path\":\"/a\",\"cache_type\":\"Cobol\"}").unwrap_err();
+        assert!(err.to_string().contains("line 1"));
+
+        // An indented report, as saved with --json-pretty, followed by a bad record
+        let pretty = "{\n  \"root\": \"/b\",\n  \"items\": [\n    {\"path\": \"/b/target\", \"cache_type\": \"Rust\"}\n  ]\n}\n\n{\"path\":";
+        let err = parse_targets(pretty).unwrap_err();
+        assert!(err.to_string().contains("line 8"), "{}", err);
+        assert_eq!(parse_targets(pretty.trim_end_matches("{\"path\":")).unwrap(), vec![Target {
+            path: PathBuf::from("/b/target"),
+            cache_type: CacheType::Rust,
+        }]);
+    }
+}
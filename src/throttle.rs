@@ -0,0 +1,124 @@
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Counting semaphore capping how many filesystem deletions run at once,
+/// independent of the number of worker threads
+#[derive(Debug)]
+pub struct IoLimiter {
+    limit: usize,
+    active: Mutex<usize>,
+    available: Condvar,
+}
+
+/// Held while a deletion is in flight; releases its slot on drop
+pub struct IoPermit<'a> {
+    limiter: &'a IoLimiter,
+}
+
+impl IoLimiter {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit: limit.max(1),
+            active: Mutex::new(0),
+            available: Condvar::new(),
+        }
+    }
+
+    /// Block until a slot is free
+    pub fn acquire(&self) -> IoPermit<'_> {
+        let mut active = self.active.lock().unwrap();
+        while *active >= self.limit {
+            active = self.available.wait(active).unwrap();
+        }
+        *active += 1;
+        IoPermit { limiter: self }
+    }
+}
+
+impl Drop for IoPermit<'_> {
+    fn drop(&mut self) {
+        let mut active = self.limiter.active.lock().unwrap();
+        *active -= 1;
+        self.limiter.available.notify_one();
+    }
+}
+
+/// Token bucket capping deletion throughput at a number of bytes per second.
+/// Up to one second's worth may be deleted in a burst; larger items run into
+/// debt that later deletions wait out, so the average rate still holds.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bytes_per_second: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64, // Negative while in debt
+    refilled: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_second: u64) -> Self {
+        let bytes_per_second = bytes_per_second.max(1) as f64;
+        Self {
+            bytes_per_second,
+            bucket: Mutex::new(Bucket {
+                tokens: bytes_per_second,
+                refilled: Instant::now(),
+            }),
+        }
+    }
+
+    /// Block until deleting `bytes` more keeps within the rate
+    pub fn consume(&self, bytes: u64) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = Instant::now();
+            let refill = now.duration_since(bucket.refilled).as_secs_f64() * self.bytes_per_second;
+            bucket.tokens = (bucket.tokens + refill).min(self.bytes_per_second) - bytes as f64;
+            bucket.refilled = now;
+            // Reserved under the lock, waited out after it, so concurrent callers queue behind each other
+            (bucket.tokens < 0.0).then(|| Duration::from_secs_f64(-bucket.tokens / self.bytes_per_second))
+        };
+        if let Some(wait) = wait {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_io_limiter_caps_concurrency() {
+        let limiter = Arc::new(IoLimiter::new(2));
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let limiter = Arc::clone(&limiter);
+                let running = Arc::clone(&running);
+                let max_running = Arc::clone(&max_running);
+                std::thread::spawn(move || {
+                    let _permit = limiter.acquire();
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(10));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+    }
+}
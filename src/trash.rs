@@ -0,0 +1,380 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+const MANIFEST: &str = "manifest.json";
+const STAGING_PREFIX: &str = ".clearcache-staging-";
+
+/// Default trash location (`~/.local/state/clearcache/trash`)
+pub fn default_trash_dir() -> Option<PathBuf> {
+    crate::utils::state_dir().map(|dir| dir.join("trash"))
+}
+
+/// An item moved to the trash, and where it came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub original: PathBuf,
+    pub stored: PathBuf,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    entries: Vec<TrashEntry>,
+}
+
+/// Trash directory for a single run. Items are moved into `<trash>/<run id>/` and
+/// recorded in that directory's manifest so the run can be undone.
+pub struct TrashRun {
+    run_dir: PathBuf,
+    next_index: AtomicUsize,
+    manifest: Mutex<Manifest>,
+}
+
+impl TrashRun {
+    pub fn new(trash_dir: &Path) -> Self {
+        // Zero-padded timestamps sort lexically, so the latest run is the largest name
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        Self {
+            run_dir: trash_dir.join(format!("{:020}", nanos)),
+            next_index: AtomicUsize::new(0),
+            manifest: Mutex::new(Manifest::default()),
+        }
+    }
+
+    /// Move `path` into the trash and record it in the manifest
+    pub fn store(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.run_dir)?;
+        let index = self.next_index.fetch_add(1, Ordering::Relaxed);
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let stored = self.run_dir.join(format!("{}-{}", index, name));
+
+        move_path(path, &stored)?;
+
+        // Save after every item so a crash mid-run still leaves a usable manifest
+        let mut manifest = self.manifest.lock().unwrap();
+        manifest.entries.push(TrashEntry {
+            original: std::path::absolute(path)?,
+            stored,
+        });
+        std::fs::write(self.run_dir.join(MANIFEST), serde_json::to_string(&*manifest)?)
+    }
+}
+
+/// Directory on the scan root's filesystem that items are renamed into during a run and
+/// removed in one go afterwards, so the project is never left half-cleaned for long
+pub struct StagingDir {
+    dir: PathBuf,
+    next_index: AtomicUsize,
+}
+
+impl StagingDir {
+    /// Staging directory for this process under `root`. Ones left there by earlier runs that
+    /// exited before purging are removed first.
+    pub fn new(root: &Path) -> Self {
+        remove_stale_staging(root);
+        Self {
+            dir: root.join(format!("{}{}", STAGING_PREFIX, std::process::id())),
+            next_index: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Rename `path` into the staging directory. Returns false if it can't be renamed there,
+    /// e.g. because it is on another filesystem, in which case it should be deleted directly.
+    pub fn stage(&self, path: &Path) -> bool {
+        let index = self.next_index.fetch_add(1, Ordering::Relaxed);
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        std::fs::create_dir_all(&self.dir).is_ok()
+            && std::fs::rename(path, self.dir.join(format!("{}-{}", index, name))).is_ok()
+    }
+
+    /// Delete everything staged so far
+    pub fn purge(&self) -> std::io::Result<()> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// Remove the staging directories under `root` whose process is no longer running
+fn remove_stale_staging(root: &Path) {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(pid) = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(STAGING_PREFIX))
+            .and_then(|pid| pid.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if pid != std::process::id() && !is_process_running(pid) {
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_process_running(pid: u32) -> bool {
+    // Signal 0 only checks that the process exists; EPERM means it does but belongs to another user
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    let status = unsafe { libc::kill(pid, 0) };
+    status == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Without a portable check, another run's staging directory is assumed to be in use
+#[cfg(not(unix))]
+fn is_process_running(_pid: u32) -> bool {
+    true
+}
+
+/// Outcome of restoring a run from the trash
+#[derive(Debug, Default)]
+pub struct UndoResult {
+    pub restored: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+/// Restore every item of the most recent run. Returns `None` if the trash is empty.
+/// Items that can't be restored stay in the trash and in the manifest.
+pub fn undo_last(trash_dir: &Path) -> Result<Option<UndoResult>> {
+    let Some(run_dir) = latest_run(trash_dir) else {
+        return Ok(None);
+    };
+    let manifest_path = run_dir.join(MANIFEST);
+    let manifest: Manifest = serde_json::from_str(&std::fs::read_to_string(&manifest_path)?)?;
+
+    let mut result = UndoResult::default();
+    let mut remaining = Vec::new();
+    for entry in manifest.entries.into_iter().rev() {
+        match restore(&entry) {
+            Ok(()) => result.restored.push(entry.original),
+            Err(e) => {
+                result.failed.push((entry.original.clone(), e.to_string()));
+                remaining.push(entry);
+            }
+        }
+    }
+
+    if remaining.is_empty() {
+        std::fs::remove_dir_all(&run_dir)?;
+    } else {
+        remaining.reverse();
+        std::fs::write(&manifest_path, serde_json::to_string(&Manifest { entries: remaining })?)?;
+    }
+    Ok(Some(result))
+}
+
+fn latest_run(trash_dir: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(trash_dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.join(MANIFEST).is_file())
+        .max()
+}
+
+fn restore(entry: &TrashEntry) -> std::io::Result<()> {
+    // An empty directory left by --preserve-skeleton gives way to the one being restored
+    if entry.original.is_dir() && !entry.original.is_symlink() {
+        let _ = std::fs::remove_dir(&entry.original);
+    }
+    if entry.original.exists() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            "original path already exists",
+        ));
+    }
+    if let Some(parent) = entry.original.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    move_path(&entry.stored, &entry.original)
+}
+
+/// Free bytes that moving `from` into `to_dir` would use up: those of the destination
+/// filesystem when the move has to copy, or `None` for a plain rename (or if unknown)
+pub fn space_for_move(from: &Path, to_dir: &Path) -> Option<u64> {
+    let destination = to_dir.ancestors().find(|dir| dir.exists())?;
+    if same_filesystem(from, destination) {
+        return None;
+    }
+    crate::utils::available_space(destination)
+}
+
+#[cfg(unix)]
+fn same_filesystem(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (std::fs::symlink_metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_filesystem(_a: &Path, _b: &Path) -> bool {
+    false
+}
+
+/// Rename, falling back to copy-and-delete when crossing filesystems
+fn move_path(from: &Path, to: &Path) -> std::io::Result<()> {
+    match std::fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(_) if from.exists() && !to.exists() => {
+            copy_recursive(from, to)?;
+            if from.is_dir() {
+                std::fs::remove_dir_all(from)
+            } else {
+                std::fs::remove_file(from)
+            }
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Copy a tree without recursing per level, so deep trees can't exhaust the stack.
+/// Links are copied as links.
+fn copy_recursive(from: &Path, to: &Path) -> std::io::Result<()> {
+    for entry in WalkDir::new(from).follow_links(false).follow_root_links(false) {
+        let entry = entry?;
+        // Joining the root's empty relative path would add a trailing `/`
+        let target = match entry.path().strip_prefix(from).map_err(std::io::Error::other)? {
+            relative if relative.as_os_str().is_empty() => to.to_path_buf(),
+            relative => to.join(relative),
+        };
+        let file_type = entry.file_type();
+        if file_type.is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else if file_type.is_symlink() {
+            let link = std::fs::read_link(entry.path())?;
+            crate::utils::create_symlink(&link, &target).map_err(std::io::Error::other)?;
+        } else if crate::utils::is_special_file(&file_type) {
+            // Reading a named pipe waits for a writer; pipes and sockets are recreated by their owner
+            continue;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_trash_then_undo_restores_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let trash_dir = temp_dir.path().join("trash");
+        let cache = temp_dir.path().join("project/node_modules");
+        std::fs::create_dir_all(cache.join("dep")).unwrap();
+        std::fs::write(cache.join("dep/index.js"), "module.exports = 1").unwrap();
+        let lockfile = temp_dir.path().join("project/yarn.lock");
+        std::fs::write(&lockfile, "lock").unwrap();
+
+        let run = TrashRun::new(&trash_dir);
+        run.store(&cache).unwrap();
+        run.store(&lockfile).unwrap();
+        assert!(!cache.exists());
+        assert!(!lockfile.exists());
+
+        // Something new now occupies the lockfile's old path
+        std::fs::write(&lockfile, "new lock").unwrap();
+
+        let result = undo_last(&trash_dir).unwrap().unwrap();
+        assert_eq!(result.restored, vec![cache.clone()]);
+        assert_eq!(result.failed.len(), 1);
+        assert_eq!(std::fs::read_to_string(cache.join("dep/index.js")).unwrap(), "module.exports = 1");
+        assert_eq!(std::fs::read_to_string(&lockfile).unwrap(), "new lock");
+
+        // The unrestorable item stays in the trash for a later attempt
+        std::fs::remove_file(&lockfile).unwrap();
+        let result = undo_last(&trash_dir).unwrap().unwrap();
+        assert_eq!(result.restored, vec![lockfile.clone()]);
+        assert_eq!(std::fs::read_to_string(&lockfile).unwrap(), "lock");
+        assert!(undo_last(&trash_dir).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_staging_moves_items_then_purges() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in ["web/node_modules/dep", "api/node_modules/dep"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join("index.js"), "module.exports = 1").unwrap();
+        }
+
+        let staging = StagingDir::new(root);
+        assert!(staging.stage(&root.join("web/node_modules")));
+        assert!(staging.stage(&root.join("api/node_modules")));
+        assert!(!root.join("web/node_modules").exists());
+        assert!(!root.join("api/node_modules").exists());
+        assert_eq!(std::fs::read_dir(staging.path()).unwrap().count(), 2);
+        assert!(staging.path().join("0-node_modules/dep/index.js").exists());
+
+        // Nothing left to rename falls back to a direct delete
+        assert!(!staging.stage(&root.join("web/node_modules")));
+
+        staging.purge().unwrap();
+        assert!(!staging.path().exists());
+        staging.purge().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_staging_removes_leftovers_of_exited_runs() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        // A finished child's pid stays unused long enough for the check
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let exited = child.id();
+        child.wait().unwrap();
+        let running = std::os::unix::process::parent_id();
+        for pid in [exited, running] {
+            std::fs::create_dir_all(root.join(format!(".clearcache-staging-{}/0-node_modules", pid))).unwrap();
+        }
+        std::fs::create_dir_all(root.join(".clearcache-staging-notes")).unwrap();
+
+        let staging = StagingDir::new(root);
+        assert!(!root.join(format!(".clearcache-staging-{}", exited)).exists());
+        assert!(root.join(format!(".clearcache-staging-{}", running)).exists());
+        assert!(root.join(".clearcache-staging-notes").exists());
+        assert!(!staging.path().exists());
+    }
+
+    #[test]
+    fn test_copy_recursive_copies_deep_trees_and_links() {
+        let temp_dir = TempDir::new().unwrap();
+        let from = temp_dir.path().join("node_modules");
+        let deepest = (0..300).fold(from.clone(), |dir, _| dir.join("d"));
+        std::fs::create_dir_all(&deepest).unwrap();
+        std::fs::write(deepest.join("index.js"), "module.exports = 1").unwrap();
+        crate::utils::create_symlink(Path::new("d/d"), &from.join("link")).unwrap();
+
+        let to = temp_dir.path().join("copy");
+        copy_recursive(&from, &to).unwrap();
+        let copied = to.join(deepest.strip_prefix(&from).unwrap());
+        assert_eq!(std::fs::read_to_string(copied.join("index.js")).unwrap(), "module.exports = 1");
+        assert_eq!(std::fs::read_link(to.join("link")).unwrap(), Path::new("d/d"));
+
+        // A link as the item itself is copied as a link, not followed
+        crate::utils::create_symlink(&from, &temp_dir.path().join("linked")).unwrap();
+        copy_recursive(&temp_dir.path().join("linked"), &temp_dir.path().join("linked-copy")).unwrap();
+        assert_eq!(std::fs::read_link(temp_dir.path().join("linked-copy")).unwrap(), from);
+    }
+}
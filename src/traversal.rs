@@ -1,9 +1,13 @@
+use crate::attributes::AttributeRules;
 use crate::cache_types::{CachePattern, CacheType};
 use anyhow::Result;
-use ignore::{WalkBuilder, WalkState};
+use ignore::gitignore::GitignoreBuilder;
+use ignore::{Match, ParallelVisitor, ParallelVisitorBuilder, WalkBuilder, WalkParallel, WalkState};
+use serde::Serialize;
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
 use walkdir::WalkDir;
 
 #[derive(Debug, Clone)]
@@ -14,6 +18,11 @@ pub struct TraversalConfig {
     pub respect_gitignore: bool,
     pub respect_clearcacheignore: bool,
     pub parallel: bool,
+    pub skip_paths: HashSet<PathBuf>, // Subtrees to skip entirely (e.g. unchanged since last scan)
+    pub global_ignore: Option<PathBuf>, // Per-user ignore file applied on every run
+    pub attributes: bool, // Also match paths marked as caches by .clearcacheattributes files
+    pub case_sensitive: bool, // False matches `Node_Modules` against `node_modules`, as on macOS and Windows
+    pub canonicalize: bool, // Resolve paths to dedup visits; false trusts logical paths and skips the syscall
 }
 
 impl Default for TraversalConfig {
@@ -21,10 +30,15 @@ impl Default for TraversalConfig {
         Self {
             max_depth: 20,
             follow_links: false,
-            ignore_hidden: true,
+            ignore_hidden: false, // Most caches are dot-directories (.next, .pytest_cache, ...)
             respect_gitignore: true,
             respect_clearcacheignore: true,
             parallel: true,
+            skip_paths: HashSet::new(),
+            global_ignore: None,
+            attributes: false,
+            case_sensitive: true,
+            canonicalize: true,
         }
     }
 }
@@ -36,36 +50,189 @@ pub struct FoundCacheItem {
     pub cache_type: CacheType,
     pub size: u64,
     pub is_directory: bool,
+    pub modified: Option<SystemTime>,
+}
+
+impl FoundCacheItem {
+    fn from_entry(entry: &ignore::DirEntry, cache_type: CacheType, pattern: CachePattern) -> Self {
+        let metadata = entry.metadata().ok();
+        Self {
+            path: entry.path().to_path_buf(),
+            pattern,
+            cache_type,
+            size: metadata.as_ref().map(|m| m.len()).unwrap_or(0),
+            is_directory: metadata.as_ref().map(|m| m.is_dir()).unwrap_or(false),
+            modified: metadata.as_ref().and_then(|m| m.modified().ok()),
+        }
+    }
+}
+
+/// Creates one [`MatchCollector`] per walker thread
+struct MatchCollectorBuilder<'s> {
+    traversal: &'s CacheTraversal,
+    root: &'s Path,
+    finished: &'s Mutex<Vec<Vec<FoundCacheItem>>>,
+}
+
+impl<'s> ParallelVisitorBuilder<'s> for MatchCollectorBuilder<'s> {
+    fn build(&mut self) -> Box<dyn ParallelVisitor + 's> {
+        Box::new(MatchCollector {
+            traversal: self.traversal,
+            root: self.root,
+            found: Vec::new(),
+            finished: self.finished,
+        })
+    }
+}
+
+/// One walker thread's matches, kept locally and handed over when the thread is done
+struct MatchCollector<'s> {
+    traversal: &'s CacheTraversal,
+    root: &'s Path,
+    found: Vec<FoundCacheItem>,
+    finished: &'s Mutex<Vec<Vec<FoundCacheItem>>>,
+}
+
+impl ParallelVisitor for MatchCollector<'_> {
+    fn visit(&mut self, entry: std::result::Result<ignore::DirEntry, ignore::Error>) -> WalkState {
+        let Ok(entry) = entry else {
+            return WalkState::Continue;
+        };
+        let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+        self.traversal.check_depth(entry.path(), self.root, entry.depth(), is_dir);
+        // Only the first matching pattern is used, to avoid duplicates
+        if let Some((cache_type, pattern)) = self.traversal.match_entry(entry.path(), self.root) {
+            self.found.push(FoundCacheItem::from_entry(&entry, cache_type, pattern));
+        }
+        WalkState::Continue
+    }
+}
+
+impl Drop for MatchCollector<'_> {
+    fn drop(&mut self) {
+        if !self.found.is_empty() {
+            let found = std::mem::take(&mut self.found);
+            self.finished.lock().unwrap_or_else(|e| e.into_inner()).push(found);
+        }
+    }
+}
+
+/// Why a single path was or wasn't matched, as reported by `--explain`
+#[derive(Debug, Serialize)]
+pub struct PathExplanation {
+    pub path: PathBuf,
+    pub root: PathBuf,
+    pub is_directory: bool,
+    pub ignored_by: Option<String>, // "<ignore file>: <rule>" when an ignore file excludes the path
+    pub matched: Option<String>, // Name of the first matching pattern, which is the one the scan uses
+    pub checks: Vec<PatternCheck>,
+}
+
+/// Outcome of testing one glob of one pattern against a path
+#[derive(Debug, Serialize)]
+pub struct PatternCheck {
+    pub cache_type: CacheType,
+    pub pattern_name: String,
+    pub glob: String,
+    pub matched: bool,
+    pub reason: String,
 }
 
 pub struct CacheTraversal {
     config: TraversalConfig,
     patterns: Vec<(CacheType, CachePattern)>,
+    attributes: AttributeRules,
+    depth_limited: OnceLock<PathBuf>, // First directory whose contents lay beyond max_depth
 }
 
 impl CacheTraversal {
     pub fn new(config: TraversalConfig, patterns: Vec<(CacheType, CachePattern)>) -> Self {
-        Self { config, patterns }
+        Self {
+            config,
+            patterns,
+            attributes: AttributeRules::default(),
+            depth_limited: OnceLock::new(),
+        }
+    }
+
+    /// Record `path` if it is a non-empty directory at the depth limit, whose entries the walk
+    /// skips. Inside a matched item (e.g. nested `node_modules`) nothing is lost, as it goes whole.
+    fn check_depth(&self, path: &Path, root: &Path, depth: usize, is_dir: bool) {
+        if depth == self.config.max_depth
+            && is_dir
+            && self.depth_limited.get().is_none()
+            && std::fs::read_dir(path).is_ok_and(|mut entries| entries.next().is_some())
+            && !path.ancestors().take(depth).any(|dir| self.match_entry(dir, root).is_some())
+        {
+            let _ = self.depth_limited.set(path.to_path_buf());
+        }
+    }
+
+    /// A warning when the last scan stopped at `max_depth` with directories left unvisited,
+    /// since caches nested deeper were missed
+    pub fn depth_limit_warning(&self) -> Option<String> {
+        self.depth_limited.get().map(|dir| {
+            format!(
+                "depth limit reached ({} levels) at {}; caches nested deeper were not scanned (raise --max-depth)",
+                self.config.max_depth,
+                dir.display()
+            )
+        })
+    }
+
+    /// The first pattern matching `path`, or failing that, an attributes file rule
+    fn match_entry(&self, path: &Path, root: &Path) -> Option<(CacheType, CachePattern)> {
+        self.patterns
+            .iter()
+            .find(|(_, pattern)| matches_pattern_static(path, root, pattern, self.config.case_sensitive))
+            .cloned()
+            .or_else(|| {
+                self.config
+                    .attributes
+                    .then(|| self.attributes.match_path(path, root, self.config.case_sensitive))
+                    .flatten()
+            })
     }
 
     /// Find all cache items using the most efficient traversal method
     pub fn find_cache_items<P: AsRef<Path>>(&self, root: P) -> Result<Vec<FoundCacheItem>> {
         let root = root.as_ref();
         
-        if self.config.parallel && self.config.respect_clearcacheignore {
+        let mut items = if self.config.parallel && self.config.respect_clearcacheignore {
             // Use ignore crate for parallel traversal with .clearcacheignore support
-            self.find_with_ignore_parallel(root)
+            self.find_with_ignore_parallel(root)?
         } else if self.config.respect_clearcacheignore {
             // Use ignore crate for sequential traversal with .clearcacheignore support  
-            self.find_with_ignore_sequential(root)
+            self.find_with_ignore_sequential(root)?
+        } else if self.config.parallel {
+            // Parallel walk with every ignore rule disabled
+            self.find_with_parallel_walk(root)?
         } else {
             // Use walkdir for maximum performance when ignores aren't needed
-            self.find_with_walkdir(root)
+            self.find_with_walkdir(root)?
+        };
+
+        // Both walkers stop at symlink loops, but followed links can still reach one directory twice
+        if self.config.follow_links && self.config.canonicalize {
+            let mut visited = HashSet::new();
+            items.retain(|item| visited.insert(self.visit_key(&item.path)));
+        }
+        Ok(items)
+    }
+
+    /// The identity used to skip paths already visited: the resolved path, or the path
+    /// as walked when canonicalization is off
+    fn visit_key(&self, path: &Path) -> PathBuf {
+        if self.config.canonicalize {
+            path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+        } else {
+            path.to_path_buf()
         }
     }
 
     /// Ultra-fast traversal using walkdir (no .clearcacheignore support)
     fn find_with_walkdir<P: AsRef<Path>>(&self, root: P) -> Result<Vec<FoundCacheItem>> {
+        let root = root.as_ref();
         let mut found_items = Vec::new();
         let mut visited = HashSet::new();
 
@@ -74,6 +241,9 @@ impl CacheTraversal {
             .follow_links(self.config.follow_links)
             .into_iter()
             .filter_entry(|e| {
+                if self.config.skip_paths.contains(e.path()) {
+                    return false;
+                }
                 if self.config.ignore_hidden {
                     !is_hidden(e.path())
                 } else {
@@ -85,27 +255,27 @@ impl CacheTraversal {
             let path = entry.path();
             
             // Skip if we've already processed this path (handles symlink loops)
-            let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
-            if !visited.insert(canonical.clone()) {
+            if !visited.insert(self.visit_key(path)) {
                 continue;
             }
+            self.check_depth(path, root, entry.depth(), entry.file_type().is_dir());
 
-            // Check against all patterns
-            for (cache_type, pattern) in &self.patterns {
-                if self.matches_pattern(path, pattern) {
-                    let metadata = entry.metadata().ok();
-                    let size = metadata.as_ref().map(|m| m.len()).unwrap_or(0);
-                    let is_directory = metadata.as_ref().map(|m| m.is_dir()).unwrap_or(false);
+            // Only the first matching pattern is used, to avoid duplicates
+            if let Some((cache_type, pattern)) = self.match_entry(path, root) {
+                let metadata = entry.metadata().ok();
+                let size = metadata.as_ref().map(|m| m.len()).unwrap_or(0);
+                let is_directory = metadata.as_ref().map(|m| m.is_dir()).unwrap_or(false);
+                let modified = metadata.as_ref().and_then(|m| m.modified().ok());
 
-                    found_items.push(FoundCacheItem {
-                        path: canonical.clone(),
-                        pattern: pattern.clone(),
-                        cache_type: cache_type.clone(),
-                        size,
-                        is_directory,
-                    });
-                    break; // Only match first pattern to avoid duplicates
-                }
+                found_items.push(FoundCacheItem {
+                    // Reported under the root as given, like the other engines, even if it is a symlink
+                    path: path.to_path_buf(),
+                    pattern,
+                    cache_type,
+                    size,
+                    is_directory,
+                    modified,
+                });
             }
         }
 
@@ -114,121 +284,345 @@ impl CacheTraversal {
 
     /// Parallel traversal with .clearcacheignore support using ignore crate
     fn find_with_ignore_parallel<P: AsRef<Path>>(&self, root: P) -> Result<Vec<FoundCacheItem>> {
-        let found_items = Arc::new(std::sync::Mutex::new(Vec::new()));
-        let patterns = Arc::new(self.patterns.clone());
+        let root = root.as_ref();
+        let walker = self.walk_builder(root).build_parallel();
+        Ok(self.collect_parallel(walker, root))
+    }
+
+    /// Parallel traversal without any ignore files, equivalent to the walkdir scan
+    fn find_with_parallel_walk<P: AsRef<Path>>(&self, root: P) -> Result<Vec<FoundCacheItem>> {
+        let root = root.as_ref();
+        let ignore_hidden = self.config.ignore_hidden;
+        let skip = skip_filter(&self.config.skip_paths);
 
         let walker = WalkBuilder::new(root)
+            .standard_filters(false)
             .max_depth(Some(self.config.max_depth))
             .follow_links(self.config.follow_links)
-            .hidden(!self.config.ignore_hidden)
-            .git_ignore(self.config.respect_gitignore)
-            .add_custom_ignore_filename(".clearcacheignore")
+            .filter_entry(move |entry| skip(entry) && !(ignore_hidden && is_hidden(entry.path())))
             .build_parallel();
+        Ok(self.collect_parallel(walker, root))
+    }
 
-        walker.run(|| {
-            let found_items = Arc::clone(&found_items);
-            let patterns = Arc::clone(&patterns);
-            
-            Box::new(move |result| {
-                if let Ok(entry) = result {
-                    let path = entry.path();
-                    
-                    // Check against all patterns
-                    for (cache_type, pattern) in patterns.iter() {
-                        if matches_pattern_static(path, pattern) {
-                            let metadata = entry.metadata().ok();
-                            let size = metadata.as_ref().map(|m| m.len()).unwrap_or(0);
-                            let is_directory = metadata.as_ref().map(|m| m.is_dir()).unwrap_or(false);
-
-                            let item = FoundCacheItem {
-                                path: path.to_path_buf(),
-                                pattern: pattern.clone(),
-                                cache_type: cache_type.clone(),
-                                size,
-                                is_directory,
-                            };
-
-                            if let Ok(mut items) = found_items.lock() {
-                                items.push(item);
-                            }
-                            break; // Only match first pattern to avoid duplicates
-                        }
-                    }
-                }
-                WalkState::Continue
-            })
+    /// Run a parallel walk, collecting matches per thread. Each thread hands its matches
+    /// over once when it finishes, so match-heavy trees don't contend on a shared vector.
+    fn collect_parallel(&self, walker: WalkParallel, root: &Path) -> Vec<FoundCacheItem> {
+        let finished = Mutex::new(Vec::new());
+        walker.visit(&mut MatchCollectorBuilder {
+            traversal: self,
+            root,
+            finished: &finished,
         });
-
-        let items = found_items.lock().unwrap().clone();
-        Ok(items)
+        finished.into_inner().unwrap().into_iter().flatten().collect()
     }
 
     /// Sequential traversal with .clearcacheignore support using ignore crate
     fn find_with_ignore_sequential<P: AsRef<Path>>(&self, root: P) -> Result<Vec<FoundCacheItem>> {
+        let root = root.as_ref();
         let mut found_items = Vec::new();
 
-        let walker = WalkBuilder::new(root)
+        let walker = self.walk_builder(root).build();
+
+        for entry in walker.flatten() {
+            let path = entry.path();
+            self.check_depth(path, root, entry.depth(), entry.file_type().is_some_and(|t| t.is_dir()));
+
+            // Only the first matching pattern is used, to avoid duplicates
+            if let Some((cache_type, pattern)) = self.match_entry(path, root) {
+                found_items.push(FoundCacheItem::from_entry(&entry, cache_type, pattern));
+            }
+        }
+
+        Ok(found_items)
+    }
+
+    /// Configure an ignore-crate walker with .clearcacheignore and the global ignore file
+    fn walk_builder<P: AsRef<Path>>(&self, root: P) -> WalkBuilder {
+        let mut builder = WalkBuilder::new(root);
+        builder
             .max_depth(Some(self.config.max_depth))
             .follow_links(self.config.follow_links)
-            .hidden(!self.config.ignore_hidden)
+            .hidden(self.config.ignore_hidden)
             .git_ignore(self.config.respect_gitignore)
             .add_custom_ignore_filename(".clearcacheignore")
-            .build();
-
-        for result in walker {
-            if let Ok(entry) = result {
-                let path = entry.path();
-                
-                // Check against all patterns
-                for (cache_type, pattern) in &self.patterns {
-                    if matches_pattern_static(path, pattern) {
-                        let metadata = entry.metadata().ok();
-                        let size = metadata.as_ref().map(|m| m.len()).unwrap_or(0);
-                        let is_directory = metadata.as_ref().map(|m| m.is_dir()).unwrap_or(false);
-
-                        found_items.push(FoundCacheItem {
-                            path: path.to_path_buf(),
-                            pattern: pattern.clone(),
-                            cache_type: cache_type.clone(),
-                            size,
-                            is_directory,
-                        });
-                        break; // Only match first pattern to avoid duplicates
-                    }
-                }
-            }
+            .filter_entry(skip_filter(&self.config.skip_paths));
+
+        // A missing or unreadable global ignore file simply adds no rules
+        if let Some(global_ignore) = &self.config.global_ignore {
+            let _ = builder.add_ignore(global_ignore);
         }
+        builder
+    }
 
-        Ok(found_items)
+    /// Trace how the matcher and ignore files treat `path` under `root`, without walking
+    pub fn explain_path(&self, root: &Path, path: &Path) -> PathExplanation {
+        let is_directory = path.is_dir();
+        let checks = self
+            .patterns
+            .iter()
+            .flat_map(|(cache_type, pattern)| {
+                pattern.patterns.iter().map(move |glob| {
+                    let (matched, reason) = explain_glob(path, root, glob, self.config.case_sensitive);
+                    PatternCheck {
+                        cache_type: cache_type.clone(),
+                        pattern_name: pattern.name.clone(),
+                        glob: glob.clone(),
+                        matched,
+                        reason,
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        PathExplanation {
+            path: path.to_path_buf(),
+            root: root.to_path_buf(),
+            is_directory,
+            ignored_by: self.ignore_rule(root, path, is_directory),
+            matched: checks.iter().find(|check| check.matched).map(|check| check.pattern_name.clone()),
+            checks,
+        }
     }
 
-    /// Check if a path matches a cache pattern
-    fn matches_pattern(&self, path: &Path, pattern: &CachePattern) -> bool {
-        matches_pattern_static(path, pattern)
+    /// The ignore rule excluding `path`, checking the deepest ignore files first like the walker does
+    fn ignore_rule(&self, root: &Path, path: &Path, is_directory: bool) -> Option<String> {
+        if !path.starts_with(root) {
+            return None;
+        }
+        let mut names = Vec::new();
+        if self.config.respect_clearcacheignore {
+            names.push(".clearcacheignore");
+        }
+        if self.config.respect_gitignore {
+            names.push(".gitignore");
+        }
+
+        // Deepest ignore files first; the global file has the lowest precedence
+        let mut files: Vec<(PathBuf, PathBuf)> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(root))
+            .flat_map(|dir| names.iter().map(move |name| (dir.to_path_buf(), dir.join(name))))
+            .collect();
+        if let Some(global_ignore) = self.config.global_ignore.as_ref().filter(|_| self.config.respect_clearcacheignore) {
+            files.push((root.to_path_buf(), global_ignore.clone()));
+        }
+
+        for (base, file) in &files {
+            if !file.is_file() {
+                continue;
+            }
+            let mut builder = GitignoreBuilder::new(base);
+            builder.add(file);
+            let Ok(rules) = builder.build() else {
+                continue;
+            };
+            match rules.matched_path_or_any_parents(path, is_directory) {
+                Match::Ignore(rule) => return Some(format!("{}: {}", file.display(), rule.original())),
+                Match::Whitelist(_) => return None,
+                Match::None => {}
+            }
+        }
+        None
     }
 }
 
 /// Static function to check if a path matches a cache pattern (for use in closures)
-fn matches_pattern_static(path: &Path, pattern: &CachePattern) -> bool {
+pub(crate) fn matches_pattern_static(path: &Path, root: &Path, pattern: &CachePattern, case_sensitive: bool) -> bool {
     let file_name = path.file_name().unwrap_or_default().to_string_lossy();
 
-    for pattern_str in &pattern.patterns {
-        if pattern_str.contains('*') {
-            // Glob pattern
-            if let Ok(glob_pattern) = glob::Pattern::new(pattern_str) {
-                if glob_pattern.matches(&file_name) {
-                    return true;
+    pattern
+        .patterns
+        .iter()
+        .any(|pattern_str| matches_glob(path, root, &file_name, pattern_str, case_sensitive))
+}
+
+/// Match one glob of a pattern, expanding any braces first
+pub(crate) fn matches_glob(path: &Path, root: &Path, file_name: &str, pattern_str: &str, case_sensitive: bool) -> bool {
+    if pattern_str.contains('{') {
+        expand_braces(pattern_str)
+            .iter()
+            .any(|expanded| matches_single(path, root, file_name, expanded, case_sensitive))
+    } else {
+        matches_single(path, root, file_name, pattern_str, case_sensitive)
+    }
+}
+
+/// Match one brace-free pattern
+fn matches_single(path: &Path, root: &Path, file_name: &str, pattern_str: &str, case_sensitive: bool) -> bool {
+    if let Some(suffix) = literal_suffix(pattern_str) {
+        // Path suffix, matched against the path's trailing components
+        matches_suffix(path, root, suffix, case_sensitive)
+    } else if pattern_str.contains('/') {
+        // Path glob, matched against the path relative to the scan root
+        matches_relative_glob(path, root, pattern_str, case_sensitive)
+    } else if pattern_str.contains('*') {
+        // Glob pattern
+        let options = glob::MatchOptions {
+            case_sensitive,
+            ..glob::MatchOptions::default()
+        };
+        glob::Pattern::new(pattern_str).is_ok_and(|glob_pattern| glob_pattern.matches_with(file_name, options))
+    } else if case_sensitive {
+        // Exact match
+        file_name == pattern_str
+    } else {
+        file_name.to_lowercase() == pattern_str.to_lowercase()
+    }
+}
+
+/// Expand shell-style braces: `cmake-build-{debug,release}` becomes `cmake-build-debug` and
+/// `cmake-build-release`. Nested and repeated groups are expanded; unbalanced braces are literal.
+pub(crate) fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+
+    // Find the matching close brace and the top-level commas between them
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    let mut close = None;
+    for (i, c) in pattern[open..].char_indices().map(|(i, c)| (i + open, c)) {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(i);
+                    break;
                 }
             }
-        } else {
-            // Exact match
-            if file_name == pattern_str.as_str() {
-                return true;
-            }
+            ',' if depth == 1 => commas.push(i),
+            _ => {}
         }
     }
+    let Some(close) = close.filter(|_| !commas.is_empty()) else {
+        // No alternatives: keep this brace literally and expand the rest
+        return expand_braces(&pattern[open + 1..])
+            .into_iter()
+            .map(|rest| format!("{}{{{}", &pattern[..open], rest))
+            .collect();
+    };
+
+    let (prefix, suffix) = (&pattern[..open], &pattern[close + 1..]);
+    let mut bounds = vec![open];
+    bounds.extend(&commas);
+    bounds.push(close);
+    bounds
+        .windows(2)
+        .flat_map(|w| expand_braces(&format!("{}{}{}", prefix, &pattern[w[0] + 1..w[1]], suffix)))
+        .collect()
+}
+
+/// Test one glob the way [`matches_pattern_static`] does, describing the result
+fn explain_glob(path: &Path, root: &Path, glob_str: &str, case_sensitive: bool) -> (bool, String) {
+    if glob_str.contains('{') {
+        let results: Vec<(bool, String)> = expand_braces(glob_str)
+            .iter()
+            .map(|expanded| explain_single(path, root, expanded, case_sensitive))
+            .collect();
+        return results
+            .iter()
+            .find(|(matched, _)| *matched)
+            .or_else(|| results.iter().find(|(_, reason)| reason.contains("differs only in case")))
+            .or(results.first())
+            .cloned()
+            .unwrap_or_else(|| (false, "pattern expands to nothing".to_string()));
+    }
+    explain_single(path, root, glob_str, case_sensitive)
+}
+
+/// Describe how [`matches_single`] treats one brace-free glob. The result always comes from
+/// the matcher itself; only the wording depends on the kind of pattern.
+fn explain_single(path: &Path, root: &Path, glob_str: &str, case_sensitive: bool) -> (bool, String) {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let matched = matches_single(path, root, &file_name, glob_str, case_sensitive);
+    let outcome = if matched { "matches" } else { "does not match" };
+
+    if literal_suffix(glob_str).is_some() || (glob_str.contains('/') && Path::new(glob_str).is_absolute()) {
+        return (matched, format!("path '{}' {}", path.display(), outcome));
+    }
+    if glob_str.contains('/') {
+        let Ok(relative) = path.strip_prefix(root) else {
+            return (matched, "path is outside the scan root".to_string());
+        };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        return (matched, format!("relative path '{}' {}", relative, outcome));
+    }
+    if matched {
+        return (true, format!("file name '{}' matches", file_name));
+    }
+
+    // Near misses are the usual reason for asking
+    let case_insensitive = glob::MatchOptions {
+        case_sensitive: false,
+        ..glob::MatchOptions::default()
+    };
+    let reason = if glob::Pattern::new(glob_str).is_ok_and(|pattern| pattern.matches_with(&file_name, case_insensitive)) {
+        format!("file name '{}' differs only in case (see --case-insensitive)", file_name)
+    } else {
+        format!("file name '{}' does not match", file_name)
+    };
+    (false, reason)
+}
+
+/// Match a glob such as `apps/*/node_modules` or `**/build/cache` against the path
+/// relative to `root`. `*` stays within one component; `**` spans any number.
+/// Absolute globs (e.g. an expanded `${GOPATH}/pkg/mod`) match the whole path instead.
+fn matches_relative_glob(path: &Path, root: &Path, glob_str: &str, case_sensitive: bool) -> bool {
+    let options = glob::MatchOptions {
+        case_sensitive,
+        require_literal_separator: true,
+        ..glob::MatchOptions::default()
+    };
+    if Path::new(glob_str).is_absolute() {
+        return glob::Pattern::new(glob_str)
+            .map(|pattern| pattern.matches_with(&path.to_string_lossy().replace('\\', "/"), options))
+            .unwrap_or(false);
+    }
 
-    false
+    let Ok(relative) = path.strip_prefix(root) else {
+        return false;
+    };
+    let relative: Vec<String> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    glob::Pattern::new(glob_str.trim_start_matches("./"))
+        .map(|pattern| pattern.matches_with(&relative.join("/"), options))
+        .unwrap_or(false)
+}
+
+/// The components of a suffix pattern: `**/` followed by literal names, such as `**/.cache/pip`
+fn literal_suffix(pattern_str: &str) -> Option<&str> {
+    pattern_str
+        .strip_prefix("**/")
+        .filter(|suffix| !suffix.is_empty() && !suffix.contains(['*', '?', '[']))
+}
+
+/// Whether `path` ends with the components of `suffix`. The suffix may reach above `root`, so
+/// `**/.cache/pip` still matches `pip` when scanning `~/.cache`, but the root itself never matches.
+fn matches_suffix(path: &Path, root: &Path, suffix: &str, case_sensitive: bool) -> bool {
+    if !path.strip_prefix(root).is_ok_and(|relative| relative.components().next().is_some()) {
+        return false;
+    }
+    let mut components = path.components().rev();
+    suffix.trim_end_matches('/').rsplit('/').all(|expected| {
+        components.next().is_some_and(|component| {
+            let actual = component.as_os_str().to_string_lossy();
+            if case_sensitive {
+                actual == expected
+            } else {
+                actual.to_lowercase() == expected.to_lowercase()
+            }
+        })
+    })
+}
+
+/// Build an ignore-crate entry filter that prunes the configured skip paths
+fn skip_filter(skip_paths: &HashSet<PathBuf>) -> impl Fn(&ignore::DirEntry) -> bool + Send + Sync + 'static {
+    let skip_paths = Arc::new(skip_paths.clone());
+    move |entry| !skip_paths.contains(entry.path())
 }
 
 /// Check if a path is hidden (starts with .)
@@ -325,6 +719,7 @@ mod tests {
                 is_directory: true,
                 recursive_safe: true,
                 is_library: false,
+                truncatable: false,
             }),
             (CacheType::General, CachePattern {
                 name: "exporter_dirs".to_string(),
@@ -333,6 +728,7 @@ mod tests {
                 is_directory: true,
                 recursive_safe: true,
                 is_library: false,
+                truncatable: false,
             }),
         ];
 
@@ -354,4 +750,590 @@ mod tests {
         assert!(content.contains("package.json"));
         assert!(content.contains("README*"));
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_global_ignore_spares_matching_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("work");
+        fs::create_dir_all(root.join("app/__pycache__")).unwrap();
+        fs::create_dir_all(root.join("vendor/__pycache__")).unwrap();
+
+        // The global ignore lives outside the tree; no .clearcacheignore exists in it
+        let global_ignore = temp_dir.path().join("ignore");
+        fs::write(&global_ignore, "vendor/\n").unwrap();
+
+        let patterns = vec![(CacheType::Python, CachePattern {
+            name: "python_cache".to_string(),
+            patterns: vec!["__pycache__".to_string()],
+            description: "Python cache".to_string(),
+            is_directory: true,
+            recursive_safe: true,
+            is_library: false,
+            truncatable: false,
+        })];
+
+        for parallel in [true, false] {
+            let config = TraversalConfig {
+                parallel,
+                global_ignore: Some(global_ignore.clone()),
+                ..TraversalConfig::default()
+            };
+            let results = CacheTraversal::new(config, patterns.clone()).find_cache_items(&root).unwrap();
+            assert_eq!(results.len(), 1);
+            assert!(results[0].path.ends_with("app/__pycache__"));
+        }
+    }
+
+    #[test]
+    fn test_explain_path_reports_match_and_near_miss() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("app/__pycache__")).unwrap();
+        fs::create_dir_all(root.join("app/__PyCache__")).unwrap();
+        fs::create_dir_all(root.join("vendor/__pycache__")).unwrap();
+        fs::write(root.join(".clearcacheignore"), "vendor/\n").unwrap();
+
+        let patterns = vec![(CacheType::Python, CachePattern {
+            name: "python_cache".to_string(),
+            patterns: vec!["__pycache__".to_string(), "*.pyc".to_string()],
+            description: "Python cache".to_string(),
+            is_directory: true,
+            recursive_safe: true,
+            is_library: false,
+            truncatable: false,
+        })];
+        let traversal = CacheTraversal::new(TraversalConfig::default(), patterns);
+
+        let hit = traversal.explain_path(root, &root.join("app/__pycache__"));
+        assert_eq!(hit.matched.as_deref(), Some("python_cache"));
+        assert!(hit.is_directory);
+        assert!(hit.ignored_by.is_none());
+        assert_eq!(hit.checks.len(), 2);
+        assert!(hit.checks[0].matched);
+        assert!(!hit.checks[1].matched);
+
+        let near_miss = traversal.explain_path(root, &root.join("app/__PyCache__"));
+        assert!(near_miss.matched.is_none());
+        assert!(near_miss.checks[0].reason.contains("differs only in case"));
+
+        let ignored = traversal.explain_path(root, &root.join("vendor/__pycache__"));
+        assert_eq!(ignored.matched.as_deref(), Some("python_cache"));
+        assert!(ignored.ignored_by.unwrap().ends_with(".clearcacheignore: vendor/"));
+    }
+
+    #[test]
+    fn test_explain_agrees_with_matcher() {
+        let root = Path::new("/home/dev/code");
+        let paths = [
+            "/home/dev/code/app/__pycache__",
+            "/home/dev/code/app/__PyCache__",
+            "/home/dev/code/apps/web/node_modules",
+            "/home/dev/code/.cache/pip",
+            "/home/dev/code/pip",
+            "/home/dev/code/cmake-build-release",
+            "/home/dev/code/lib/mod.pyc",
+            "/home/dev/go/pkg/mod",
+        ];
+        let globs = [
+            "__pycache__",
+            "*.pyc",
+            "apps/*/node_modules",
+            "**/node_modules",
+            "**/.cache/pip",
+            "**/code/pip",
+            "cmake-build-{debug,release}",
+            "/home/dev/go/pkg/*",
+            "./apps/**",
+        ];
+        for path in paths.map(Path::new) {
+            let file_name = path.file_name().unwrap().to_string_lossy();
+            for glob in globs {
+                for case_sensitive in [true, false] {
+                    let (explained, reason) = explain_glob(path, root, glob, case_sensitive);
+                    let matched = matches_glob(path, root, &file_name, glob, case_sensitive);
+                    assert_eq!(explained, matched, "{} against {}: {}", glob, path.display(), reason);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_case_insensitive_matching() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("web/Node_Modules")).unwrap();
+        fs::create_dir_all(root.join("Apps/api/NODE_MODULES")).unwrap();
+        fs::write(root.join("web/Main.PYC"), "").unwrap();
+
+        let pattern = CachePattern {
+            name: "deps".to_string(),
+            patterns: vec!["node_modules".to_string(), "*.pyc".to_string(), "apps/*/node_modules".to_string()],
+            description: String::new(),
+            is_directory: true,
+            recursive_safe: true,
+            is_library: false,
+            truncatable: false,
+        };
+        for name in ["web/Node_Modules", "Apps/api/NODE_MODULES", "web/Main.PYC"] {
+            assert!(!matches_pattern_static(&root.join(name), root, &pattern, true), "{}", name);
+            assert!(matches_pattern_static(&root.join(name), root, &pattern, false), "{}", name);
+        }
+
+        let scan = |case_sensitive| {
+            let config = TraversalConfig {
+                case_sensitive,
+                ..TraversalConfig::default()
+            };
+            CacheTraversal::new(config, vec![(CacheType::Node, pattern.clone())])
+                .find_cache_items(root)
+                .unwrap()
+                .len()
+        };
+        assert_eq!(scan(true), 0);
+        assert_eq!(scan(false), 3);
+    }
+
+    #[test]
+    fn test_brace_expansion_matches_each_variant() {
+        assert_eq!(expand_braces("cmake-build-{debug,release}"), vec!["cmake-build-debug", "cmake-build-release"]);
+        assert_eq!(expand_braces("{a,b{1,2}}.{x,y}"), vec!["a.x", "a.y", "b1.x", "b1.y", "b2.x", "b2.y"]);
+        assert_eq!(expand_braces("{literal}"), vec!["{literal}"]);
+        assert_eq!(expand_braces("no-braces"), vec!["no-braces"]);
+
+        let pattern = CachePattern {
+            name: "cmake_builds".to_string(),
+            patterns: vec!["cmake-build-{debug,release}".to_string(), "*.{o,obj}".to_string()],
+            description: "CMake build directories".to_string(),
+            is_directory: true,
+            recursive_safe: true,
+            is_library: false,
+            truncatable: false,
+        };
+        let root = Path::new("/project");
+        for name in ["cmake-build-debug", "cmake-build-release", "main.o", "main.obj"] {
+            assert!(matches_pattern_static(&root.join(name), root, &pattern, true), "{}", name);
+        }
+        for name in ["cmake-build-profile", "cmake-build-{debug,release}", "main.c"] {
+            assert!(!matches_pattern_static(&root.join(name), root, &pattern, true), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_relative_path_glob_matches_depth() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("apps/web/node_modules/dep/node_modules")).unwrap();
+        fs::create_dir_all(root.join("apps/api/node_modules")).unwrap();
+        fs::create_dir_all(root.join("packages/ui/node_modules")).unwrap();
+
+        let patterns = vec![(CacheType::Node, CachePattern {
+            name: "app_deps".to_string(),
+            patterns: vec!["apps/*/node_modules".to_string()],
+            description: "App dependencies".to_string(),
+            is_directory: true,
+            recursive_safe: true,
+            is_library: true,
+            truncatable: false,
+        })];
+
+        let results = CacheTraversal::new(TraversalConfig::default(), patterns).find_cache_items(root).unwrap();
+        let mut found: Vec<PathBuf> = results
+            .iter()
+            .map(|r| r.path.strip_prefix(root).unwrap().to_path_buf())
+            .collect();
+        found.sort();
+        assert_eq!(found, vec![PathBuf::from("apps/api/node_modules"), PathBuf::from("apps/web/node_modules")]);
+
+        let pattern = |glob: &str| CachePattern {
+            name: "glob".to_string(),
+            patterns: vec![glob.to_string()],
+            description: String::new(),
+            is_directory: true,
+            recursive_safe: true,
+            is_library: false,
+            truncatable: false,
+        };
+        let nested = root.join("apps/web/node_modules/dep/node_modules");
+        assert!(!matches_pattern_static(&nested, root, &pattern("apps/*/node_modules"), true));
+        assert!(matches_pattern_static(&nested, root, &pattern("apps/**/node_modules"), true));
+        assert!(!matches_pattern_static(&nested, Path::new("/elsewhere"), &pattern("apps/**/node_modules"), true));
+    }
+
+    #[test]
+    fn test_suffix_pattern_matches_trailing_components() {
+        let pattern = CachePattern {
+            name: "pip_cache".to_string(),
+            patterns: vec!["**/.cache/pip".to_string()],
+            description: String::new(),
+            is_directory: true,
+            recursive_safe: true,
+            is_library: false,
+            truncatable: false,
+        };
+        let root = Path::new("/home/me");
+        assert!(matches_pattern_static(&root.join(".cache/pip"), root, &pattern, true));
+        assert!(matches_pattern_static(&root.join("projects/app/.cache/pip"), root, &pattern, true));
+        assert!(!matches_pattern_static(&root.join("pip"), root, &pattern, true));
+        assert!(!matches_pattern_static(&root.join("cache/pip"), root, &pattern, true));
+        assert!(!matches_pattern_static(&root.join(".cache/pip/http"), root, &pattern, true));
+        assert!(!matches_pattern_static(&root.join(".Cache/pip"), root, &pattern, true));
+        assert!(matches_pattern_static(&root.join(".Cache/pip"), root, &pattern, false));
+
+        // The suffix may start above the root, but the root itself is never a match
+        let cache = Path::new("/home/me/.cache");
+        assert!(matches_pattern_static(&cache.join("pip"), cache, &pattern, true));
+        assert!(!matches_pattern_static(&cache.join("pip"), &cache.join("pip"), &pattern, true));
+
+        // A scan finds the nested suffix but not a top-level `pip`
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("pip")).unwrap();
+        fs::create_dir_all(root.join("tools/.cache/pip/wheels")).unwrap();
+        let items = CacheTraversal::new(TraversalConfig::default(), vec![(CacheType::Python, pattern)])
+            .find_cache_items(root)
+            .unwrap();
+        let found: Vec<&Path> = items.iter().map(|item| item.path.strip_prefix(root).unwrap()).collect();
+        assert_eq!(found, vec![Path::new("tools/.cache/pip")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_root_reports_logical_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let real = temp_dir.path().join("mnt/work");
+        fs::create_dir_all(real.join("api/__pycache__")).unwrap();
+        fs::create_dir_all(real.join("web/__pycache__")).unwrap();
+        let link = temp_dir.path().join("work");
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        let patterns = vec![(CacheType::Python, CachePattern {
+            name: "python_cache".to_string(),
+            patterns: vec!["__pycache__".to_string()],
+            description: "Python cache".to_string(),
+            is_directory: true,
+            recursive_safe: true,
+            is_library: false,
+            truncatable: false,
+        })];
+        let scan = |root: &Path, parallel: bool, respect_clearcacheignore: bool| {
+            let config = TraversalConfig {
+                parallel,
+                respect_clearcacheignore,
+                ..TraversalConfig::default()
+            };
+            let mut paths: Vec<PathBuf> = CacheTraversal::new(config, patterns.clone())
+                .find_cache_items(root)
+                .unwrap()
+                .into_iter()
+                .map(|item| item.path)
+                .collect();
+            paths.sort();
+            paths
+        };
+
+        // Every engine reports items under the root as given; a resolved root gives real paths
+        for (parallel, respect_clearcacheignore) in [(false, false), (true, false), (false, true), (true, true)] {
+            assert_eq!(
+                scan(&link, parallel, respect_clearcacheignore),
+                vec![link.join("api/__pycache__"), link.join("web/__pycache__")]
+            );
+            assert_eq!(
+                scan(&link.canonicalize().unwrap(), parallel, respect_clearcacheignore),
+                vec![real.join("api/__pycache__"), real.join("web/__pycache__")]
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_no_canonicalize_dedups_by_logical_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("real/__pycache__")).unwrap();
+        std::os::unix::fs::symlink(root.join("real"), root.join("alias")).unwrap();
+
+        let scan = |parallel: bool, respect_clearcacheignore: bool, canonicalize: bool| {
+            let config = TraversalConfig {
+                follow_links: true,
+                parallel,
+                respect_clearcacheignore,
+                canonicalize,
+                ..TraversalConfig::default()
+            };
+            let mut paths: Vec<PathBuf> = CacheTraversal::new(config, python_cache_patterns())
+                .find_cache_items(root)
+                .unwrap()
+                .into_iter()
+                .map(|item| item.path.strip_prefix(root).unwrap().to_path_buf())
+                .collect();
+            paths.sort();
+            paths
+        };
+
+        // Resolved, the link and its target are one directory; as walked, they are two
+        for (parallel, respect_clearcacheignore) in [(false, false), (true, false), (false, true), (true, true)] {
+            assert_eq!(scan(parallel, respect_clearcacheignore, true).len(), 1);
+            assert_eq!(
+                scan(parallel, respect_clearcacheignore, false),
+                vec![PathBuf::from("alias/__pycache__"), PathBuf::from("real/__pycache__")]
+            );
+        }
+    }
+
+    #[test]
+    fn test_depth_limit_reached_warns() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a/b/c/__pycache__")).unwrap();
+        fs::create_dir_all(root.join("shallow/__pycache__")).unwrap();
+        // Deleted as a whole, so its deeper contents don't matter
+        fs::create_dir_all(root.join("x/__pycache__/nested/deeper")).unwrap();
+
+        for (parallel, respect_clearcacheignore) in [(false, false), (true, false), (false, true), (true, true)] {
+            let scan = |max_depth: usize| {
+                let config = TraversalConfig {
+                    max_depth,
+                    parallel,
+                    respect_clearcacheignore,
+                    ..TraversalConfig::default()
+                };
+                let traversal = CacheTraversal::new(config, python_cache_patterns());
+                let found = traversal.find_cache_items(root).unwrap().len();
+                (found, traversal.depth_limit_warning())
+            };
+
+            // `a/b` sits at the limit and has entries the scan never visits
+            let (found, warning) = scan(2);
+            assert_eq!(found, 2);
+            let warning = warning.expect("no warning at the depth limit");
+            assert!(warning.contains("depth limit reached (2 levels)"), "{}", warning);
+            assert!(warning.contains(&root.join("a/b").display().to_string()), "{}", warning);
+
+            assert_eq!(scan(20), (3, None));
+            fs::remove_dir_all(root.join("a")).unwrap();
+            assert_eq!(scan(2), (2, None));
+            fs::create_dir_all(root.join("a/b/c/__pycache__")).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_ignore_file_walker_honours_ignore_hidden() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("tree");
+        for dir in ["app/.pytest_cache", ".venv/lib/.pytest_cache", "lib/__pycache__"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+
+        let patterns = vec![(CacheType::Python, CachePattern {
+            name: "python_cache".to_string(),
+            patterns: vec!["__pycache__".to_string(), ".pytest_cache".to_string()],
+            description: "Python cache".to_string(),
+            is_directory: true,
+            recursive_safe: true,
+            is_library: false,
+            truncatable: false,
+        })];
+        // WalkBuilder::hidden(true) skips hidden entries, so it takes ignore_hidden as is
+        for parallel in [false, true] {
+            let found = |ignore_hidden: bool| {
+                let config = TraversalConfig {
+                    parallel,
+                    ignore_hidden,
+                    respect_clearcacheignore: true,
+                    ..TraversalConfig::default()
+                };
+                CacheTraversal::new(config, patterns.clone()).find_cache_items(&root).unwrap().len()
+            };
+            assert_eq!(found(false), 3);
+            assert_eq!(found(true), 1);
+        }
+    }
+
+    #[test]
+    fn test_parallel_no_ignore_matches_sequential() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("tree");
+        for dir in ["a/__pycache__", "a/b/c/__pycache__", "d/.cache", "e/f/node_modules", "g/.hidden/__pycache__"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::write(root.join(".clearcacheignore"), "a/\n").unwrap();
+
+        let patterns = vec![
+            (CacheType::Python, CachePattern {
+                name: "python_cache".to_string(),
+                patterns: vec!["__pycache__".to_string()],
+                description: "Python cache".to_string(),
+                is_directory: true,
+                recursive_safe: true,
+                is_library: false,
+                truncatable: false,
+            }),
+            (CacheType::General, CachePattern {
+                name: "cache".to_string(),
+                patterns: vec![".cache".to_string()],
+                description: "Cache".to_string(),
+                is_directory: true,
+                recursive_safe: true,
+                is_library: false,
+                truncatable: false,
+            }),
+        ];
+
+        for ignore_hidden in [true, false] {
+            let scan = |parallel: bool| {
+                let config = TraversalConfig {
+                    parallel,
+                    ignore_hidden,
+                    respect_clearcacheignore: false,
+                    ..TraversalConfig::default()
+                };
+                let mut paths: Vec<PathBuf> = CacheTraversal::new(config, patterns.clone())
+                    .find_cache_items(&root)
+                    .unwrap()
+                    .into_iter()
+                    .map(|item| item.path)
+                    .collect();
+                paths.sort();
+                paths
+            };
+            let sequential = scan(false);
+            assert_eq!(scan(true), sequential);
+            assert_eq!(sequential.len(), if ignore_hidden { 2 } else { 4 });
+        }
+    }
+
+    #[test]
+    fn test_parallel_collection_keeps_every_match() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in 0..40 {
+            let path = root.join(format!("pkg{}/src", dir));
+            fs::create_dir_all(&path).unwrap();
+            for file in 0..25 {
+                fs::write(path.join(format!("module{}.pyc", file)), "x").unwrap();
+            }
+        }
+        let patterns = vec![(CacheType::Python, CachePattern {
+            name: "python_bytecode".to_string(),
+            patterns: vec!["*.pyc".to_string()],
+            description: "Python bytecode".to_string(),
+            is_directory: false,
+            recursive_safe: true,
+            is_library: false,
+            truncatable: false,
+        })];
+
+        let scan = |parallel: bool, respect_clearcacheignore: bool| {
+            let config = TraversalConfig {
+                parallel,
+                respect_clearcacheignore,
+                ..TraversalConfig::default()
+            };
+            let mut paths: Vec<PathBuf> = CacheTraversal::new(config, patterns.clone())
+                .find_cache_items(root)
+                .unwrap()
+                .into_iter()
+                .map(|item| item.path)
+                .collect();
+            paths.sort();
+            paths
+        };
+        let sequential = scan(false, true);
+        assert_eq!(sequential.len(), 1000);
+        assert_eq!(scan(true, true), sequential);
+        assert_eq!(scan(true, false), sequential);
+    }
+
+    /// Scan with .clearcacheignore support both ways, returning the matches relative to `root`
+    fn scan_respecting_ignore_files(root: &Path, patterns: &[(CacheType, CachePattern)]) -> Vec<PathBuf> {
+        let scan = |parallel: bool| {
+            let config = TraversalConfig {
+                parallel,
+                ..TraversalConfig::default()
+            };
+            let mut paths: Vec<PathBuf> = CacheTraversal::new(config, patterns.to_vec())
+                .find_cache_items(root)
+                .unwrap()
+                .into_iter()
+                .map(|item| item.path.strip_prefix(root).unwrap().to_path_buf())
+                .collect();
+            paths.sort();
+            paths
+        };
+        let sequential = scan(false);
+        assert_eq!(scan(true), sequential);
+        sequential
+    }
+
+    fn python_cache_patterns() -> Vec<(CacheType, CachePattern)> {
+        vec![(CacheType::Python, CachePattern {
+            name: "python_cache".to_string(),
+            patterns: vec!["__pycache__".to_string()],
+            description: "Python cache".to_string(),
+            is_directory: true,
+            recursive_safe: true,
+            is_library: false,
+            truncatable: false,
+        })]
+    }
+
+    #[test]
+    fn test_clearcacheignore_negation_reincludes_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in ["legacy-a/__pycache__", "legacy-b/__pycache__", "legacy-keep/__pycache__", "app/__pycache__"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::write(root.join(".clearcacheignore"), "legacy-*/\n!legacy-keep/\n").unwrap();
+
+        let found = scan_respecting_ignore_files(root, &python_cache_patterns());
+        assert_eq!(found, vec![PathBuf::from("app/__pycache__"), PathBuf::from("legacy-keep/__pycache__")]);
+
+        let traversal = CacheTraversal::new(TraversalConfig::default(), python_cache_patterns());
+        assert!(traversal.explain_path(root, &root.join("legacy-keep/__pycache__")).ignored_by.is_none());
+        assert!(traversal.explain_path(root, &root.join("legacy-a/__pycache__")).ignored_by.is_some());
+    }
+
+    #[test]
+    fn test_clearcacheignore_directory_only_pattern_spares_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a/cache")).unwrap();
+        fs::create_dir_all(root.join("b")).unwrap();
+        fs::write(root.join("b/cache"), "x").unwrap();
+        fs::write(root.join(".clearcacheignore"), "cache/\n").unwrap();
+
+        let patterns = vec![(CacheType::General, CachePattern {
+            name: "cache".to_string(),
+            patterns: vec!["cache".to_string()],
+            description: "Cache".to_string(),
+            is_directory: false,
+            recursive_safe: true,
+            is_library: false,
+            truncatable: false,
+        })];
+        assert_eq!(scan_respecting_ignore_files(root, &patterns), vec![PathBuf::from("b/cache")]);
+    }
+
+    #[test]
+    fn test_nested_clearcacheignore_overrides_parent() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in ["generated/__pycache__", "sub/generated/__pycache__", "sub/other/__pycache__"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::write(root.join(".clearcacheignore"), "generated/\nother/\n").unwrap();
+        fs::write(root.join("sub/.clearcacheignore"), "!generated/\n").unwrap();
+
+        let found = scan_respecting_ignore_files(root, &python_cache_patterns());
+        assert_eq!(found, vec![PathBuf::from("sub/generated/__pycache__")]);
+
+        let traversal = CacheTraversal::new(TraversalConfig::default(), python_cache_patterns());
+        assert!(traversal.explain_path(root, &root.join("sub/generated/__pycache__")).ignored_by.is_none());
+        assert!(traversal
+            .explain_path(root, &root.join("sub/other/__pycache__"))
+            .ignored_by
+            .unwrap()
+            .ends_with(".clearcacheignore: other/"));
+    }
+}
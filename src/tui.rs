@@ -0,0 +1,240 @@
+use crate::cache_cleaner::CleanedItem;
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::ExecutableCommand;
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::{Frame, Terminal};
+use std::io::IsTerminal;
+
+/// Selection state for the review screen, kept separate from rendering
+#[derive(Debug)]
+pub struct ReviewModel {
+    items: Vec<CleanedItem>,
+    selected: Vec<bool>,
+    cursor: usize,
+}
+
+/// What the user asked for after a key press
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReviewAction {
+    Continue,
+    Delete,
+    Quit,
+}
+
+impl ReviewModel {
+    /// Start with every item selected, largest first
+    pub fn new(mut items: Vec<CleanedItem>) -> Self {
+        items.sort_by_key(|item| std::cmp::Reverse(item.size));
+        let selected = vec![true; items.len()];
+        Self {
+            items,
+            selected,
+            cursor: 0,
+        }
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn is_selected(&self, index: usize) -> bool {
+        self.selected.get(index).copied().unwrap_or(false)
+    }
+
+    pub fn move_up(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_down(&mut self) {
+        if self.cursor + 1 < self.items.len() {
+            self.cursor += 1;
+        }
+    }
+
+    /// Toggle the item under the cursor
+    pub fn toggle(&mut self) {
+        if let Some(selected) = self.selected.get_mut(self.cursor) {
+            *selected = !*selected;
+        }
+    }
+
+    /// Select everything, or clear the selection if everything is already selected
+    pub fn toggle_all(&mut self) {
+        let all = self.selected.iter().all(|s| *s);
+        self.selected.iter_mut().for_each(|s| *s = !all);
+    }
+
+    pub fn selected_size(&self) -> u64 {
+        self.selected_iter().map(|item| item.size).sum()
+    }
+
+    pub fn selected_count(&self) -> usize {
+        self.selected.iter().filter(|s| **s).count()
+    }
+
+    /// Consume the model, returning the items chosen for deletion
+    pub fn into_selected(self) -> Vec<CleanedItem> {
+        self.items
+            .into_iter()
+            .zip(self.selected)
+            .filter_map(|(item, selected)| selected.then_some(item))
+            .collect()
+    }
+
+    /// Apply a key press
+    pub fn handle_key(&mut self, key: KeyCode) -> ReviewAction {
+        match key {
+            KeyCode::Up | KeyCode::Char('k') => self.move_up(),
+            KeyCode::Down | KeyCode::Char('j') => self.move_down(),
+            KeyCode::Char(' ') => self.toggle(),
+            KeyCode::Char('a') => self.toggle_all(),
+            KeyCode::Char('d') | KeyCode::Enter => return ReviewAction::Delete,
+            KeyCode::Char('q') | KeyCode::Esc => return ReviewAction::Quit,
+            _ => {}
+        }
+        ReviewAction::Continue
+    }
+
+    fn selected_iter(&self) -> impl Iterator<Item = &CleanedItem> {
+        self.items
+            .iter()
+            .zip(&self.selected)
+            .filter_map(|(item, selected)| selected.then_some(item))
+    }
+}
+
+/// Whether both stdin and stdout are terminals, so the review screen can run
+pub fn is_interactive() -> bool {
+    std::io::stdin().is_terminal() && std::io::stdout().is_terminal()
+}
+
+/// Show the review screen. Returns the items to delete, or `None` if the user quit.
+pub fn run_review(items: Vec<CleanedItem>) -> Result<Option<Vec<CleanedItem>>> {
+    let mut model = ReviewModel::new(items);
+
+    enable_raw_mode()?;
+    std::io::stdout().execute(EnterAlternateScreen)?;
+    let result = review_loop(&mut model);
+    // Always restore the terminal, even if drawing failed
+    let _ = disable_raw_mode();
+    let _ = std::io::stdout().execute(LeaveAlternateScreen);
+
+    match result? {
+        ReviewAction::Delete => Ok(Some(model.into_selected())),
+        _ => Ok(None),
+    }
+}
+
+fn review_loop(model: &mut ReviewModel) -> Result<ReviewAction> {
+    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+    loop {
+        terminal.draw(|frame| draw(frame, model))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match model.handle_key(key.code) {
+                ReviewAction::Continue => {}
+                action => return Ok(action),
+            }
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, model: &ReviewModel) {
+    let [list_area, footer_area] = Layout::vertical([Constraint::Min(1), Constraint::Length(2)]).areas(frame.area());
+
+    let rows: Vec<ListItem> = model
+        .items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let marker = if model.is_selected(index) { "[x]" } else { "[ ]" };
+            let library = if item.is_library { " [LIBRARY]" } else { "" };
+            ListItem::new(format!(
+                "{} {:>10}  {}{}",
+                marker,
+                humansize::format_size(item.size, humansize::BINARY),
+                item.path.display(),
+                library
+            ))
+        })
+        .collect();
+
+    let list = List::new(rows)
+        .block(Block::default().borders(Borders::ALL).title(" Review cache items "))
+        .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD));
+    let mut state = ListState::default().with_selected(Some(model.cursor()));
+    frame.render_stateful_widget(list, list_area, &mut state);
+
+    let footer = Paragraph::new(vec![
+        Line::from(format!(
+            "{} of {} selected, {}",
+            model.selected_count(),
+            model.items.len(),
+            humansize::format_size(model.selected_size(), humansize::BINARY)
+        )),
+        Line::from("↑/↓ move  space toggle  a all  d/enter delete selected  q quit"),
+    ]);
+    frame.render_widget(footer, footer_area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache_types::CacheType;
+    use std::path::PathBuf;
+
+    fn item(path: &str, size: u64) -> CleanedItem {
+        CleanedItem {
+            path: PathBuf::from(path),
+            cache_type: CacheType::Node,
+            pattern_name: "test".to_string(),
+            is_library: false,
+            files: 1,
+            size,
+            apparent_size: size,
+            modified: None,
+            duration_secs: 0.0,
+            size_known: true,
+            new: None,
+        }
+    }
+
+    #[test]
+    fn test_review_model_selection() {
+        let mut model = ReviewModel::new(vec![item("/small", 10), item("/large", 1000), item("/medium", 100)]);
+        assert_eq!(model.selected_count(), 3);
+        assert_eq!(model.selected_size(), 1110);
+
+        // Largest first; deselect it
+        model.toggle();
+        assert!(!model.is_selected(0));
+        assert_eq!(model.selected_size(), 110);
+
+        model.handle_key(KeyCode::Down);
+        model.handle_key(KeyCode::Down);
+        model.handle_key(KeyCode::Down);
+        assert_eq!(model.cursor(), 2);
+        model.handle_key(KeyCode::Char(' '));
+        assert_eq!(model.selected_count(), 1);
+
+        model.toggle_all();
+        assert_eq!(model.selected_count(), 3);
+        model.toggle_all();
+        assert_eq!(model.selected_count(), 0);
+        model.handle_key(KeyCode::Up);
+        model.toggle();
+
+        assert_eq!(model.handle_key(KeyCode::Char('d')), ReviewAction::Delete);
+        let chosen = model.into_selected();
+        assert_eq!(chosen.len(), 1);
+        assert_eq!(chosen[0].path, PathBuf::from("/medium"));
+    }
+}
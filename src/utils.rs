@@ -2,22 +2,186 @@ use anyhow::Result;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+/// File count and sizes of a directory tree
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirectorySize {
+    pub files: u64,
+    pub apparent: u64, // Sum of every file's logical length
+    pub actual: u64,   // On-disk usage: allocated blocks, each hard-linked inode counted once
+}
+
+impl DirectorySize {
+    /// Size of a single file
+    pub fn of_file(metadata: &std::fs::Metadata) -> Self {
+        #[cfg(unix)]
+        let actual = std::os::unix::fs::MetadataExt::blocks(metadata) * 512;
+        #[cfg(not(unix))]
+        let actual = metadata.len();
+        Self {
+            files: 1,
+            apparent: metadata.len(),
+            actual,
+        }
+    }
+
+    /// Bytes to report as freed: logical length with `apparent`, otherwise disk usage
+    pub fn freed(&self, apparent: bool) -> u64 {
+        if apparent {
+            self.apparent
+        } else {
+            self.actual
+        }
+    }
+}
+
 /// Calculate the total size and file count of a directory
-pub fn calculate_directory_size(path: &Path) -> Result<(u64, u64)> {
-    let mut total_size = 0;
-    let mut file_count = 0;
+pub fn calculate_directory_size(path: &Path) -> Result<DirectorySize> {
+    let mut size = DirectorySize::default();
+    #[cfg(unix)]
+    let mut seen_inodes = std::collections::HashSet::new();
 
     for entry in WalkDir::new(path).follow_links(false) {
         let entry = entry?;
         if entry.file_type().is_file() {
             if let Ok(metadata) = entry.metadata() {
-                total_size += metadata.len();
-                file_count += 1;
+                size.files += 1;
+                size.apparent += metadata.len();
+
+                // Hard links (e.g. pnpm stores) share storage; only the first link frees space.
+                // Sparse files occupy fewer blocks than their length suggests.
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::MetadataExt;
+                    if metadata.nlink() > 1 && !seen_inodes.insert((metadata.dev(), metadata.ino())) {
+                        continue;
+                    }
+                    size.actual += metadata.blocks() * 512;
+                }
+                #[cfg(not(unix))]
+                {
+                    size.actual += metadata.len();
+                }
             }
         }
     }
 
-    Ok((file_count, total_size))
+    Ok(size)
+}
+
+/// Whether `file_type` is a named pipe, socket or device node. These hold no data to free,
+/// and opening one (to copy or lock it) can block forever.
+pub fn is_special_file(file_type: &std::fs::FileType) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        file_type.is_fifo() || file_type.is_socket() || file_type.is_block_device() || file_type.is_char_device()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = file_type;
+        false
+    }
+}
+
+/// Files whose presence marks a directory as a project root
+pub const PROJECT_MANIFESTS: &[&str] = &[
+    "package.json",
+    "Cargo.toml",
+    "go.mod",
+    "pyproject.toml",
+    "setup.py",
+    "requirements.txt",
+    "pom.xml",
+    "build.gradle",
+    "Gemfile",
+    "composer.json",
+];
+
+/// Find the nearest ancestor of `path` (excluding `path` itself) that contains a
+/// project manifest, without walking above `stop_at`
+pub fn find_project_root(path: &Path, stop_at: &Path) -> Option<PathBuf> {
+    let mut current = path.parent();
+    while let Some(dir) = current {
+        if PROJECT_MANIFESTS.iter().any(|manifest| dir.join(manifest).is_file()) {
+            return Some(dir.to_path_buf());
+        }
+        if dir == stop_at || !dir.starts_with(stop_at) {
+            break;
+        }
+        current = dir.parent();
+    }
+    None
+}
+
+/// Version-control metadata directories, never deleted whatever pattern matches them
+pub const VCS_DIRS: &[&str] = &[".git", ".svn", ".hg", ".bzr"];
+
+/// Whether `path` is, or is inside, a version-control directory below `root`.
+/// A root inside such a directory was named explicitly, so only components under it count.
+pub fn is_in_vcs_dir(path: &Path, root: &Path) -> bool {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .any(|component| VCS_DIRS.iter().any(|dir| component.as_os_str() == *dir))
+}
+
+/// The first version-control root found in `dir` itself or one of its immediate subdirectories,
+/// e.g. a vendored repository or submodule inside a matched `build` directory. Submodules have
+/// a `.git` file rather than a directory, so any entry with a VCS name counts.
+pub fn find_nested_repository(dir: &Path) -> Option<PathBuf> {
+    let has_vcs = |dir: &Path| VCS_DIRS.iter().map(|name| dir.join(name)).find(|path| path.symlink_metadata().is_ok());
+    if let Some(found) = has_vcs(dir) {
+        return Some(found);
+    }
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .find_map(|entry| has_vcs(&entry.path()))
+}
+
+/// Trees owned by a system package manager, and the tool that owns each
+const MANAGED_SYSTEM_TREES: &[(&str, &str)] = &[
+    ("/nix/store", "Nix"),
+    ("/gnu/store", "Guix"),
+    ("/opt/homebrew", "Homebrew"),
+    ("/usr/local/Cellar", "Homebrew"),
+    ("/usr/local/Homebrew", "Homebrew"),
+    ("/usr/local/Caskroom", "Homebrew"),
+    ("/home/linuxbrew/.linuxbrew", "Homebrew"),
+    ("/opt/local", "MacPorts"),
+    ("/snap", "snap"),
+    ("/var/lib/snapd", "snap"),
+    ("/var/lib/flatpak", "Flatpak"),
+    ("/Library/Frameworks/Python.framework", "the system Python"),
+    ("/System/Library/Frameworks/Python.framework", "the system Python"),
+    ("C:\\ProgramData\\chocolatey", "Chocolatey"),
+];
+
+/// Prefixes whose `site-packages`/`dist-packages` belong to a system Python, installed by the OS
+const SYSTEM_PYTHON_PREFIXES: &[&str] = &["/usr/lib", "/usr/lib64", "/usr/local/lib", "/usr/share"];
+
+/// The package manager that owns `path`, if it lies in a tree that manager installs into:
+/// a Nix or Guix store, a Homebrew or MacPorts prefix, snaps, Flatpaks, or a system Python's
+/// packages. Cache-looking directories there belong to installed software, not to a project.
+/// Symlinks are resolved first, since profiles such as `~/.nix-profile` point into the store.
+pub fn managed_by(path: &Path) -> Option<&'static str> {
+    let path = path
+        .canonicalize()
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf());
+    if let Some((_, manager)) = MANAGED_SYSTEM_TREES.iter().find(|(tree, _)| path.starts_with(tree)) {
+        return Some(manager);
+    }
+    let in_packages = path
+        .components()
+        .any(|component| matches!(component.as_os_str().to_str(), Some("site-packages" | "dist-packages")));
+    (in_packages && SYSTEM_PYTHON_PREFIXES.iter().any(|prefix| path.starts_with(prefix))).then_some("the system Python")
+}
+
+/// Whether `path` is inside a tree managed by a system package manager; see [`managed_by`]
+pub fn is_managed_system_path(path: &Path) -> bool {
+    managed_by(path).is_some()
 }
 
 /// Check if a directory should be skipped during traversal
@@ -72,76 +236,112 @@ pub fn is_git_ignored(path: &Path) -> bool {
     false
 }
 
-/// Check if a path is safe to delete
-pub fn is_safe_to_delete(path: &Path) -> bool {
-    // Safety checks to prevent accidental deletion of important directories
-    let dangerous_paths = [
-        "/",
-        "/usr",
-        "/bin",
-        "/sbin",
-        "/etc",
-        "/var",
-        "/home",
-        "/root",
-        "/boot",
-        "/dev",
-        "/proc",
-        "/sys",
-        "/tmp",
-        "/Library",
-        "/System",
-        "/Applications",
-        "/Users",
-        "/Volumes",
-        "C:\\",
-        "C:\\Windows",
-        "C:\\Program Files",
-        "C:\\Program Files (x86)",
-        "C:\\Users",
-    ];
+/// Paths that must never be deleted, seeded with system directories and
+/// extendable via the `protected_paths` config setting
+#[derive(Debug, Clone)]
+pub struct ProtectedPaths {
+    paths: Vec<PathBuf>,
+}
 
-    let path_str = path.to_string_lossy();
-    
-    // Check if it's a dangerous system path
-    for dangerous in &dangerous_paths {
-        if path_str == *dangerous {
-            return false;
+impl Default for ProtectedPaths {
+    fn default() -> Self {
+        let paths = [
+            "/",
+            "/usr",
+            "/bin",
+            "/sbin",
+            "/etc",
+            "/var",
+            "/home",
+            "/root",
+            "/boot",
+            "/dev",
+            "/proc",
+            "/sys",
+            "/tmp",
+            "/Library",
+            "/System",
+            "/Applications",
+            "/Users",
+            "/Volumes",
+            "C:\\",
+            "C:\\Windows",
+            "C:\\Program Files",
+            "C:\\Program Files (x86)",
+            "C:\\Users",
+        ];
+
+        Self {
+            paths: paths.iter().map(PathBuf::from).collect(),
+        }
+    }
+}
+
+impl ProtectedPaths {
+    /// Defaults plus `add`, minus any default listed in `remove`
+    pub fn with_overrides(add: &[PathBuf], remove: &[PathBuf]) -> Self {
+        let mut protected = Self::default();
+        protected.paths.retain(|path| !remove.contains(path));
+        for path in add {
+            if !protected.paths.contains(path) {
+                protected.paths.push(path.clone());
+            }
         }
+        protected
     }
 
-    // Check if it's too close to root
-    if path.components().count() < 3 {
+    /// Check if a path is a protected system path or too close to the filesystem root
+    pub fn is_protected(&self, path: &Path) -> bool {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+
+        // Check if it's a dangerous system path
+        if self.paths.contains(&path) {
+            return true;
+        }
+
+        // Check if it's too close to root
+        path.components().count() < 3
+    }
+}
+
+/// Check if a path is safe to delete
+pub fn is_safe_to_delete(path: &Path) -> bool {
+    is_safe_to_delete_with(path, &ProtectedPaths::default())
+}
+
+/// Check if a path is safe to delete, using a custom set of protected paths
+pub fn is_safe_to_delete_with(path: &Path, protected: &ProtectedPaths) -> bool {
+    // Safety checks to prevent accidental deletion of important directories
+    if protected.is_protected(path) {
         return false;
     }
 
     // Additional safety: don't delete if it contains important files
     if path.is_dir() {
-        for entry in std::fs::read_dir(path).unwrap_or_else(|_| {
-            return std::fs::read_dir(".").unwrap(); // Fallback to current dir
-        }) {
-            if let Ok(entry) = entry {
-                let name = entry.file_name();
-                let name_str = name.to_string_lossy();
-                
-                // Check for important files that suggest this isn't a cache directory
-                let important_files = [
-                    "main.rs",
-                    "lib.rs",
-                    "index.js",
-                    "package.json",
-                    "Cargo.toml",
-                    "go.mod",
-                    "requirements.txt",
-                    "setup.py",
-                    "Makefile",
-                    "README.md",
-                    "LICENSE",
-                ];
-
-                if important_files.iter().any(|&important| name_str == important) {
-                    return false;
-                }
+        let entries = std::fs::read_dir(path).unwrap_or_else(|_| {
+            std::fs::read_dir(".").unwrap() // Fallback to current dir
+        });
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name_str = name.to_string_lossy();
+
+            // Check for important files that suggest this isn't a cache directory
+            let important_files = [
+                "main.rs",
+                "lib.rs",
+                "index.js",
+                "package.json",
+                "Cargo.toml",
+                "go.mod",
+                "requirements.txt",
+                "setup.py",
+                "Makefile",
+                "README.md",
+                "LICENSE",
+            ];
+
+            if important_files.iter().any(|&important| name_str == important) {
+                return false;
             }
         }
     }
@@ -149,11 +349,50 @@ pub fn is_safe_to_delete(path: &Path) -> bool {
     true
 }
 
+/// Clear the read-only attribute on a path and everything beneath it
+#[cfg(windows)]
+pub fn clear_readonly(path: &Path) -> std::io::Result<()> {
+    for entry in WalkDir::new(path).follow_links(false).into_iter().flatten() {
+        let metadata = entry.metadata()?;
+        let mut permissions = metadata.permissions();
+        if permissions.readonly() {
+            #[allow(clippy::permissions_set_readonly_false)]
+            permissions.set_readonly(false);
+            std::fs::set_permissions(entry.path(), permissions)?;
+        }
+    }
+    Ok(())
+}
+
 /// Get the size of a file or directory in a human-readable format
 pub fn format_size(size: u64) -> String {
     humansize::format_size(size, humansize::BINARY)
 }
 
+/// Parse a human-readable size such as `10G`, `500MB`, `1.5GiB` or `4096`.
+/// Units are binary (1K = 1024 bytes).
+pub fn parse_size(input: &str) -> Result<u64> {
+    let trimmed = input.trim();
+    let split = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+
+    let value: f64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid size: {}", input))?;
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => return Err(anyhow::anyhow!("Invalid size unit in '{}' (use B, K, M, G or T)", input)),
+    };
+
+    Ok((value * multiplier as f64) as u64)
+}
+
 /// Create a symbolic link
 pub fn create_symlink(original: &Path, link: &Path) -> Result<()> {
     #[cfg(unix)]
@@ -183,15 +422,303 @@ pub fn get_parent_dir(path: &Path) -> Option<PathBuf> {
     path.parent().map(|p| p.to_path_buf())
 }
 
-/// Normalize a path (resolve .. and . components)
+/// Directory for persistent tool state (scan cache, checkpoints).
+/// Uses `$XDG_STATE_HOME/clearcache`, falling back to `~/.local/state/clearcache`.
+pub fn state_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_STATE_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir).join("clearcache"));
+    }
+
+    #[cfg(windows)]
+    {
+        if let Some(dir) = std::env::var_os("LOCALAPPDATA") {
+            return Some(PathBuf::from(dir).join("clearcache").join("state"));
+        }
+    }
+
+    home_dir().map(|home| home.join(".local").join("state").join("clearcache"))
+}
+
+/// Base directory for user caches per the XDG Base Directory spec.
+/// `$XDG_CACHE_HOME` is honored only when set to an absolute path.
+#[cfg(target_os = "linux")]
+pub fn xdg_cache_home() -> Option<PathBuf> {
+    xdg_cache_home_with(|name| std::env::var_os(name))
+}
+
+/// [`xdg_cache_home`] with environment variables read through `var`
+#[cfg(target_os = "linux")]
+pub fn xdg_cache_home_with(var: impl Fn(&str) -> Option<std::ffi::OsString>) -> Option<PathBuf> {
+    var("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| {
+            var("HOME")
+                .or_else(|| var("USERPROFILE"))
+                .filter(|d| !d.is_empty())
+                .map(|home| PathBuf::from(home).join(".cache"))
+        })
+}
+
+/// Whether the block device backing `path` is a spinning disk, when detectable
+#[cfg(target_os = "linux")]
+pub fn is_rotational(path: &Path) -> Option<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let dev = std::fs::metadata(path).ok()?.dev();
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    let device = PathBuf::from(format!("/sys/dev/block/{}:{}", major, minor));
+
+    // Partitions don't have a queue directory of their own; use the parent disk's
+    [device.join("queue/rotational"), device.join("../queue/rotational")]
+        .iter()
+        .find_map(|flag| std::fs::read_to_string(flag).ok())
+        .map(|value| value.trim() == "1")
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_rotational(_path: &Path) -> Option<bool> {
+    None
+}
+
+/// Filesystem type of the mount containing `path` (e.g. `ext4`, `nfs4`), when detectable
+#[cfg(target_os = "linux")]
+pub fn filesystem_type(path: &Path) -> Option<String> {
+    let path = path.canonicalize().ok()?;
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+    mount_fstype(&mountinfo, &path)
+}
+
+/// UNC paths (`\\server\share`) are network shares; local drives report no type
+#[cfg(windows)]
+pub fn filesystem_type(path: &Path) -> Option<String> {
+    let path = path.to_string_lossy();
+    let unc = path.starts_with(r"\\?\UNC\") || (path.starts_with(r"\\") && !path.starts_with(r"\\?\"));
+    unc.then(|| "unc".to_string())
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub fn filesystem_type(_path: &Path) -> Option<String> {
+    None
+}
+
+/// Bytes available to unprivileged users on the filesystem containing `path`
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stats` is a valid out-pointer
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+        return None;
+    }
+    Some(stats.f_bavail as u64 * stats.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Files currently held open by any process we can inspect, from `/proc/<pid>/fd`.
+/// Processes owned by other users are silently skipped when not readable.
+#[cfg(target_os = "linux")]
+pub fn open_files() -> Vec<PathBuf> {
+    let Ok(procs) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    procs
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().bytes().all(|b| b.is_ascii_digit()))
+        .filter_map(|entry| std::fs::read_dir(entry.path().join("fd")).ok())
+        .flat_map(|fds| fds.flatten().filter_map(|fd| std::fs::read_link(fd.path()).ok()))
+        .filter(|target| target.is_absolute())
+        .collect()
+}
+
+/// Open-file detection is only implemented on Linux
+#[cfg(not(target_os = "linux"))]
+pub fn open_files() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+/// The first of `markers` (globs relative to `dir`) showing a build in progress: a directory
+/// that exists, or a lockfile another process holds locked (detected on Unix only)
+pub fn active_build_marker(dir: &Path, markers: &[&str]) -> Option<PathBuf> {
+    let base = glob::Pattern::escape(&dir.to_string_lossy());
+    markers
+        .iter()
+        .filter_map(|marker| glob::glob(&format!("{}/{}", base, marker)).ok())
+        .flat_map(|paths| paths.flatten())
+        .find(|path| {
+            path.is_dir()
+                || path.is_file() && std::fs::File::open(path).is_ok_and(|file| is_locked(&file))
+        })
+}
+
+/// Whether another process holds a `flock` lock on `file`, as Cargo does on `.cargo-lock`.
+/// A lock taken here is released when `file` is closed.
+#[cfg(unix)]
+fn is_locked(file: &std::fs::File) -> bool {
+    use std::os::unix::io::AsRawFd;
+    let status = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    status != 0 && std::io::Error::last_os_error().raw_os_error() == Some(libc::EWOULDBLOCK)
+}
+
+#[cfg(not(unix))]
+fn is_locked(_file: &std::fs::File) -> bool {
+    false
+}
+
+/// The fstype and mount options of the longest mount point containing `path` in a mountinfo table
+#[cfg(target_os = "linux")]
+fn mount_entry(mountinfo: &str, path: &Path) -> Option<(String, String)> {
+    let mut best: Option<(usize, String, String)> = None;
+    for line in mountinfo.lines() {
+        // <id> <parent> <maj:min> <root> <mount point> <options> [optional...] - <fstype> <source> <superopts>
+        let Some((left, right)) = line.split_once(" - ") else {
+            continue;
+        };
+        let mut fields = left.split(' ').skip(4);
+        let (Some(mount_point), Some(options), Some(fstype)) = (fields.next(), fields.next(), right.split(' ').next()) else {
+            continue;
+        };
+        let mount_point = PathBuf::from(mount_point.replace("\\040", " "));
+        let depth = mount_point.components().count();
+        if path.starts_with(&mount_point) && best.as_ref().is_none_or(|(d, _, _)| depth >= *d) {
+            best = Some((depth, fstype.to_string(), options.to_string()));
+        }
+    }
+    best.map(|(_, fstype, options)| (fstype, options))
+}
+
+#[cfg(target_os = "linux")]
+fn mount_fstype(mountinfo: &str, path: &Path) -> Option<String> {
+    mount_entry(mountinfo, path).map(|(fstype, _)| fstype)
+}
+
+/// Whether reading a file under `path` updates its access time. `relatime` (the Linux default)
+/// still does, at most once a day; `noatime` never does. `None` when the mount can't be found.
+#[cfg(target_os = "linux")]
+pub fn records_access_time(path: &Path) -> Option<bool> {
+    let path = path.canonicalize().ok()?;
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+    mount_records_access_time(&mountinfo, &path)
+}
+
+#[cfg(target_os = "linux")]
+fn mount_records_access_time(mountinfo: &str, path: &Path) -> Option<bool> {
+    mount_entry(mountinfo, path).map(|(_, options)| !options.split(',').any(|option| option == "noatime"))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn records_access_time(_path: &Path) -> Option<bool> {
+    None
+}
+
+/// Check that `path` could be deleted without actually deleting it: its parent and every
+/// directory inside it must be writable and searchable. Symlinks are not followed.
+pub fn can_delete(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        check_writable_dir(parent)?;
+    }
+    if !std::fs::symlink_metadata(path)?.is_dir() {
+        return Ok(());
+    }
+    for entry in WalkDir::new(path).follow_links(false) {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            check_writable_dir(entry.path())?;
+        } else if cfg!(windows) && entry.metadata()?.permissions().readonly() {
+            // Windows refuses to delete read-only files (unless --clear-readonly)
+            return Err(anyhow::anyhow!("{} is read-only", entry.path().display()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+pub(crate) fn check_writable_dir(dir: &Path) -> Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    let c_path = std::ffi::CString::new(dir.as_os_str().as_bytes())?;
+    // Removing entries needs write and search permission on the containing directory
+    if unsafe { libc::access(c_path.as_ptr(), libc::W_OK | libc::X_OK | libc::R_OK) } != 0 {
+        return Err(anyhow::anyhow!(
+            "no write permission on {}: {}",
+            dir.display(),
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn check_writable_dir(dir: &Path) -> Result<()> {
+    if std::fs::metadata(dir)?.permissions().readonly() {
+        return Err(anyhow::anyhow!("{} is read-only", dir.display()));
+    }
+    Ok(())
+}
+
+/// Whether a filesystem type reported by [`filesystem_type`] is network-backed
+pub fn is_network_filesystem(fstype: &str) -> bool {
+    matches!(
+        fstype,
+        "nfs" | "nfs4" | "cifs" | "smb3" | "smbfs" | "9p" | "afs" | "ceph" | "glusterfs" | "davfs" | "unc"
+    ) || fstype.starts_with("fuse.sshfs")
+        || fstype.starts_with("fuse.rclone")
+}
+
+/// Directory for user configuration.
+/// Uses `$XDG_CONFIG_HOME/clearcache`, falling back to `~/.config/clearcache`.
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir).join("clearcache"));
+    }
+
+    #[cfg(windows)]
+    {
+        if let Some(dir) = std::env::var_os("APPDATA") {
+            return Some(PathBuf::from(dir).join("clearcache"));
+        }
+    }
+
+    home_dir().map(|home| home.join(".config").join("clearcache"))
+}
+
+/// Current user's home directory
+pub fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Modification time of a path in nanoseconds since the Unix epoch
+pub fn mtime_nanos(path: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    let since_epoch = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some(since_epoch.as_nanos() as u64)
+}
+
+/// Normalize a path lexically (resolve `..` and `.` components) without touching the
+/// filesystem, so symlinks are not followed as `canonicalize` would.
+///
+/// `..` at the start of a relative path is kept; `..` at the root is dropped.
 pub fn normalize_path(path: &Path) -> PathBuf {
     let mut components = Vec::new();
-    
+
     for component in path.components() {
         match component {
-            std::path::Component::ParentDir => {
-                components.pop();
-            }
+            std::path::Component::ParentDir => match components.last() {
+                Some(std::path::Component::Normal(_)) => {
+                    components.pop();
+                }
+                Some(std::path::Component::RootDir | std::path::Component::Prefix(_)) => {}
+                _ => components.push(component),
+            },
             std::path::Component::CurDir => {
                 // Skip current directory components
             }
@@ -200,6 +727,170 @@ pub fn normalize_path(path: &Path) -> PathBuf {
             }
         }
     }
-    
+
+    if components.is_empty() {
+        return PathBuf::from(".");
+    }
     components.iter().collect()
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_in_vcs_dir() {
+        let root = Path::new("/work/app");
+        assert!(is_in_vcs_dir(Path::new("/work/app/.git"), root));
+        assert!(is_in_vcs_dir(Path::new("/work/app/lib/.svn/tmp"), root));
+        assert!(!is_in_vcs_dir(Path::new("/work/app/.github/cache"), root));
+        // A root inside .git was named explicitly
+        assert!(!is_in_vcs_dir(Path::new("/work/app/.git/logs"), Path::new("/work/app/.git")));
+    }
+
+    #[test]
+    fn test_normalize_path_is_lexical() {
+        assert_eq!(normalize_path(Path::new("a/./b/../c")), PathBuf::from("a/c"));
+        assert_eq!(normalize_path(Path::new("../a/./b/..")), PathBuf::from("../a"));
+        assert_eq!(normalize_path(Path::new("a/..")), PathBuf::from("."));
+        assert_eq!(normalize_path(Path::new("/../a")), PathBuf::from("/a"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_normalize_path_keeps_symlinks() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("real/inner")).unwrap();
+        std::os::unix::fs::symlink(root.join("real/inner"), root.join("link")).unwrap();
+
+        // The link stays in the path, and `..` after it means its parent, not the target's
+        assert_eq!(normalize_path(&root.join("./link/.")), root.join("link"));
+        assert_eq!(normalize_path(&root.join("link/../real")), root.join("real"));
+        assert_eq!(root.join("link/..").canonicalize().unwrap(), root.join("real").canonicalize().unwrap());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_mount_fstype_picks_longest_mount() {
+        let mountinfo = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+40 22 0:50 / /mnt/shared\\040drive rw,relatime shared:20 - nfs4 server:/export rw
+41 40 0:51 / /mnt/shared\\040drive/local rw - tmpfs tmpfs rw";
+        let fstype = |path: &str| mount_fstype(mountinfo, Path::new(path));
+        assert_eq!(fstype("/home/me/project").as_deref(), Some("ext4"));
+        assert_eq!(fstype("/mnt/shared drive/app/node_modules").as_deref(), Some("nfs4"));
+        assert_eq!(fstype("/mnt/shared drive/local/cache").as_deref(), Some("tmpfs"));
+        assert!(is_network_filesystem("nfs4"));
+        assert!(!is_network_filesystem("ext4"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_noatime_mounts_do_not_record_access_time() {
+        let mountinfo = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+30 22 8:2 / /home rw,noatime shared:2 - ext4 /dev/sda2 rw
+31 22 8:3 / /srv rw,strictatime shared:3 - xfs /dev/sda3 rw";
+        let records = |path: &str| mount_records_access_time(mountinfo, Path::new(path));
+        assert_eq!(records("/var/cache"), Some(true));
+        assert_eq!(records("/home/me/.cargo/registry"), Some(false));
+        assert_eq!(records("/srv/store"), Some(true));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_directory_size_counts_hard_links_once() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("store")).unwrap();
+        std::fs::create_dir_all(root.join("project")).unwrap();
+        std::fs::write(root.join("store/pkg.js"), vec![0u8; 1000]).unwrap();
+        std::fs::hard_link(root.join("store/pkg.js"), root.join("project/pkg.js")).unwrap();
+        std::fs::hard_link(root.join("store/pkg.js"), root.join("project/pkg-copy.js")).unwrap();
+        std::fs::write(root.join("project/own.js"), vec![0u8; 10]).unwrap();
+
+        use std::os::unix::fs::MetadataExt;
+        let blocks = |name: &str| std::fs::metadata(root.join(name)).unwrap().blocks() * 512;
+        let size = calculate_directory_size(root).unwrap();
+        assert_eq!(size, DirectorySize {
+            files: 4,
+            apparent: 3010,
+            actual: blocks("store/pkg.js") + blocks("project/own.js"),
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_directory_size_of_sparse_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file = std::fs::File::create(temp_dir.path().join("sparse.img")).unwrap();
+        file.set_len(64 * 1024 * 1024).unwrap();
+
+        let size = calculate_directory_size(temp_dir.path()).unwrap();
+        assert_eq!(size.apparent, 64 * 1024 * 1024);
+        assert!(size.actual < size.apparent);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert_eq!(parse_size("10G").unwrap(), 10 << 30);
+        assert_eq!(parse_size("500MB").unwrap(), 500 << 20);
+        assert_eq!(parse_size("1.5GiB").unwrap(), 3 << 29);
+        assert!(parse_size("12 parsecs").is_err());
+    }
+
+    #[test]
+    fn test_protected_paths_configurable() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir_all(&data_dir).unwrap();
+
+        assert!(is_safe_to_delete(&data_dir));
+
+        let protected = ProtectedPaths::with_overrides(std::slice::from_ref(&data_dir), &[]);
+        assert!(!is_safe_to_delete_with(&data_dir, &protected));
+
+        // Defaults and the near-root heuristic still apply
+        assert!(protected.is_protected(Path::new("/usr")));
+        assert!(protected.is_protected(Path::new("/opt")));
+
+        // Relaxing a default keeps the near-root heuristic
+        let relaxed = ProtectedPaths::with_overrides(&[], &[PathBuf::from("/home")]);
+        assert!(relaxed.is_protected(Path::new("/home")));
+        assert!(!relaxed.paths.contains(&PathBuf::from("/home")));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_xdg_cache_home_override() {
+        let env = |cache: &'static str| {
+            move |name: &str| match name {
+                "XDG_CACHE_HOME" if !cache.is_empty() => Some(cache.into()),
+                "HOME" => Some("/home/user".into()),
+                _ => None,
+            }
+        };
+
+        assert_eq!(xdg_cache_home_with(env("/srv/cache")), Some(PathBuf::from("/srv/cache")));
+        assert_eq!(xdg_cache_home_with(env("")), Some(PathBuf::from("/home/user/.cache")));
+        // Relative values are invalid per the spec and fall back to ~/.cache
+        assert_eq!(xdg_cache_home_with(env("relative/cache")), Some(PathBuf::from("/home/user/.cache")));
+        assert_eq!(xdg_cache_home_with(|_| None), None);
+    }
+
+    #[test]
+    fn test_managed_system_paths() {
+        assert_eq!(managed_by(Path::new("/nix/store/abc123-python3-3.11/lib/__pycache__")), Some("Nix"));
+        assert!(is_managed_system_path(Path::new("/nix/store")));
+        assert!(is_managed_system_path(Path::new("/opt/homebrew/lib/node_modules/npm/node_modules")));
+        assert!(is_managed_system_path(Path::new("/usr/local/Cellar/python@3.12/3.12.1/build")));
+        assert_eq!(managed_by(Path::new("/usr/lib/python3/dist-packages/yaml/__pycache__")), Some("the system Python"));
+
+        // Only the store itself, and only system Pythons
+        assert!(!is_managed_system_path(Path::new("/nix/store-backup/build")));
+        assert!(!is_managed_system_path(Path::new("/home/dev/app/.venv/lib/python3.12/site-packages/__pycache__")));
+        assert!(!is_managed_system_path(Path::new("/usr/lib/node_modules")));
+    }
+}
@@ -0,0 +1,49 @@
+/// Semantic version of this build
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Short git commit hash, empty when built outside a git checkout
+pub const GIT_SHA: &str = env!("CLEARCACHE_GIT_SHA");
+
+/// UTC build date (`YYYY-MM-DD`)
+pub const BUILD_DATE: &str = env!("CLEARCACHE_BUILD_DATE");
+
+/// `rustc --version` of the compiler that produced this build
+pub const RUSTC_VERSION: &str = env!("CLEARCACHE_RUSTC_VERSION");
+
+/// Version string for `--version`; `verbose` adds build metadata for bug reports
+pub fn version_string(verbose: bool) -> String {
+    let mut version = format!("clearcache {}", VERSION);
+    if !GIT_SHA.is_empty() {
+        version.push_str(&format!(" ({})", GIT_SHA));
+    }
+
+    if verbose {
+        version.push_str(&format!("\ncommit: {}", if GIT_SHA.is_empty() { "unknown" } else { GIT_SHA }));
+        version.push_str(&format!("\nbuild date: {}", BUILD_DATE));
+        if !RUSTC_VERSION.is_empty() {
+            version.push_str(&format!("\nrustc: {}", RUSTC_VERSION));
+        }
+        version.push_str(&format!("\ntarget: {}-{}", std::env::consts::ARCH, std::env::consts::OS));
+    }
+
+    version
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_string_contains_build_metadata() {
+        let short = version_string(false);
+        assert!(short.contains(env!("CARGO_PKG_VERSION")));
+
+        let verbose = version_string(true);
+        assert!(verbose.contains(env!("CARGO_PKG_VERSION")));
+        assert!(verbose.contains("build date: "));
+        if !GIT_SHA.is_empty() {
+            assert!(short.contains(GIT_SHA));
+            assert!(verbose.contains(&format!("commit: {}", GIT_SHA)));
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! Process exit codes, as scripts and CI see them
+
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+/// Run the binary with an isolated config directory, so no user ignore file or config applies
+fn clearcache(config_home: &Path, args: &[&str]) -> i32 {
+    let output = Command::new(env!("CARGO_BIN_EXE_clearcache"))
+        .args(args)
+        .env("XDG_CONFIG_HOME", config_home)
+        .output()
+        .unwrap();
+    output.status.code().unwrap()
+}
+
+fn project_with_caches(root: &Path) {
+    for dir in ["app/__pycache__", "app/tests/__pycache__"] {
+        std::fs::create_dir_all(root.join(dir)).unwrap();
+        std::fs::write(root.join(dir).join("cached"), vec![0u8; 4096]).unwrap();
+    }
+}
+
+#[test]
+fn test_clean_run_exits_zero() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("code");
+    project_with_caches(&root);
+    let root = root.to_str().unwrap();
+
+    assert_eq!(clearcache(temp_dir.path(), &[root, "--recursive", "--dry-run"]), 0);
+    assert_eq!(clearcache(temp_dir.path(), &[root, "--recursive"]), 0);
+}
+
+#[test]
+fn test_safety_limits_exit_one() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("code");
+    project_with_caches(&root);
+    let root = root.to_str().unwrap();
+
+    assert_eq!(clearcache(temp_dir.path(), &[root, "--recursive", "--budget", "1"]), 1);
+    assert_eq!(clearcache(temp_dir.path(), &[root, "--recursive", "--budget", "1G"]), 0);
+    assert_eq!(clearcache(temp_dir.path(), &[root, "--recursive", "--max-total-deletions", "1"]), 1);
+    assert!(Path::new(root).join("app/__pycache__").exists());
+}
+
+/// A failing item makes the run exit 1 unless `--ignore-errors` is given. `cargo clean` is
+/// replaced by a script that fails, since deleting as root cannot be made to fail portably.
+#[cfg(unix)]
+#[test]
+fn test_failed_items_exit_one_unless_ignored() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let bin = temp_dir.path().join("bin");
+    std::fs::create_dir_all(&bin).unwrap();
+    let cargo = bin.join("cargo");
+    std::fs::write(
+        &cargo,
+        "#!/bin/sh\n\
+         if [ \"$1\" = metadata ]; then echo \"{\\\"target_directory\\\":\\\"$PWD/target\\\"}\"; exit 0; fi\n\
+         echo 'error: failed to remove file' >&2\n\
+         exit 101\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&cargo, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default());
+
+    let run = |ignore_errors: bool| {
+        let root = temp_dir.path().join(format!("app-{}", ignore_errors));
+        std::fs::create_dir_all(root.join("target/debug")).unwrap();
+        std::fs::write(root.join("Cargo.toml"), "[package]").unwrap();
+        let mut command = Command::new(env!("CARGO_BIN_EXE_clearcache"));
+        command
+            .arg(&root)
+            .args(["--types", "rust", "--include-libraries", "--use-native-clean", "--force"])
+            .env("XDG_CONFIG_HOME", temp_dir.path())
+            .env("PATH", &path);
+        if ignore_errors {
+            command.arg("--ignore-errors");
+        }
+        command.output().unwrap().status.code().unwrap()
+    };
+
+    assert_eq!(run(false), 1);
+    assert_eq!(run(true), 0);
+}
+
+#[test]
+fn test_invalid_arguments_exit_two() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().to_str().unwrap();
+
+    for depth in ["abc", "-1"] {
+        assert_eq!(clearcache(temp_dir.path(), &[root, "--dry-run", "--max-depth", depth]), 2);
+    }
+    for flag in ["--parallel", "--io-concurrency"] {
+        for count in ["0", "abc"] {
+            assert_eq!(clearcache(temp_dir.path(), &[root, "--dry-run", flag, count]), 2);
+        }
+    }
+
+    // --target-free sizes every item to order them and to know when to stop
+    let args = [root, "--dry-run", "--target-free", "1G", "--no-recursive-size"];
+    assert_eq!(clearcache(temp_dir.path(), &args), 2);
+}
+
+#[test]
+fn test_version_ignores_roots() {
+    let temp_dir = TempDir::new().unwrap();
+    let missing = temp_dir.path().join("missing");
+    let missing = missing.to_str().unwrap();
+
+    assert_eq!(clearcache(temp_dir.path(), &[missing, "--dereference", "--version"]), 0);
+    assert_eq!(clearcache(temp_dir.path(), &["--from-stdin", "--version"]), 0);
+}
+
+#[test]
+fn test_cancelled_run_still_writes_summaries() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("code");
+    project_with_caches(&root);
+    let summary = temp_dir.path().join("summary.json");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_clearcache"))
+        .arg(&root)
+        .args(["--recursive", "--max-total-deletions", "1", "--machine-summary", "--summary-json-to"])
+        .arg(&summary)
+        .env("XDG_CONFIG_HOME", temp_dir.path())
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("clearcache: freed=0 items=0 errors=1"), "{}", stderr);
+    let report: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&summary).unwrap()).unwrap();
+    assert_eq!(report["status"], "aborted");
+}
+
+#[test]
+fn test_json_report_lists_every_root() {
+    let temp_dir = TempDir::new().unwrap();
+    let (a, b) = (temp_dir.path().join("a"), temp_dir.path().join("b"));
+    project_with_caches(&a);
+    project_with_caches(&b);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_clearcache"))
+        .args([&a, &b])
+        .args(["--recursive", "--dry-run", "--format", "json"])
+        .env("XDG_CONFIG_HOME", temp_dir.path())
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["roots"], serde_json::json!([a, b]));
+    assert_eq!(report["items"].as_array().unwrap().len(), 4);
+}
+
+#[test]
+fn test_tui_rejects_several_roots() {
+    let temp_dir = TempDir::new().unwrap();
+    let (a, b) = (temp_dir.path().join("a"), temp_dir.path().join("b"));
+    std::fs::create_dir_all(&a).unwrap();
+    std::fs::create_dir_all(&b).unwrap();
+
+    assert_eq!(clearcache(temp_dir.path(), &[a.to_str().unwrap(), b.to_str().unwrap(), "--tui"]), 1);
+}